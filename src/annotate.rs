@@ -0,0 +1,27 @@
+use pinyin::ToPinyin;
+
+/// the pinyin with tone marks of `c`, none for punctuation and non chinese characters
+pub fn pinyin(c: char) -> Option<&'static str> {
+    c.to_pinyin().map(|p| p.with_tone())
+}
+
+/// one cell per character of `line`: the pinyin and how many columns the cell takes,
+/// a chinese character is two columns wide
+pub(crate) fn pinyin_cells(line: &str) -> Vec<(char, &'static str, usize)> {
+    line.chars()
+        .map(|c| {
+            let py = pinyin(c).unwrap_or("");
+            (c, py, py.chars().count().max(2) + 1)
+        })
+        .collect()
+}
+
+/// a key ordering chinese text by pinyin, characters without pinyin are ordered by themselves
+pub(crate) fn sort_key(s: &str) -> Vec<String> {
+    s.chars()
+        .map(|c| {
+            c.to_pinyin()
+                .map_or_else(|| c.to_string(), |p| p.plain().to_string())
+        })
+        .collect()
+}
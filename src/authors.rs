@@ -0,0 +1,109 @@
+use crate::{
+    output::{Render, Style},
+    PoemError,
+};
+use anyhow::{Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+const AUTHORS_STR: &str = include_str!("../authors.json");
+
+/// what is known about an author of the poems
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Author {
+    pub name: String,
+    pub dynasty: String,
+    /// the year of birth, negative before the common era
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub birth: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub death: Option<i32>,
+    /// 字
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub courtesy_name: Option<String>,
+    #[serde(default)]
+    pub biography: String,
+}
+
+impl Author {
+    /// the years of the life like `701—762`, `?` for the unknown one, none if both are unknown
+    pub fn years(&self) -> Option<String> {
+        let year = |y: Option<i32>| y.map_or("?".to_string(), |y| y.to_string());
+        (self.birth.is_some() || self.death.is_some())
+            .then(|| format!("{}—{}", year(self.birth), year(self.death)))
+    }
+}
+
+/// the authors of a dataset in the format of the bundled `authors.json`
+#[derive(Debug, Default, Clone)]
+pub struct Authors {
+    authors: Vec<Author>,
+}
+
+impl Authors {
+    /// the bundled authors
+    pub fn bundled() -> Result<Self> {
+        Ok(Self {
+            authors: serde_json::from_str(AUTHORS_STR)?,
+        })
+    }
+
+    /// the authors from a json file, or the bundled ones if no file is given
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => return Self::bundled(),
+        };
+        let s = fs::read_to_string(path).map_err(PoemError::io(path))?;
+        Ok(Self {
+            authors: serde_json::from_str(&s)
+                .map_err(PoemError::from)
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+        })
+    }
+
+    /// the author of `name` in `dynasty`, or the only one of `name` since datasets
+    /// do not always write a dynasty the same way
+    pub fn get(&self, name: &str, dynasty: &str) -> Option<&Author> {
+        let named = self.named(name);
+        match named.iter().find(|a| a.dynasty == dynasty) {
+            Some(author) => Some(author),
+            None if named.len() == 1 => Some(named[0]),
+            None => None,
+        }
+    }
+
+    /// the years the author of `name` in `dynasty` lived, from the birth to the death,
+    /// the known one for both if only one is known
+    pub(crate) fn active_years(&self, name: &str, dynasty: &str) -> Option<(i32, i32)> {
+        let author = self.get(name, dynasty)?;
+        let from = author.birth.or(author.death)?;
+        Some((from, author.death.unwrap_or(from)))
+    }
+
+    /// every author of `name`, there can be one in each dynasty
+    pub fn named(&self, name: &str) -> Vec<&Author> {
+        self.authors.iter().filter(|a| a.name == name).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Author> {
+        self.authors.iter()
+    }
+}
+
+impl Render for Author {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(f, "{}〔{}〕", self.name.bright_cyan(), self.dynasty.cyan())?;
+        if let Some(years) = self.years() {
+            write!(f, " {}", years)?;
+        }
+        if let Some(courtesy_name) = &self.courtesy_name {
+            write!(f, "\n字{}", courtesy_name)?;
+        }
+        if !self.biography.is_empty() {
+            write!(f, "\n{}", self.biography)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    collection::{COLLECTIONS_DIR, INDEX_DIR},
+    config::CONFIG_FILE,
+    favorites::FAVORITES_FILE,
+    fetch::DATASET_FILE,
+    history::HISTORY_FILE,
+    memorize::MEMORIZE_FILE,
+    notes::NOTES_FILE,
+    profile,
+    tags::TAGS_FILE,
+    user_poems::USER_POEMS_FILE,
+};
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    path::{Component, Path},
+    process::{Command, Output},
+};
+
+/// the files of the data dir kept in a backup, the indexes are left out since they can be rebuilt
+const FILES: &[&str] = &[
+    USER_POEMS_FILE,
+    FAVORITES_FILE,
+    TAGS_FILE,
+    NOTES_FILE,
+    MEMORIZE_FILE,
+    HISTORY_FILE,
+    CONFIG_FILE,
+    DATASET_FILE,
+    COLLECTIONS_DIR,
+];
+
+pub(crate) fn tar(args: &[&str]) -> Result<Output> {
+    let output = Command::new("tar")
+        .args(args)
+        .output()
+        .context("failed to run tar, is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output)
+}
+
+pub(crate) fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("{} is not valid utf-8", path.display()))
+}
+
+/// write the user data to the gzipped tar archive `out`, returns the files and directories in it
+pub fn backup(out: &Path) -> Result<Vec<String>> {
+    let dir = profile::data_dir()?;
+    let files: Vec<String> = FILES
+        .iter()
+        .filter(|f| dir.join(f).exists())
+        .map(|f| f.to_string())
+        .collect();
+    if files.is_empty() {
+        return Err(anyhow!("nothing to back up in {}", dir.display()));
+    }
+    // 相对路径的输出文件要按当前目录算 tar的-C会改变目录
+    let out = std::env::current_dir()?.join(out);
+    let exclude = format!("--exclude={}/*/{}", COLLECTIONS_DIR, INDEX_DIR);
+    let mut args = vec!["-czf", path_str(&out)?, &exclude, "-C", path_str(&dir)?];
+    args.extend(files.iter().map(String::as_str));
+    tar(&args)?;
+    Ok(files)
+}
+
+/// put the user data in the archive `archive` back into the data dir, replacing the files
+/// there of the same name, returns the files restored
+pub fn restore(archive: &Path) -> Result<Vec<String>> {
+    let archive = &std::env::current_dir()?.join(archive);
+    let listing = tar(&["-tzf", path_str(archive)?])?;
+    let entries: Vec<String> = String::from_utf8_lossy(&listing.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    if let Some(entry) = entries.iter().find(|e| !restorable(e)) {
+        return Err(anyhow!(
+            "{} is not a backup, it has {}",
+            archive.display(),
+            entry
+        ));
+    }
+    let dir = profile::data_dir()?;
+    fs::create_dir_all(&dir)?;
+    tar(&["-xzf", path_str(archive)?, "-C", path_str(&dir)?])?;
+    Ok(entries.into_iter().filter(|e| !e.ends_with('/')).collect())
+}
+
+/// whether the entry of an archive is one of the files a backup has, and can not be written
+/// outside of the data dir
+fn restorable(entry: &str) -> bool {
+    // 只认备份里会有的文件 不能写到数据目录外面去
+    let path = Path::new(entry);
+    let known = path
+        .components()
+        .next()
+        .is_some_and(|c| FILES.iter().any(|f| c.as_os_str() == *f));
+    let inside = path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    known && inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_of_a_backup_are_restorable() {
+        assert!(restorable("poems.json"));
+        assert!(restorable("favorites.json"));
+        assert!(restorable("collections/"));
+        assert!(restorable("collections/唐诗/poems.json"));
+    }
+
+    #[test]
+    fn other_entries_are_refused() {
+        assert!(!restorable("notes.txt"));
+        assert!(!restorable("../poems.json"));
+        assert!(!restorable("/etc/passwd"));
+        assert!(!restorable("collections/../../.bashrc"));
+        assert!(!restorable("./poems.json"));
+        assert!(!restorable(""));
+    }
+}
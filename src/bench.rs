@@ -0,0 +1,157 @@
+use crate::{
+    build_index,
+    output::{Render, Style},
+    Authors, Poem, PoemFilter, PoemRepository, SearchOptions, TokenizerConfig,
+};
+use anyhow::{anyhow, Result};
+use indicatif::ProgressBar;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+use std::{
+    env, fmt, fs, process,
+    time::{Duration, Instant},
+};
+
+/// what `bench` runs
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// how many random queries to search
+    pub queries: usize,
+    /// how many random poems to pick
+    pub samples: usize,
+    pub tokenizer: TokenizerConfig,
+    pub threads: Option<usize>,
+    /// the same seed picks the same queries and poems, so runs can be compared
+    pub seed: u64,
+}
+
+/// how fast a workload ran
+#[derive(Debug, Serialize)]
+pub struct Workload {
+    /// index, search or sample
+    pub name: &'static str,
+    pub runs: usize,
+    /// poems indexed a second for index, runs a second for the others
+    pub per_second: f64,
+    /// the latencies of a run in milliseconds
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Workload {
+    fn new(name: &'static str, mut latencies: Vec<Duration>, units: usize) -> Self {
+        latencies.sort();
+        let total: Duration = latencies.iter().sum();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let percentile = |p: f64| {
+            let rank = (p * latencies.len() as f64).ceil() as usize;
+            ms(latencies[rank.clamp(1, latencies.len()) - 1])
+        };
+        Self {
+            name,
+            runs: latencies.len(),
+            per_second: units as f64 / total.as_secs_f64().max(f64::EPSILON),
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+            p99_ms: percentile(0.99),
+            max_ms: ms(*latencies.last().unwrap()),
+        }
+    }
+}
+
+/// index `poems` with the years of their authors in `authors` in a temporary directory,
+/// then search it for random words of them and pick random poems from it, timing each run
+pub fn bench(poems: Vec<Poem>, authors: &Authors, opts: &BenchOptions) -> Result<Vec<Workload>> {
+    if poems.is_empty() {
+        return Err(anyhow!("there are no poems to run the benchmarks with"));
+    }
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    let queries: Vec<String> = (0..opts.queries)
+        .filter_map(|_| random_word(&poems[rng.gen_range(0..poems.len())], &mut rng))
+        .collect();
+
+    let dir = env::temp_dir().join(format!("poem-bench-{}", process::id()));
+    let count = poems.len();
+    let begin = Instant::now();
+    let built = build_index(
+        &dir,
+        poems.clone(),
+        authors,
+        true,
+        Some(opts.tokenizer.clone()),
+        opts.threads,
+        &ProgressBar::hidden(),
+    );
+    let indexing = begin.elapsed();
+    // 不管成败都把临时索引删掉
+    let result = built.and_then(|_| {
+        let repo = PoemRepository::open(&dir)?.with_poems(poems);
+        let mut workloads = vec![Workload::new("index", vec![indexing], count)];
+        if !queries.is_empty() {
+            let opts = SearchOptions::default();
+            let latencies = queries
+                .iter()
+                .map(|q| time(|| repo.search(q, &opts)))
+                .collect::<Result<Vec<_>>>()?;
+            workloads.push(Workload::new("search", latencies, queries.len()));
+        }
+        if opts.samples > 0 {
+            let filter = PoemFilter::default();
+            let latencies = (0..opts.samples)
+                .map(|_| time(|| Ok(repo.random_with(1, &filter, &mut rng))))
+                .collect::<Result<Vec<_>>>()?;
+            workloads.push(Workload::new("sample", latencies, opts.samples));
+        }
+        Ok(workloads)
+    });
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    result
+}
+
+fn time<T>(f: impl FnOnce() -> Result<T>) -> Result<Duration> {
+    let begin = Instant::now();
+    f()?;
+    Ok(begin.elapsed())
+}
+
+/// two characters in a row of the content of `poem`, none if it has no two without punctuation between
+fn random_word(poem: &Poem, rng: &mut impl Rng) -> Option<String> {
+    let chars: Vec<char> = poem.content.chars().collect();
+    let starts: Vec<usize> = chars
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| w.iter().all(|c| c.is_alphanumeric()))
+        .map(|(i, _)| i)
+        .collect();
+    if starts.is_empty() {
+        return None;
+    }
+    let start = starts[rng.gen_range(0..starts.len())];
+    Some(chars[start..start + 2].iter().collect())
+}
+
+impl Render for Workload {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        let (name, unit) = match self.name {
+            "index" => ("建索引", "首"),
+            "search" => ("搜索", "次"),
+            _ => ("随机抽取", "次"),
+        };
+        write!(
+            f,
+            "{}：{}次，每秒{:.1}{}，p50 {:.3}ms，p90 {:.3}ms，p99 {:.3}ms，最慢 {:.3}ms",
+            name,
+            self.runs,
+            self.per_second,
+            unit,
+            self.p50_ms,
+            self.p90_ms,
+            self.p99_ms,
+            self.max_ms
+        )
+    }
+}
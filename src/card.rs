@@ -0,0 +1,106 @@
+use crate::{export::escape_text, Poem};
+use std::fmt::Write;
+
+const FONT_SIZE: usize = 32;
+const MARGIN: usize = 64;
+
+/// how a poem card looks
+#[derive(clap::Args, Debug, Clone)]
+pub struct CardStyle {
+    /// the background color, any css color
+    #[clap(long, default_value = "#f5efe0")]
+    pub background: String,
+    /// the color of the text, any css color
+    #[clap(long, default_value = "#333333")]
+    pub color: String,
+    /// the font family of the text
+    #[clap(long, default_value = "serif")]
+    pub font: String,
+}
+
+/// `poem` as an svg image to share, the lines from top to bottom
+/// or in columns from right to left if `vertical`
+pub fn svg(poem: &Poem, style: &CardStyle, vertical: bool) -> String {
+    let lines: Vec<&str> = poem.content.trim().lines().map(str::trim).collect();
+    let subtitle = format!("{}〔{}〕", poem.author, poem.dynasty);
+    let mut texts = String::new();
+    let (width, height) = if vertical {
+        // 每列一句 从右往左是标题 作者 正文
+        let columns: Vec<(&str, usize)> =
+            [(&poem.title[..], FONT_SIZE), (&subtitle, FONT_SIZE * 3 / 4)]
+                .into_iter()
+                .chain(lines.iter().map(|l| (*l, FONT_SIZE)))
+                .collect();
+        let step = FONT_SIZE * 2;
+        let rows = columns
+            .iter()
+            .map(|(c, _)| c.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = MARGIN * 2 + step * columns.len();
+        let height = MARGIN * 2 + rows * FONT_SIZE * 6 / 5;
+        for (i, (column, size)) in columns.iter().enumerate() {
+            let x = width - MARGIN - step * i - step / 2;
+            for (j, c) in column.chars().enumerate() {
+                let y = MARGIN + j * FONT_SIZE * 6 / 5 + size;
+                text(&mut texts, x, y, *size, &c.to_string());
+            }
+        }
+        (width, height)
+    } else {
+        let chars = lines
+            .iter()
+            .chain([&poem.title[..], &subtitle].iter())
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = MARGIN * 2 + chars * FONT_SIZE;
+        let mut y = MARGIN + FONT_SIZE;
+        text(&mut texts, width / 2, y, FONT_SIZE * 5 / 4, &poem.title);
+        y += FONT_SIZE * 3 / 2;
+        text(&mut texts, width / 2, y, FONT_SIZE * 3 / 4, &subtitle);
+        y += FONT_SIZE;
+        for line in &lines {
+            y += FONT_SIZE * 9 / 5;
+            text(&mut texts, width / 2, y, FONT_SIZE, line);
+        }
+        (width, y + MARGIN)
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        attr(&style.background)
+    );
+    let _ = writeln!(
+        svg,
+        r#"<g font-family="{}" fill="{}" text-anchor="middle">"#,
+        attr(&style.font),
+        attr(&style.color)
+    );
+    svg.push_str(&texts);
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+fn text(svg: &mut String, x: usize, y: usize, size: usize, s: &str) {
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="{}">{}</text>"#,
+        x,
+        y,
+        size,
+        escape_text(s)
+    );
+}
+
+fn attr(s: &str) -> String {
+    escape_text(s).replace('"', "&quot;")
+}
@@ -0,0 +1,135 @@
+use crate::Poem;
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+/// the form of a poem told by the number and lengths of its lines
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Form {
+    /// 五言绝句: four lines of five characters
+    Wujue,
+    /// 七言绝句: four lines of seven characters
+    Qijue,
+    /// 五言律诗: eight lines of five characters
+    Wulv,
+    /// 七言律诗: eight lines of seven characters
+    Qilv,
+    /// 词: lines of different lengths to a tune
+    Ci,
+    /// 古体: anything else
+    Guti,
+}
+
+impl Form {
+    /// the chinese name, also used in the index
+    pub fn name(self) -> &'static str {
+        match self {
+            Form::Wujue => "五言绝句",
+            Form::Qijue => "七言绝句",
+            Form::Wulv => "五言律诗",
+            Form::Qilv => "七言律诗",
+            Form::Ci => "词",
+            Form::Guti => "古体",
+        }
+    }
+}
+
+impl Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// the form of `poem`
+///
+/// 律诗 and 绝句 also have rules for tones and rhymes, only the shape is checked here
+pub fn classify(poem: &Poem) -> Form {
+    let lengths: Vec<usize> = poem.lines().iter().map(|l| l.chars().count()).collect();
+    let same = lengths.windows(2).all(|w| w[0] == w[1]);
+    match (same, lengths.first(), lengths.len()) {
+        (true, Some(5), 4) => Form::Wujue,
+        (true, Some(7), 4) => Form::Qijue,
+        (true, Some(5), 8) => Form::Wulv,
+        (true, Some(7), 8) => Form::Qilv,
+        (true, _, _) => Form::Guti,
+        // 长短句多是词 词牌和题目之间常用·隔开 唐代的杂言诗算古体
+        (false, _, _) if poem.title.contains('·') || is_ci_dynasty(&poem.dynasty) => Form::Ci,
+        (false, _, _) => Form::Guti,
+    }
+}
+
+fn is_ci_dynasty(dynasty: &str) -> bool {
+    matches!(dynasty, "五代" | "宋" | "元")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    #[test]
+    fn forms_of_known_poems() {
+        for (title, dynasty, content, form) in [
+            (
+                "静夜思",
+                "唐",
+                "床前明月光，疑是地上霜。举头望明月，低头思故乡。",
+                Form::Wujue,
+            ),
+            (
+                "早发白帝城",
+                "唐",
+                "朝辞白帝彩云间，千里江陵一日还。两岸猿声啼不住，轻舟已过万重山。",
+                Form::Qijue,
+            ),
+            (
+                "春望",
+                "唐",
+                "国破山河在，城春草木深。感时花溅泪，恨别鸟惊心。\
+                 烽火连三月，家书抵万金。白头搔更短，浑欲不胜簪。",
+                Form::Wulv,
+            ),
+            (
+                "登高",
+                "唐",
+                "风急天高猿啸哀，渚清沙白鸟飞回。无边落木萧萧下，不尽长江滚滚来。\
+                 万里悲秋常作客，百年多病独登台。艰难苦恨繁霜鬓，潦倒新停浊酒杯。",
+                Form::Qilv,
+            ),
+            (
+                "如梦令·常记溪亭日暮",
+                "宋",
+                "常记溪亭日暮，沉醉不知归路。兴尽晚回舟，误入藕花深处。争渡，争渡，惊起一滩鸥鹭。",
+                Form::Ci,
+            ),
+            (
+                "相见欢",
+                "五代",
+                "无言独上西楼，月如钩。寂寞梧桐深院锁清秋。",
+                Form::Ci,
+            ),
+            // 唐代的杂言诗
+            (
+                "将进酒",
+                "唐",
+                "君不见黄河之水天上来，奔流到海不复回。",
+                Form::Guti,
+            ),
+            (
+                "敕勒歌",
+                "南北朝",
+                "敕勒川，阴山下。天似穹庐，笼盖四野。",
+                Form::Guti,
+            ),
+            (
+                "六句",
+                "唐",
+                "一二三四五，一二三四五，一二三四五，一二三四五，一二三四五，一二三四五。",
+                Form::Guti,
+            ),
+        ] {
+            let mut poem = poem(title, content);
+            poem.dynasty = dynasty.to_string();
+            assert_eq!(classify(&poem), form, "{}", title);
+        }
+    }
+}
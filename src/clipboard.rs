@@ -0,0 +1,56 @@
+use crate::Poem;
+use anyhow::{anyhow, Result};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+// 按顺序试 第一个能运行的就用它
+const COMMANDS: &[&[&str]] = &[
+    &["pbcopy"],
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["clip.exe"],
+];
+
+/// `poems` as plain text without colors: the title, the author and the content of each,
+/// separated by blank lines
+pub fn plain_text(poems: &[&Poem]) -> String {
+    poems
+        .iter()
+        .map(|p| {
+            format!(
+                "{}\n{}〔{}〕\n{}",
+                p.title,
+                p.author,
+                p.dynasty,
+                p.content.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// put `text` on the system clipboard with the first clipboard program found
+pub fn copy(text: &str) -> Result<()> {
+    for command in COMMANDS {
+        let mut child = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        let status = child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        return Err(anyhow!("{} failed: {}", command[0], status));
+    }
+    Err(anyhow!(
+        "no clipboard program found, install one of pbcopy, wl-copy, xclip or xsel"
+    ))
+}
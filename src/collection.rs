@@ -0,0 +1,132 @@
+use crate::{
+    load_poems,
+    output::{Render, Style},
+    profile, Poem,
+};
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use serde::Serialize;
+use std::{fmt, fs, path::PathBuf};
+
+pub(crate) const COLLECTIONS_DIR: &str = "collections";
+const POEMS_FILE: &str = "poems.json";
+pub(crate) const INDEX_DIR: &str = "index";
+
+/// a named set of poems with an index of its own, kept in `collections/<name>` of the data dir
+#[derive(Debug, Clone)]
+pub struct Collection {
+    pub name: String,
+    dir: PathBuf,
+}
+
+impl Collection {
+    /// the collection called `name`, which has to exist
+    pub fn open(name: &str) -> Result<Self> {
+        let collection = Self::at(name)?;
+        if !collection.poems_path().exists() {
+            return Err(anyhow!(
+                "no collection called {}, create it with `poem collection create {}`",
+                name,
+                name
+            ));
+        }
+        Ok(collection)
+    }
+
+    fn at(name: &str) -> Result<Self> {
+        // 名字就是目录名 不能跑到别的目录去
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(anyhow!("invalid collection name `{}`", name));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            dir: profile::data_dir()?.join(COLLECTIONS_DIR).join(name),
+        })
+    }
+
+    /// a new collection called `name` with `poems`, it is indexed separately
+    pub fn create(name: &str, poems: &[Poem]) -> Result<Self> {
+        let collection = Self::at(name)?;
+        if collection.poems_path().exists() {
+            return Err(anyhow!("collection {} already exists", name));
+        }
+        fs::create_dir_all(&collection.dir)?;
+        let path = collection.poems_path();
+        fs::write(&path, serde_json::to_string_pretty(poems)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(collection)
+    }
+
+    /// remove the poems and the index of the collection
+    pub fn delete(self) -> Result<()> {
+        fs::remove_dir_all(&self.dir)
+            .with_context(|| format!("failed to remove {}", self.dir.display()))
+    }
+
+    /// all collections by name
+    pub fn list() -> Result<Vec<Self>> {
+        let dir = profile::data_dir()?.join(COLLECTIONS_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut collections = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if !path.join(POEMS_FILE).exists() {
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                collections.push(Self::at(name)?);
+            }
+        }
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(collections)
+    }
+
+    /// the json file of the poems, used as `--poems-file`
+    pub fn poems_path(&self) -> PathBuf {
+        self.dir.join(POEMS_FILE)
+    }
+
+    /// the directory of the index, used as `--index-path`
+    pub fn index_path(&self) -> PathBuf {
+        self.dir.join(INDEX_DIR)
+    }
+
+    /// how many poems and whether they are indexed
+    pub fn summary(&self) -> Result<Summary> {
+        Ok(Summary {
+            name: self.name.clone(),
+            poems: load_poems(Some(&self.poems_path()))?.len(),
+            indexed: self.index_path().join("meta.json").exists(),
+            path: self.dir.clone(),
+        })
+    }
+}
+
+/// a line of `collection list`
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub name: String,
+    pub poems: usize,
+    pub indexed: bool,
+    pub path: PathBuf,
+}
+
+impl Render for Summary {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        let indexed = if self.indexed {
+            "indexed".green()
+        } else {
+            "not indexed".yellow()
+        };
+        write!(
+            f,
+            "{}\t{} poems\t{}\t{}",
+            self.name.cyan(),
+            self.poems,
+            indexed,
+            self.path.display().to_string().dimmed()
+        )
+    }
+}
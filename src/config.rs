@@ -0,0 +1,88 @@
+use crate::{output::Output, profile, PoemError, Tokenizer};
+use anyhow::{Context, Result};
+use clap::ArgEnum;
+use colored::control;
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+pub(crate) const CONFIG_FILE: &str = "config.toml";
+
+/// defaults of the command line options, read from `config.toml` in the data dir
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub index_path: Option<String>,
+    /// the default `--limit` of `list`
+    pub limit: Option<usize>,
+    pub color: Option<Color>,
+    /// the tokenizer of `index`
+    pub tokenizer: Option<Tokenizer>,
+    pub output: Option<Output>,
+    /// the `--embed-command` of `semantic index`
+    pub embed_command: Option<String>,
+    /// where your poems are kept, see `--storage`
+    pub storage: Option<String>,
+}
+
+/// when to print with colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    /// only when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl Config {
+    /// the config in `path`, or in the data dir if no path is given,
+    /// a missing file in the data dir is the default config
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let path = profile::data_dir()?.join(CONFIG_FILE);
+                if !path.exists() {
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+        let s = fs::read_to_string(&path).map_err(PoemError::io(&path))?;
+        toml::from_str(&s).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// turn colors on or off as configured
+    pub fn apply_color(&self) {
+        match self.color {
+            Some(Color::Always) => control::set_override(true),
+            Some(Color::Never) => control::set_override(false),
+            Some(Color::Auto) | None => {}
+        }
+    }
+
+    /// the default values of the command line arguments by argument name
+    pub fn defaults(&self) -> Vec<(&'static str, String)> {
+        let mut defaults = Vec::new();
+        if let Some(path) = &self.index_path {
+            defaults.push(("index-path", path.clone()));
+        }
+        if let Some(limit) = self.limit {
+            defaults.push(("limit", limit.to_string()));
+        }
+        if let Some(tokenizer) = self.tokenizer {
+            defaults.push(("tokenizer", tokenizer.to_string()));
+        }
+        if let Some(command) = &self.embed_command {
+            defaults.push(("embed-command", command.clone()));
+        }
+        if let Some(storage) = &self.storage {
+            defaults.push(("storage", storage.clone()));
+        }
+        if let Some(output) = self.output {
+            let value = output.to_possible_value().map(|v| v.get_name().to_string());
+            defaults.extend(value.map(|v| ("output", v)));
+        }
+        defaults
+    }
+}
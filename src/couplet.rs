@@ -0,0 +1,164 @@
+use crate::{
+    poem::is_line_break,
+    tones::{is_fixed, tone_of, Tone},
+    Line, LineHit,
+};
+use anyhow::Result;
+use colored::*;
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+    ops::Range,
+};
+
+/// `line` without punctuation and spaces, as typed by the user
+pub fn clean(line: &str) -> String {
+    line.chars()
+        .filter(|c| !is_line_break(*c) && !c.is_whitespace())
+        .collect()
+}
+
+/// the byte ranges of the characters of `second` with a fixed tone opposite to the one in `first`,
+/// none when the lines differ in length or the last characters are not opposite
+fn opposites(first: &str, second: &str) -> Option<(f32, Vec<Range<usize>>)> {
+    let len = first.chars().count();
+    if len == 0 || second.chars().count() != len {
+        return None;
+    }
+    let mut checked = 0;
+    let mut ranges = Vec::new();
+    let mut last = false;
+    for (i, (a, (offset, c))) in first.chars().zip(second.char_indices()).enumerate() {
+        if !is_fixed(i, len) {
+            continue;
+        }
+        let (a, b) = match (tone_of(a), tone_of(c)) {
+            (Some(a), Some(b)) => (a, b),
+            // 读不出声调的字不算
+            _ => continue,
+        };
+        checked += 1;
+        if a != b {
+            ranges.push(offset..offset + c.len_utf8());
+            last |= i + 1 == len;
+        }
+    }
+    if !last {
+        return None;
+    }
+    Some((ranges.len() as f32 / checked as f32, ranges))
+}
+
+/// how well `second` answers `first` as the other half of a couplet: the share of the characters
+/// with a fixed tone that have the opposite one, none when it can not answer at all
+pub fn complement(first: &str, second: &str) -> Option<f32> {
+    opposites(first, second).map(|(score, _)| score)
+}
+
+/// the lines in `lines` that could answer `first`, the best `limit` first with the characters
+/// of opposite tones highlighted
+///
+/// the lines really following `first` in a poem come before the others
+pub fn candidates(lines: &[Line], first: &str, limit: usize) -> Vec<LineHit> {
+    let originals: HashSet<(&str, u64)> = lines
+        .iter()
+        .filter(|l| l.text == first)
+        .map(|l| (&l.poem_id[..], l.position + 1))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut hits: Vec<(bool, LineHit)> = lines
+        .iter()
+        .filter(|l| l.text != first)
+        .filter_map(|l| {
+            let (score, highlights) = opposites(first, &l.text)?;
+            let original = originals.contains(&(&l.poem_id[..], l.position));
+            Some((
+                original,
+                LineHit {
+                    score,
+                    line: l.clone(),
+                    highlights,
+                },
+            ))
+        })
+        .collect();
+    // 稳定排序 分数一样的按索引里的顺序
+    hits.sort_by(|(a, x), (b, y)| b.cmp(a).then(y.score.total_cmp(&x.score)));
+    hits.into_iter()
+        .map(|(_, hit)| hit)
+        .filter(|h| seen.insert(h.line.text.clone()))
+        .take(limit)
+        .collect()
+}
+
+fn symbols(line: &str) -> String {
+    line.chars().map(|c| Tone::symbol(tone_of(c))).collect()
+}
+
+/// practice 对对子: the program gives the first line of a couplet from `lines`, the player answers
+/// and is told how well the tones are opposite before the original is shown
+///
+/// ends on an empty answer, returns how many lines the player answered
+pub fn practice(
+    lines: &[Line],
+    rng: &mut impl Rng,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<usize> {
+    // 出句取奇数句 下一句是对句
+    let pairs: Vec<(&Line, &Line)> = lines
+        .windows(2)
+        .filter(|w| {
+            w[0].position % 2 == 0
+                && w[1].poem_id == w[0].poem_id
+                && w[1].position == w[0].position + 1
+                && w[0].text.chars().count() == w[1].text.chars().count()
+        })
+        .map(|w| (&w[0], &w[1]))
+        .collect();
+    if pairs.is_empty() {
+        writeln!(output, "诗库里没有能用来对对子的句子")?;
+        return Ok(0);
+    }
+
+    writeln!(output, "对对子：按平仄相对对出下句，直接回车结束\n")?;
+    let mut rounds = 0;
+    loop {
+        let (first, second) = pairs.choose(rng).unwrap();
+        writeln!(output, "出句：{}", first.text.cyan())?;
+        writeln!(output, "      {}", symbols(&first.text).dimmed())?;
+        write!(output, "对句：")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let answer = clean(&line);
+        if answer.is_empty() {
+            break;
+        }
+        rounds += 1;
+        let len = first.text.chars().count();
+        if answer.chars().count() != len {
+            writeln!(output, "{}", format!("字数不对，要{}个字", len).red())?;
+        } else {
+            writeln!(output, "      {}", symbols(&answer))?;
+            match complement(&first.text, &answer) {
+                Some(score) => writeln!(
+                    output,
+                    "{}",
+                    format!("平仄相对：{:.0}%", score * 100.0).green()
+                )?,
+                None => writeln!(output, "{}", "末字平仄没有相对".red())?,
+            }
+        }
+        writeln!(
+            output,
+            "原句：{} {}\n",
+            second.text.cyan(),
+            format!("——{}《{}》", second.author, second.title).dimmed()
+        )?;
+    }
+    Ok(rounds)
+}
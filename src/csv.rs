@@ -0,0 +1,254 @@
+use crate::Poem;
+use anyhow::{anyhow, Result};
+use std::io::Write;
+
+// excel存的utf-8文件开头有这个
+const BOM: char = '\u{feff}';
+
+/// the columns of the fields of a poem, by the name in the header row or by number from 1
+#[derive(clap::Args, Debug, Clone)]
+pub struct Columns {
+    /// the column of the title
+    #[clap(long, default_value = "title")]
+    pub title_column: String,
+    /// the column of the author
+    #[clap(long, default_value = "author")]
+    pub author_column: String,
+    /// the column of the dynasty
+    #[clap(long, default_value = "dynasty")]
+    pub dynasty_column: String,
+    /// the column of the content, the lines in one cell
+    #[clap(long, default_value = "content")]
+    pub content_column: String,
+}
+
+impl Columns {
+    fn names(&self) -> [&str; 4] {
+        [
+            &self.title_column,
+            &self.author_column,
+            &self.dynasty_column,
+            &self.content_column,
+        ]
+    }
+
+    /// the index in a row of every column, from the numbers or the names in `header`
+    fn indexes(&self, header: Option<&[String]>) -> Result<[usize; 4]> {
+        let mut indexes = [0; 4];
+        for (i, name) in self.names().into_iter().enumerate() {
+            indexes[i] = match (name.parse::<usize>(), header) {
+                (Ok(n), _) if n > 0 => n - 1,
+                (_, Some(header)) => header
+                    .iter()
+                    .position(|h| h.trim() == name)
+                    .ok_or_else(|| anyhow!("no column called `{}` in the header", name))?,
+                _ => {
+                    return Err(anyhow!(
+                        "without a header the column `{}` has to be a number",
+                        name
+                    ))
+                }
+            };
+        }
+        Ok(indexes)
+    }
+}
+
+/// the poems in the rows of `text`, the first row is the header unless `no_header` is set
+///
+/// fields may be quoted with `"`, a quoted field can span lines and has `""` for a quote
+pub fn read(text: &str, columns: &Columns, delimiter: char, no_header: bool) -> Result<Vec<Poem>> {
+    let mut rows = parse(text.strip_prefix(BOM).unwrap_or(text), delimiter)?.into_iter();
+    let header = if no_header { None } else { rows.next() };
+    let indexes = columns.indexes(header.as_deref())?;
+    let first = if no_header { 1 } else { 2 };
+    rows.enumerate()
+        .map(|(i, row)| {
+            let cell = |column: usize| {
+                row.get(indexes[column])
+                    .map(|c| c.trim().to_string())
+                    .unwrap_or_default()
+            };
+            let poem = Poem {
+                title: cell(0),
+                author: cell(1),
+                dynasty: cell(2),
+                content: cell(3),
+                source: None,
+                tags: Vec::new(),
+                translation: None,
+                appreciation: None,
+            };
+            if poem.title.is_empty() || poem.content.is_empty() {
+                return Err(anyhow!("row {} has no title or no content", first + i));
+            }
+            Ok(poem)
+        })
+        .collect()
+}
+
+fn parse(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                // 格子里的换行统一成\n
+                '\r' if chars.peek() == Some(&'\n') => {}
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("a quote is not closed at the end of the file"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    // 空行不算
+    rows.retain(|r| r.iter().any(|c| !c.trim().is_empty()));
+    Ok(rows)
+}
+
+/// write `poems` with a header row of the names of `columns`, with a byte order mark first
+/// if `bom` is set so excel knows the file is utf-8
+pub fn write(
+    poems: &[&Poem],
+    columns: &Columns,
+    delimiter: char,
+    bom: bool,
+    mut out: impl Write,
+) -> Result<()> {
+    if bom {
+        write!(out, "{}", BOM)?;
+    }
+    let mut row = |cells: [&str; 4]| -> Result<()> {
+        let cells: Vec<String> = cells.iter().map(|c| quote(c, delimiter)).collect();
+        write!(out, "{}\r\n", cells.join(&delimiter.to_string()))?;
+        Ok(())
+    };
+    row(columns.names())?;
+    for poem in poems {
+        row([&poem.title, &poem.author, &poem.dynasty, &poem.content])?;
+    }
+    Ok(())
+}
+
+fn quote(cell: &str, delimiter: char) -> String {
+    if cell.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    fn columns() -> Columns {
+        Columns {
+            title_column: "title".to_string(),
+            author_column: "author".to_string(),
+            dynasty_column: "dynasty".to_string(),
+            content_column: "content".to_string(),
+        }
+    }
+
+    fn round_trip(poems: &[Poem], delimiter: char, bom: bool) -> (String, Vec<Poem>) {
+        let mut out = Vec::new();
+        let refs: Vec<&Poem> = poems.iter().collect();
+        write(&refs, &columns(), delimiter, bom, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let read = read(&text, &columns(), delimiter, false).unwrap();
+        (text, read)
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|r| r.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn commas_are_quoted() {
+        let poems = [poem("将进酒,其一", "君不见, 黄河之水天上来")];
+        let (text, read) = round_trip(&poems, ',', false);
+        assert!(text.contains("\"将进酒,其一\","), "{}", text);
+        assert_eq!(read, poems);
+        // 别的分隔符时逗号不用引号
+        let (text, read) = round_trip(&poems, '\t', false);
+        assert!(text.contains("将进酒,其一\t"), "{}", text);
+        assert_eq!(read, poems);
+    }
+
+    #[test]
+    fn quotes_are_doubled() {
+        let poems = [poem("\"静夜思\"", "他说\"床前明月光\"")];
+        let (text, read) = round_trip(&poems, ',', false);
+        assert!(text.contains(r#""""静夜思""""#), "{}", text);
+        assert_eq!(read, poems);
+        assert_eq!(
+            parse(r#"a,"b ""c"" d""#, ',').unwrap(),
+            rows(&[&["a", r#"b "c" d"#]])
+        );
+    }
+
+    #[test]
+    fn line_breaks_in_quoted_fields() {
+        let poems = [poem(
+            "静夜思",
+            "床前明月光，疑是地上霜。\n举头望明月，低头思故乡。",
+        )];
+        let (text, read) = round_trip(&poems, ',', false);
+        assert!(text.ends_with("\"\r\n"), "{:?}", text);
+        assert_eq!(read, poems);
+        assert_eq!(
+            parse("a,\"b\r\nc\"\r\nd,e\n", ',').unwrap(),
+            rows(&[&["a", "b\nc"], &["d", "e"]])
+        );
+    }
+
+    #[test]
+    fn a_byte_order_mark_is_skipped() {
+        let poems = [poem("静夜思", "床前明月光")];
+        let (text, read) = round_trip(&poems, ',', true);
+        assert!(text.starts_with(BOM), "{:?}", text);
+        assert_eq!(read, poems);
+        let text = "\u{feff}title,author,dynasty,content\n静夜思,李白,唐,床前明月光\n";
+        assert_eq!(super::read(text, &columns(), ',', false).unwrap(), poems);
+    }
+
+    #[test]
+    fn trailing_empty_fields_are_kept() {
+        assert_eq!(
+            parse("a,b,\nc,,\n", ',').unwrap(),
+            rows(&[&["a", "b", ""], &["c", "", ""]])
+        );
+        assert_eq!(parse("a,", ',').unwrap(), rows(&[&["a", ""]]));
+        // 空行不算一行
+        assert_eq!(parse("a\n\n,\nb", ',').unwrap(), rows(&[&["a"], &["b"]]));
+        assert!(parse("a,\"b", ',').is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{
+    output::{Render, Style},
+    Poem,
+};
+use colored::*;
+use fnv::FnvHasher;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+// 常见的异体字和繁体字 左边换成右边再比较
+const VARIANTS: &str = "爲为為为來来裏里裡里遊游峯峰羣群牀床牕窗窓窗眞真靑青淸清歎叹嘆叹歸归\
+    風风雲云東东時时無无長长門门開开間间聞闻見见萬万雙双鄉乡頭头樓楼鳥鸟馬马龍龙國国書书\
+    詩诗語语過过還还處处獨独難难憶忆夢梦別别淚泪邊边曉晓葉叶憐怜應应飛飞爭争歲岁誰谁樹树\
+    盡尽舊旧陽阳邨村";
+
+/// poems that are probably the same one, the first is the one kept by `dedup --apply`
+#[derive(Debug, Serialize)]
+pub struct Duplicates<'a> {
+    /// where the poems are in the dataset, from 0
+    #[serde(skip)]
+    pub indexes: Vec<usize>,
+    pub poems: Vec<&'a Poem>,
+    /// how many of the pairs of characters of the contents are shared by the least similar two,
+    /// 1 if they only differ in punctuation and variant characters
+    pub similarity: f32,
+}
+
+static VARIANT_MAP: Lazy<HashMap<char, char>> = Lazy::new(|| {
+    let chars: Vec<char> = VARIANTS.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+});
+
+/// which poem of a group of duplicates to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefer {
+    /// the one found first
+    First,
+    /// the one with the most characters, the first of those
+    LongerContent,
+}
+
+impl Duplicates<'_> {
+    /// the indexes in the dataset of the poems but the one at `keep` of the group
+    pub fn others(&self, keep: usize) -> impl Iterator<Item = usize> + '_ {
+        self.indexes
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| *i != keep)
+            .map(|(_, &index)| index)
+    }
+
+    /// the position in the group of the poem to keep
+    pub fn keep(&self, prefer: Prefer) -> usize {
+        match prefer {
+            Prefer::First => 0,
+            Prefer::LongerContent => {
+                let most = self.poems.iter().map(|p| p.char_count()).max().unwrap_or(0);
+                self.poems
+                    .iter()
+                    .position(|p| p.char_count() == most)
+                    .unwrap_or(0)
+            }
+        }
+    }
+}
+
+/// `text` without punctuation and whitespace, with the variant characters replaced
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| VARIANT_MAP.get(&c).copied().unwrap_or(c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// the hashes of every two characters next to each other
+fn shingles(text: &str) -> HashSet<u64> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(2)
+        .map(|w| {
+            let mut hasher = FnvHasher::default();
+            w.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
+
+/// the groups of `poems` that are the same one: the same content once punctuation and variant
+/// characters are ignored, or the same title and author with at least `threshold` of the pairs
+/// of characters of the content shared
+pub fn find(poems: &[Poem], threshold: f32) -> Vec<Duplicates<'_>> {
+    let contents: Vec<String> = poems.iter().map(|p| normalize(&p.content)).collect();
+    let shingles: Vec<HashSet<u64>> = contents.iter().map(|c| shingles(c)).collect();
+    let mut groups = Groups::new(poems.len());
+
+    let mut by_content: HashMap<&str, usize> = HashMap::new();
+    let mut by_title: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (i, poem) in poems.iter().enumerate() {
+        match by_content.get(&contents[i][..]) {
+            Some(&first) => groups.join(first, i, 1.0),
+            None if !contents[i].is_empty() => {
+                by_content.insert(&contents[i], i);
+            }
+            None => {}
+        }
+        by_title
+            .entry((normalize(&poem.title), normalize(&poem.author)))
+            .or_default()
+            .push(i);
+    }
+    // 同名同作者的才两两比较 不然太慢
+    for same in by_title.values() {
+        for (n, &a) in same.iter().enumerate() {
+            for &b in &same[n + 1..] {
+                let similarity = jaccard(&shingles[a], &shingles[b]);
+                if similarity >= threshold {
+                    groups.join(a, b, similarity);
+                }
+            }
+        }
+    }
+    groups.into_duplicates(poems)
+}
+
+// 并查集 每组记着最不像的两首的相似度
+struct Groups {
+    parents: Vec<usize>,
+    similarity: Vec<f32>,
+}
+
+impl Groups {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+            similarity: vec![1.0; len],
+        }
+    }
+
+    fn root(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    fn join(&mut self, a: usize, b: usize, similarity: f32) {
+        let (a, b) = (self.root(a), self.root(b));
+        // 编号小的当根 留下的是最早出现的那首
+        let (root, child) = (a.min(b), a.max(b));
+        let least = self.similarity[a].min(self.similarity[b]).min(similarity);
+        self.parents[child] = root;
+        self.similarity[root] = least;
+    }
+
+    fn into_duplicates(mut self, poems: &[Poem]) -> Vec<Duplicates<'_>> {
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in 0..poems.len() {
+            let root = self.root(i);
+            members.entry(root).or_default().push(i);
+        }
+        let mut duplicates: Vec<Duplicates> = members
+            .into_iter()
+            .filter(|(_, indexes)| indexes.len() > 1)
+            .map(|(root, indexes)| Duplicates {
+                poems: indexes.iter().map(|&i| &poems[i]).collect(),
+                indexes,
+                similarity: self.similarity[root],
+            })
+            .collect();
+        duplicates.sort_by_key(|d| d.indexes[0]);
+        duplicates
+    }
+}
+
+impl Render for Duplicates<'_> {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            format!("{:.0}% similar", self.similarity * 100.0).yellow()
+        )?;
+        for (n, poem) in self.poems.iter().enumerate() {
+            let first_line = poem.lines().first().copied().unwrap_or_default();
+            write!(
+                f,
+                "\n{}. {} {}〔{}〕 {} {}",
+                n + 1,
+                poem.title.bright_cyan(),
+                poem.author,
+                poem.dynasty,
+                first_line,
+                poem.id().dimmed()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    #[test]
+    fn punctuation_whitespace_and_variants_are_ignored() {
+        assert_eq!(
+            normalize("牀前明月光， 疑是地上霜。\n舉頭望明月"),
+            "床前明月光疑是地上霜舉头望明月"
+        );
+        assert_eq!(normalize("「春眠」不覺曉！"), "春眠不覺晓");
+        assert_eq!(normalize("A b"), "ab");
+    }
+
+    #[test]
+    fn the_first_of_the_same_content_is_kept() {
+        let poems = [
+            poem("静夜思", "床前明月光，疑是地上霜。"),
+            poem("春晓", "春眠不觉晓，处处闻啼鸟。"),
+            poem("夜思", "牀前明月光 疑是地上霜"),
+            poem("静夜思", "床前明月光。疑是地上霜！"),
+        ];
+        let duplicates = find(&poems, 0.6);
+        assert_eq!(duplicates.len(), 1);
+        let group = &duplicates[0];
+        assert_eq!(group.indexes, [0, 2, 3]);
+        assert_eq!(group.similarity, 1.0);
+        assert_eq!(group.keep(Prefer::First), 0);
+        assert_eq!(group.others(0).collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn the_same_title_and_author_need_similar_content() {
+        let poems = [
+            poem("静夜思", "床前明月光，疑是地上霜。举头望明月，低头思故乡。"),
+            poem("静夜思", "床前看月光，疑是地上霜。举头望山月，低头思故乡。"),
+            poem("静夜思", "春眠不觉晓，处处闻啼鸟。"),
+        ];
+        let duplicates = find(&poems, 0.5);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].indexes, [0, 1]);
+        assert!(duplicates[0].similarity < 1.0);
+        assert!(find(&poems, 0.9).is_empty());
+    }
+
+    #[test]
+    fn the_longer_content_can_be_kept() {
+        let poems = [
+            poem("静夜思", "床前明月光，疑是地上霜。"),
+            poem("静夜思", "床前明月光，疑是地上霜。举头望明月。"),
+            poem("静夜思", "床前明月光，疑是地上霜。举头望明月！"),
+        ];
+        let duplicates = find(&poems, 0.5);
+        let group = &duplicates[0];
+        assert_eq!(group.indexes, [0, 1, 2]);
+        // 一样长的留前面那首
+        assert_eq!(group.keep(Prefer::LongerContent), 1);
+        assert_eq!(group.others(1).collect::<Vec<_>>(), [0, 2]);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{
+    export::{escape_text, group_by},
+    Poem,
+};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use fnv::FnvHasher;
+use std::{
+    fmt::Write as _,
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+const STYLESHEET: &str = "\
+body { font-family: serif; line-height: 1.8; }
+h1 { text-align: center; margin: 2em 0 1em; }
+h2 { margin: 2em 0 0.2em; }
+.author { color: #888; font-size: 0.9em; margin: 0; }
+.content { margin-top: 0.8em; }
+";
+
+/// write `poems` as an epub book titled `title` with one chapter for each author,
+/// in the order the authors first appear
+pub fn write(poems: &[&Poem], title: &str, out: impl Write) -> Result<()> {
+    // 书里至少要有一章
+    if poems.is_empty() {
+        return Err(anyhow!("no poems to put in the book"));
+    }
+    let chapters = group_by(poems, |p| &p.author);
+    let mut book = Zip::new(out);
+    // mimetype必须是第一个文件 而且不能压缩
+    book.add("mimetype", b"application/epub+zip")?;
+    book.add("META-INF/container.xml", CONTAINER.as_bytes())?;
+    book.add("OEBPS/style.css", STYLESHEET.as_bytes())?;
+    book.add(
+        "OEBPS/content.opf",
+        package(poems, title, chapters.len()).as_bytes(),
+    )?;
+    book.add("OEBPS/nav.xhtml", nav(title, &chapters).as_bytes())?;
+    book.add("OEBPS/toc.ncx", ncx(poems, title, &chapters).as_bytes())?;
+    for (i, (author, poems)) in chapters.iter().enumerate() {
+        book.add(
+            &format!("OEBPS/{}", chapter_file(i)),
+            chapter(author, poems).as_bytes(),
+        )?;
+    }
+    book.finish()
+}
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn chapter_file(i: usize) -> String {
+    format!("chapter{}.xhtml", i)
+}
+
+/// the same poems always make a book with the same identifier
+fn identifier(poems: &[&Poem]) -> String {
+    let mut hasher = FnvHasher::default();
+    poems.iter().for_each(|p| p.id().hash(&mut hasher));
+    format!("urn:poem:{:016x}", hasher.finish())
+}
+
+fn package(poems: &[&Poem], title: &str, chapters: usize) -> String {
+    let mut opf = String::new();
+    opf.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    opf.push('\n');
+    opf.push_str(
+        r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id" xml:lang="zh">"#,
+    );
+    opf.push_str("\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    let _ = writeln!(
+        opf,
+        "<dc:identifier id=\"id\">{}</dc:identifier>",
+        identifier(poems)
+    );
+    let _ = writeln!(opf, "<dc:title>{}</dc:title>", escape_text(title));
+    opf.push_str("<dc:language>zh</dc:language>\n");
+    let _ = writeln!(
+        opf,
+        "<meta property=\"dcterms:modified\">{}</meta>",
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    opf.push_str("</metadata>\n<manifest>\n");
+    opf.push_str(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    opf.push_str("<item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n");
+    opf.push_str("<item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>\n");
+    for i in 0..chapters {
+        let _ = writeln!(
+            opf,
+            "<item id=\"c{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+            i,
+            chapter_file(i)
+        );
+    }
+    opf.push_str("</manifest>\n<spine toc=\"ncx\">\n");
+    for i in 0..chapters {
+        let _ = writeln!(opf, "<itemref idref=\"c{}\"/>", i);
+    }
+    opf.push_str("</spine>\n</package>\n");
+    opf
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="zh" lang="zh">
+<head>
+<meta charset="utf-8"/>
+<title>{}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        escape_text(title),
+        body
+    )
+}
+
+/// the table of contents: the authors and their poems
+fn nav(title: &str, chapters: &[(&str, Vec<&Poem>)]) -> String {
+    let mut body = String::new();
+    body.push_str("<nav epub:type=\"toc\">\n");
+    let _ = writeln!(body, "<h1>{}</h1>", escape_text(title));
+    body.push_str("<ol>\n");
+    for (i, (author, poems)) in chapters.iter().enumerate() {
+        let file = chapter_file(i);
+        let _ = writeln!(
+            body,
+            "<li><a href=\"{}\">{}</a>\n<ol>",
+            file,
+            escape_text(author)
+        );
+        for (j, poem) in poems.iter().enumerate() {
+            let _ = writeln!(
+                body,
+                "<li><a href=\"{}#p{}\">{}</a></li>",
+                file,
+                j,
+                escape_text(&poem.title)
+            );
+        }
+        body.push_str("</ol>\n</li>\n");
+    }
+    body.push_str("</ol>\n</nav>\n");
+    xhtml(title, &body)
+}
+
+// 老的阅读器只认epub2的ncx目录
+fn ncx(poems: &[&Poem], title: &str, chapters: &[(&str, Vec<&Poem>)]) -> String {
+    let mut ncx = String::new();
+    ncx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    ncx.push_str("<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n");
+    let _ = writeln!(
+        ncx,
+        "<head><meta name=\"dtb:uid\" content=\"{}\"/></head>",
+        identifier(poems)
+    );
+    let _ = writeln!(
+        ncx,
+        "<docTitle><text>{}</text></docTitle>",
+        escape_text(title)
+    );
+    ncx.push_str("<navMap>\n");
+    let mut order = 0;
+    for (i, (author, poems)) in chapters.iter().enumerate() {
+        order += 1;
+        let _ = writeln!(
+            ncx,
+            "<navPoint id=\"c{}\" playOrder=\"{}\"><navLabel><text>{}</text></navLabel><content src=\"{}\"/>",
+            i,
+            order,
+            escape_text(author),
+            chapter_file(i)
+        );
+        for (j, poem) in poems.iter().enumerate() {
+            order += 1;
+            let _ = writeln!(
+                ncx,
+                "<navPoint id=\"c{}p{}\" playOrder=\"{}\"><navLabel><text>{}</text></navLabel><content src=\"{}#p{}\"/></navPoint>",
+                i,
+                j,
+                order,
+                escape_text(&poem.title),
+                chapter_file(i),
+                j
+            );
+        }
+        ncx.push_str("</navPoint>\n");
+    }
+    ncx.push_str("</navMap>\n</ncx>\n");
+    ncx
+}
+
+fn chapter(author: &str, poems: &[&Poem]) -> String {
+    let mut body = String::new();
+    let _ = writeln!(body, "<h1>{}</h1>", escape_text(author));
+    for (i, poem) in poems.iter().enumerate() {
+        let _ = writeln!(body, "<h2 id=\"p{}\">{}</h2>", i, escape_text(&poem.title));
+        let _ = writeln!(
+            body,
+            "<p class=\"author\">{}〔{}〕</p>",
+            escape_text(&poem.author),
+            escape_text(&poem.dynasty)
+        );
+        let lines: Vec<String> = poem.content.trim().lines().map(escape_text).collect();
+        let _ = writeln!(body, "<p class=\"content\">{}</p>", lines.join("<br/>\n"));
+    }
+    xhtml(author, &body)
+}
+
+/// a zip archive with the files stored as they are, epub readers do not need them compressed
+struct Zip<W> {
+    out: W,
+    offset: u32,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl<W: Write> Zip<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            central: Vec::new(),
+            entries: 0,
+        }
+    }
+
+    fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let crc = crc32fast::hash(data);
+        let size = data.len() as u32;
+        // 版本 标志 不压缩 时间 日期(1980-01-01)
+        let common = |buf: &mut Vec<u8>| {
+            buf.extend(20u16.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+            buf.extend(33u16.to_le_bytes());
+            buf.extend(crc.to_le_bytes());
+            buf.extend(size.to_le_bytes());
+            buf.extend(size.to_le_bytes());
+            buf.extend((name.len() as u16).to_le_bytes());
+            buf.extend(0u16.to_le_bytes());
+        };
+
+        let mut local = Vec::new();
+        local.extend(0x04034b50u32.to_le_bytes());
+        common(&mut local);
+        local.extend(name.as_bytes());
+        self.out.write_all(&local)?;
+        self.out.write_all(data)?;
+
+        self.central.extend(0x02014b50u32.to_le_bytes());
+        self.central.extend(20u16.to_le_bytes());
+        common(&mut self.central);
+        // 注释长度 磁盘号 内部属性 外部属性
+        self.central.extend(0u16.to_le_bytes());
+        self.central.extend(0u16.to_le_bytes());
+        self.central.extend(0u16.to_le_bytes());
+        self.central.extend(0u32.to_le_bytes());
+        self.central.extend(self.offset.to_le_bytes());
+        self.central.extend(name.as_bytes());
+
+        self.offset += local.len() as u32 + size;
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.out.write_all(&self.central)?;
+        let mut end = Vec::new();
+        end.extend(0x06054b50u32.to_le_bytes());
+        end.extend(0u16.to_le_bytes());
+        end.extend(0u16.to_le_bytes());
+        end.extend(self.entries.to_le_bytes());
+        end.extend(self.entries.to_le_bytes());
+        end.extend((self.central.len() as u32).to_le_bytes());
+        end.extend(self.offset.to_le_bytes());
+        end.extend(0u16.to_le_bytes());
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
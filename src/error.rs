@@ -0,0 +1,88 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// the errors the user can do something about, each exits the cli with its own code
+///
+/// they are carried in the `anyhow::Error`s the rest of the crate returns,
+/// `PoemError::exit_code_of` finds the one in an error
+#[derive(Debug, Error)]
+pub enum PoemError {
+    #[error("no index found at {}, run `poem index` first", .0.display())]
+    IndexMissing(PathBuf),
+    #[error("the index at {} can not be used, {reason}, rebuild it with `poem index --force`", .path.display())]
+    IndexCorrupt { path: PathBuf, reason: String },
+    #[error("the index at {} is being written by {}, try again when it is done", .path.display(), holder(.pid))]
+    IndexLocked { path: PathBuf, pid: Option<u32> },
+    #[error("`{query}` is not a valid query: {reason}")]
+    QuerySyntax { query: String, reason: String },
+    #[error("line {line} column {column}: {reason}")]
+    DataParse {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    #[error("failed to read {}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl PoemError {
+    /// the code the cli exits with, 1 is for nothing found and 2 for the other errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::IndexMissing(_) => 3,
+            Self::IndexCorrupt { .. } => 4,
+            Self::QuerySyntax { .. } => 5,
+            Self::DataParse { .. } => 6,
+            Self::Io { .. } => 7,
+            Self::IndexLocked { .. } => 8,
+        }
+    }
+
+    /// the error of failing to read the file at `path`
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        |source| Self::Io { path, source }
+    }
+
+    /// the exit code of the first `PoemError` in `e`, 2 if there is none
+    pub fn exit_code_of(e: &anyhow::Error) -> i32 {
+        e.chain()
+            .find_map(|e| e.downcast_ref::<Self>())
+            .map_or(2, Self::exit_code)
+    }
+}
+
+fn holder(pid: &Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!("process {}", pid),
+        None => "another process".to_string(),
+    }
+}
+
+impl From<serde_json::Error> for PoemError {
+    fn from(e: serde_json::Error) -> Self {
+        // 只留下原因 位置单独存
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let reason = e.to_string();
+        Self::DataParse {
+            line: e.line(),
+            column: e.column(),
+            reason: reason
+                .strip_suffix(&position)
+                .unwrap_or(&reason)
+                .to_string(),
+        }
+    }
+}
+
+/// the content of the file at `path`
+pub(crate) fn read_to_string(path: &Path) -> Result<String, PoemError> {
+    fs::read_to_string(path).map_err(PoemError::io(path))
+}
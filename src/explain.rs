@@ -0,0 +1,103 @@
+use crate::index::schema;
+use anyhow::Result;
+use colored::*;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tantivy::schema::{Field, FieldType};
+
+/// how the score of a hit is made up, a tree of the parts it is computed from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Explanation {
+    pub value: f32,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<Explanation>,
+    /// the terms matched, as `field:text`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<String>,
+}
+
+impl Explanation {
+    /// the explanation of tantivy with the terms made readable
+    pub(crate) fn new(explanation: &tantivy::query::Explanation) -> Result<Self> {
+        // tantivy的解释只能转成json 字段都是私有的
+        let mut explanation: Self =
+            serde_json::to_value(explanation).and_then(serde_json::from_value)?;
+        explanation.readable_context();
+        Ok(explanation)
+    }
+
+    fn readable_context(&mut self) {
+        self.context.iter_mut().for_each(|c| *c = readable(c));
+        self.details.iter_mut().for_each(Self::readable_context);
+    }
+
+    fn render(&self, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{} {}",
+            "  ".repeat(depth),
+            format!("{:.3}", self.value).yellow(),
+            self.description
+        )?;
+        for context in &self.context {
+            write!(f, " {}", context.cyan())?;
+        }
+        writeln!(f)?;
+        self.details.iter().try_for_each(|d| d.render(depth + 1, f))
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(0, f)
+    }
+}
+
+// 查询的Debug输出里词是 Term(field=1,bytes=[..]) 解释里是 Term =Field(1):[..] 单独的字段是 Field(1)
+static TERM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"Term\(field=(\d+),bytes=\[([\d, ]*)\]\)|Field\((\d+)\):\[([\d, ]*)\]|Field\(\s*(\d+),?\s*\)",
+    )
+    .unwrap()
+});
+
+/// `text` with the fields and terms tantivy prints as numbers and bytes replaced by
+/// their names and `field:text`
+pub(crate) fn readable(text: &str) -> String {
+    TERM.replace_all(text, |caps: &Captures| {
+        if let Some(field) = caps.get(5) {
+            let field = Field::from_field_id(field.as_str().parse().unwrap());
+            return schema().get_field_name(field).to_string();
+        }
+        let field = caps.get(1).or_else(|| caps.get(3)).unwrap().as_str();
+        let bytes: Vec<u8> = caps
+            .get(2)
+            .or_else(|| caps.get(4))
+            .unwrap()
+            .as_str()
+            .split(',')
+            .filter_map(|b| b.trim().parse().ok())
+            .collect();
+        term(Field::from_field_id(field.parse().unwrap()), &bytes)
+    })
+    .into_owned()
+}
+
+fn term(field: Field, bytes: &[u8]) -> String {
+    let schema = schema();
+    let entry = schema.get_field_entry(field);
+    let value = match entry.field_type() {
+        FieldType::U64(_) if bytes.len() == 8 => {
+            u64::from_be_bytes(bytes.try_into().unwrap()).to_string()
+        }
+        // 分面的各级之间是\0
+        FieldType::HierarchicalFacet(_) => {
+            format!("/{}", String::from_utf8_lossy(bytes).replace('\0', "/"))
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    format!("{}:{}", entry.name(), value)
+}
@@ -0,0 +1,262 @@
+use crate::{annotate::pinyin, Poem};
+use anyhow::Result;
+use std::{collections::HashMap, io::Write};
+
+/// a field of a poem that can be put on a card
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoemField {
+    Id,
+    Title,
+    Author,
+    Dynasty,
+    Content,
+}
+
+impl PoemField {
+    fn of(self, poem: &Poem) -> String {
+        match self {
+            PoemField::Id => poem.id(),
+            PoemField::Title => poem.title.clone(),
+            PoemField::Author => poem.author.clone(),
+            PoemField::Dynasty => poem.dynasty.clone(),
+            PoemField::Content => poem.content.clone(),
+        }
+    }
+}
+
+/// what the cards of a deck ask
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardKind {
+    /// one card per poem with the front and back fields
+    Poem,
+    /// one card per line asking for the line after it
+    NextLine,
+}
+
+/// write `poems` as cards in the tab separated format anki imports
+///
+/// the fields are html, the author and dynasty are the tags
+pub fn anki(
+    poems: &[&Poem],
+    kinds: &[CardKind],
+    front: &[PoemField],
+    back: &[PoemField],
+    mut out: impl Write,
+) -> Result<()> {
+    writeln!(out, "#separator:tab")?;
+    writeln!(out, "#html:true")?;
+    writeln!(out, "#tags column:3")?;
+    for poem in poems {
+        let tags = format!("{} {}", tag(&poem.author), tag(&poem.dynasty));
+        if kinds.contains(&CardKind::Poem) {
+            let side = |fields: &[PoemField]| {
+                fields
+                    .iter()
+                    .map(|f| escape(&f.of(poem)))
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            };
+            writeln!(out, "{}\t{}\t{}", side(front), side(back), tags)?;
+        }
+        if kinds.contains(&CardKind::NextLine) {
+            let lines = poem.lines();
+            for pair in lines.windows(2) {
+                writeln!(
+                    out,
+                    "{}<br><small>——{}《{}》</small>\t{}\t{}",
+                    escape(pair[0]),
+                    escape(&poem.author),
+                    escape(&poem.title),
+                    escape(pair[1]),
+                    tags
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', " ")
+        .replace('\n', "<br>")
+}
+
+const STYLESHEET: &str = "\
+body { max-width: 42em; margin: 0 auto; padding: 1em; font-family: serif; line-height: 1.8; \
+color: #333; background: #fdfbf6; }
+nav { border-bottom: 1px solid #ddd; margin-bottom: 2em; }
+nav ul { list-style: none; padding-left: 1em; }
+nav li { display: inline-block; margin-right: 1em; }
+h2 { border-bottom: 1px solid #ddd; margin-top: 2em; }
+h3 { color: #8b4513; }
+article { margin: 1.5em 0; }
+article h4 { margin-bottom: 0.2em; }
+article .author { color: #888; font-size: 0.9em; }
+article p { white-space: pre-wrap; margin-top: 0.5em; }
+a { color: #8b4513; text-decoration: none; }
+";
+
+/// write `poems` as a standalone html page titled `title`, grouped by dynasty and then author
+/// in the order they first appear, with links to every group at the top
+pub fn html(poems: &[&Poem], title: &str, mut out: impl Write) -> Result<()> {
+    let dynasties: Vec<_> = group_by(poems, |p| &p.dynasty)
+        .into_iter()
+        .map(|(dynasty, poems)| (dynasty, group_by(&poems, |p| &p.author)))
+        .collect();
+    // 名字可能有空格或符号 锚点用序号
+    let mut anchors = HashMap::new();
+    for (i, (dynasty, authors)) in dynasties.iter().enumerate() {
+        for (j, (author, _)) in authors.iter().enumerate() {
+            anchors.insert((*dynasty, *author), format!("a{}-{}", i, j));
+        }
+    }
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"zh\">")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape(title))?;
+    writeln!(out, "<style>\n{}</style>", STYLESHEET)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>{}</h1>", escape(title))?;
+
+    writeln!(out, "<nav>")?;
+    for (i, (dynasty, authors)) in dynasties.iter().enumerate() {
+        writeln!(out, "<a href=\"#d{}\">{}</a>", i, escape(dynasty))?;
+        writeln!(out, "<ul>")?;
+        for (author, poems) in authors {
+            writeln!(
+                out,
+                "<li><a href=\"#{}\">{}</a>（{}）</li>",
+                anchors[&(*dynasty, *author)],
+                escape(author),
+                poems.len()
+            )?;
+        }
+        writeln!(out, "</ul>")?;
+    }
+    writeln!(out, "</nav>")?;
+
+    for (i, (dynasty, authors)) in dynasties.iter().enumerate() {
+        writeln!(out, "<section>")?;
+        writeln!(out, "<h2 id=\"d{}\">{}</h2>", i, escape(dynasty))?;
+        for (author, poems) in authors {
+            writeln!(
+                out,
+                "<h3 id=\"{}\">{}</h3>",
+                anchors[&(*dynasty, *author)],
+                escape(author)
+            )?;
+            for poem in poems {
+                writeln!(out, "<article id=\"{}\">", poem.id())?;
+                writeln!(out, "<h4>{}</h4>", escape(&poem.title))?;
+                writeln!(
+                    out,
+                    "<div class=\"author\">{}〔{}〕</div>",
+                    escape(&poem.author),
+                    escape(&poem.dynasty)
+                )?;
+                // 换行靠pre-wrap保留 不用<br>
+                writeln!(out, "<p>{}</p>", escape_text(poem.content.trim()))?;
+                writeln!(out, "</article>")?;
+            }
+        }
+        writeln!(out, "</section>")?;
+    }
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+    Ok(())
+}
+
+/// write `poems` as a latex document for xelatex with one poem per page,
+/// with `with_pinyin` every character gets its pinyin above it
+pub fn latex(poems: &[&Poem], with_pinyin: bool, mut out: impl Write) -> Result<()> {
+    writeln!(out, "\\documentclass[12pt]{{article}}")?;
+    writeln!(out, "\\usepackage[a4paper,margin=2.5cm]{{geometry}}")?;
+    writeln!(out, "\\usepackage{{xeCJK}}")?;
+    writeln!(out, "% 换成任何装了的中文字体都可以")?;
+    writeln!(out, "\\setCJKmainfont{{FandolSong-Regular.otf}}")?;
+    writeln!(
+        out,
+        "\\newcommand{{\\py}}[2]{{\\begin{{tabular}}[b]{{@{{}}c@{{}}}}{{\\scriptsize #2}}\\\\#1\\end{{tabular}}}}"
+    )?;
+    writeln!(out, "\\pagestyle{{empty}}")?;
+    writeln!(out, "\\begin{{document}}")?;
+    for poem in poems {
+        writeln!(out)?;
+        writeln!(out, "\\begin{{center}}")?;
+        writeln!(out, "{{\\Large {}}}\\\\[0.5em]", tex(&poem.title))?;
+        writeln!(
+            out,
+            "{}〔{}〕\\\\[1.5em]",
+            tex(&poem.author),
+            tex(&poem.dynasty)
+        )?;
+        for line in poem.content.trim().lines() {
+            let line = if with_pinyin {
+                line.chars()
+                    .map(|c| match pinyin(c) {
+                        Some(py) => format!("\\py{{{}}}{{{}}}", c, py),
+                        None => tex(&c.to_string()),
+                    })
+                    .collect()
+            } else {
+                tex(line)
+            };
+            writeln!(out, "{}\\\\[0.5em]", line)?;
+        }
+        writeln!(out, "\\end{{center}}")?;
+        writeln!(out, "\\newpage")?;
+    }
+    writeln!(out, "\\end{{document}}")?;
+    Ok(())
+}
+
+/// `s` with the special characters of latex escaped
+fn tex(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `poems` grouped by `key` in the order the keys first appear
+pub(crate) fn group_by<'a>(
+    poems: &[&'a Poem],
+    key: impl Fn(&'a Poem) -> &'a str,
+) -> Vec<(&'a str, Vec<&'a Poem>)> {
+    let mut groups: Vec<(&str, Vec<&Poem>)> = Vec::new();
+    for &poem in poems {
+        let k = key(poem);
+        match groups.iter_mut().find(|(g, _)| *g == k) {
+            Some((_, group)) => group.push(poem),
+            None => groups.push((k, vec![poem])),
+        }
+    }
+    groups
+}
+
+pub(crate) fn escape_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// anki的标签用空格分开 标签里不能有空格
+fn tag(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join("_")
+}
@@ -0,0 +1,76 @@
+use crate::profile;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const FAVORITES_FILE: &str = "favorites.json";
+
+/// ids of the bookmarked poems in the order they were added
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Favorites {
+    ids: Vec<String>,
+}
+
+impl Favorites {
+    pub fn load() -> Result<Self> {
+        profile::load(FAVORITES_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        profile::save(FAVORITES_FILE, self)
+    }
+
+    /// returns false if the poem is already a favorite
+    pub fn add(&mut self, id: &str) -> bool {
+        if self.contains(id) {
+            return false;
+        }
+        self.ids.push(id.to_string());
+        true
+    }
+
+    /// returns false if the poem is not a favorite
+    pub fn remove(&mut self, id: &str) -> bool {
+        let len = self.ids.len();
+        self.ids.retain(|i| i != id);
+        self.ids.len() != len
+    }
+
+    /// put poem `to` in the place of poem `from`, returns false if `from` is not a favorite
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        if !self.contains(from) {
+            return false;
+        }
+        // to已经收藏过的话只留前面那个
+        for id in std::mem::take(&mut self.ids) {
+            self.add(if id == from { to } else { &id });
+        }
+        true
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.iter().any(|i| i == id)
+    }
+
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_favorites_keep_their_place() {
+        let mut favorites = Favorites::default();
+        for id in ["a", "b", "c"] {
+            favorites.add(id);
+        }
+        assert!(favorites.rename("b", "d"));
+        assert_eq!(favorites.ids(), ["a", "d", "c"]);
+        assert!(favorites.rename("c", "a"));
+        assert_eq!(favorites.ids(), ["a", "d"]);
+        assert!(!favorites.rename("c", "e"));
+    }
+}
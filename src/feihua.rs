@@ -0,0 +1,100 @@
+use crate::{poem::is_line_break, Poem, PoemRepository, SearchOptions};
+use anyhow::Result;
+use colored::*;
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+};
+
+/// play 飞花令 with `key`: the player and the program take turns saying a line containing it,
+/// every line can only be said once
+///
+/// the lines of the player are checked against the line index, the program answers with
+/// the lines of the poems in `repo`, returns how many lines the player said
+pub fn run(
+    repo: &PoemRepository,
+    key: char,
+    rng: &mut impl Rng,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<usize> {
+    let candidates: Vec<(&str, &Poem)> = repo
+        .list(None)
+        .iter()
+        .flat_map(|p| p.lines().into_iter().map(move |l| (l, p)))
+        .filter(|(l, _)| l.contains(key))
+        .collect();
+    let exact = SearchOptions {
+        exact: true,
+        ..Default::default()
+    };
+
+    writeln!(output, "飞花令：{}", key.to_string().red().bold())?;
+    writeln!(output, "你先来，直接回车认输\n")?;
+    let mut used = HashSet::new();
+    let mut rounds = 0;
+    loop {
+        write!(output, "你：")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let guess: String = line
+            .chars()
+            .filter(|c| !is_line_break(*c) && !c.is_whitespace())
+            .collect();
+        if guess.is_empty() {
+            writeln!(output, "{}", "你认输了".red())?;
+            break;
+        }
+        if !guess.contains(key) {
+            writeln!(output, "{}", format!("这句没有「{}」，你输了", key).red())?;
+            break;
+        }
+        if used.contains(&guess) {
+            writeln!(output, "{}", "这句已经说过了，你输了".red())?;
+            break;
+        }
+        // 整句作为短语搜索 再比较全文 防止只是某一句的一部分
+        let hits = repo.search_lines(&guess, &exact)?;
+        let found = match hits.iter().find(|h| h.line.text == guess) {
+            Some(hit) => hit,
+            None => {
+                writeln!(output, "{}", "诗库里没有这句，你输了".red())?;
+                break;
+            }
+        };
+        writeln!(
+            output,
+            "{}",
+            format!("——{}《{}》", found.line.author, found.line.title).dimmed()
+        )?;
+        used.insert(guess);
+        rounds += 1;
+
+        let unused: Vec<&(&str, &Poem)> = candidates
+            .iter()
+            .filter(|(l, _)| !used.contains(*l))
+            .collect();
+        match unused.choose(rng) {
+            Some((line, poem)) => {
+                writeln!(output, "我：{}", line.cyan())?;
+                writeln!(
+                    output,
+                    "{}",
+                    format!("——{}《{}》", poem.author, poem.title).dimmed()
+                )?;
+                used.insert(line.to_string());
+            }
+            None => {
+                writeln!(output, "{}", "我接不上了，你赢了".green())?;
+                break;
+            }
+        }
+    }
+    writeln!(output, "\n你说了{}句", rounds)?;
+
+    Ok(rounds)
+}
@@ -0,0 +1,84 @@
+use crate::{classify::classify, Form, Poem};
+use serde::Deserialize;
+
+/// metadata constraints on poems, empty fields match everything
+#[derive(clap::Args, Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct PoemFilter {
+    /// only poems of this dynasty
+    #[clap(long)]
+    pub dynasty: Option<String>,
+    /// only poems by this author
+    #[clap(long)]
+    pub author: Option<String>,
+    /// only poems with this in the title
+    #[clap(long)]
+    pub title_contains: Option<String>,
+    /// only poems with this tag, see `tag`
+    #[clap(long)]
+    pub tag: Option<String>,
+    #[clap(flatten)]
+    #[serde(flatten)]
+    pub shape: ShapeFilter,
+}
+
+/// constraints on the length and form of poems, punctuation not counted
+#[derive(clap::Args, Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShapeFilter {
+    /// only poems with at least this many characters
+    #[clap(long)]
+    pub min_chars: Option<u64>,
+    /// only poems with at most this many characters
+    #[clap(long)]
+    pub max_chars: Option<u64>,
+    /// only poems with this many lines, 4 for a 绝句
+    #[clap(long)]
+    pub lines: Option<u64>,
+    /// only poems of this form: wujue 五言绝句, qijue 七言绝句, wulv 五言律诗, qilv 七言律诗,
+    /// ci 词 or guti 古体
+    #[clap(long, arg_enum)]
+    #[serde(skip)]
+    pub form: Option<Form>,
+}
+
+impl ShapeFilter {
+    /// whether there is no constraint at all
+    pub fn is_empty(&self) -> bool {
+        self.min_chars.is_none()
+            && self.max_chars.is_none()
+            && self.lines.is_none()
+            && self.form.is_none()
+    }
+
+    pub fn matches(&self, poem: &Poem) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let chars = poem.char_count() as u64;
+        self.min_chars.is_none_or(|min| chars >= min)
+            && self.max_chars.is_none_or(|max| chars <= max)
+            && self
+                .lines
+                .is_none_or(|lines| poem.lines().len() as u64 == lines)
+            && self.form.is_none_or(|form| classify(poem) == form)
+    }
+}
+
+impl PoemFilter {
+    pub fn matches(&self, poem: &Poem) -> bool {
+        self.matches_columns(poem)
+            && self.tag.as_ref().is_none_or(|t| poem.tags.contains(t))
+            && self.shape.matches(poem)
+    }
+
+    /// only the dynasty, the author and the title, the columns of a sqlite storage
+    pub(crate) fn matches_columns(&self, poem: &Poem) -> bool {
+        self.dynasty.as_ref().is_none_or(|d| *d == poem.dynasty)
+            && self.author.as_ref().is_none_or(|a| *a == poem.author)
+            && self
+                .title_contains
+                .as_ref()
+                .is_none_or(|t| poem.title.contains(&t[..]))
+    }
+}
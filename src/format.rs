@@ -0,0 +1,414 @@
+use crate::{error::PoemError, Poem};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+/// a format poems are stored in, the same fields in each
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// an array of objects, like the bundled poems
+    Json,
+    /// one object per line, read one at a time by `index` so a big file needs little memory
+    JsonLines,
+    /// a list of mappings, the content as a `|` block so the lines need no escaping
+    Yaml,
+    /// a `[[poems]]` table for every poem, the content as a multi-line string
+    Toml,
+}
+
+// toml的顶层只能是表
+#[derive(Serialize, Deserialize)]
+struct TomlPoems<T> {
+    poems: Vec<T>,
+}
+
+impl Format {
+    /// the format of `path` by its extension, json if it is not json lines, yaml or toml
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl" | "ndjson") => Self::JsonLines,
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+
+    /// the poems in `text`
+    pub fn read(self, text: &str) -> Result<Vec<Poem>> {
+        match self {
+            Self::Json => Ok(serde_json::from_str(text).map_err(PoemError::from)?),
+            Self::JsonLines => json_lines(text.as_bytes()).collect(),
+            Self::Yaml => read_yaml(text),
+            Self::Toml => {
+                let poems =
+                    toml::from_str::<TomlPoems<Poem>>(text).map_err(|e| match e.line_col() {
+                        Some((line, column)) => PoemError::DataParse {
+                            line: line + 1,
+                            column: column + 1,
+                            reason: e.to_string(),
+                        }
+                        .into(),
+                        None => anyhow::Error::from(e),
+                    })?;
+                Ok(poems.poems)
+            }
+        }
+    }
+
+    /// write `poems` to `out`
+    pub fn write(self, poems: &[&Poem], mut out: impl Write) -> Result<()> {
+        match self {
+            Self::Json => {
+                serde_json::to_writer_pretty(&mut out, poems)?;
+                writeln!(out)?;
+            }
+            Self::JsonLines => {
+                for poem in poems {
+                    serde_json::to_writer(&mut out, poem)?;
+                    writeln!(out)?;
+                }
+            }
+            Self::Yaml => write_yaml(poems, out)?,
+            Self::Toml => {
+                let poems = TomlPoems {
+                    poems: poems.to_vec(),
+                };
+                write!(out, "{}", toml::to_string_pretty(&poems)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the poems of a json lines file one by one as they are read, blank lines are skipped
+pub fn json_lines(reader: impl BufRead) -> impl Iterator<Item = Result<Poem>> {
+    reader.lines().enumerate().filter_map(|(i, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+        if line.trim().is_empty() {
+            return None;
+        }
+        // 每行单独解析 行号换成文件里的
+        Some(serde_json::from_str(&line).map_err(|e| {
+            let column = e.column();
+            match PoemError::from(e) {
+                PoemError::DataParse { reason, .. } => PoemError::DataParse {
+                    line: i + 1,
+                    column,
+                    reason,
+                },
+                e => e,
+            }
+            .into()
+        }))
+    })
+}
+
+// 只支持自己写出来的那部分yaml: 一个列表 每项是键值对 值是普通 带引号或者|块的字符串 标签是[]列表
+fn read_yaml(text: &str) -> Result<Vec<Poem>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut poems = Vec::new();
+    let mut poem: Option<Map<String, Value>> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let number = i + 1;
+        i += 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "---" {
+            continue;
+        }
+        let (indent, pair) = match trimmed.strip_prefix("- ") {
+            Some(pair) if !line.starts_with(' ') => {
+                if let Some(poem) = poem.replace(Map::new()) {
+                    poems.push(poem);
+                }
+                (2, pair.trim_start())
+            }
+            _ if line.starts_with("  ") && poem.is_some() => (2, trimmed),
+            _ => return Err(anyhow!("line {}: expected `- key: value`", number)),
+        };
+        let (key, value) = pair
+            .split_once(':')
+            .ok_or_else(|| anyhow!("line {}: expected `key: value`", number))?;
+        let value = value.trim();
+        let value = match value {
+            "|" | "|-" | "|+" => {
+                let start = i;
+                while i < lines.len()
+                    && (lines[i].trim().is_empty() || indent_of(lines[i]) > indent)
+                {
+                    i += 1;
+                }
+                let block = &lines[start..i];
+                let inner = block
+                    .iter()
+                    .filter(|l| !l.trim().is_empty())
+                    .map(|l| indent_of(l))
+                    .min()
+                    .unwrap_or(0);
+                Value::String(
+                    block
+                        .iter()
+                        .map(|l| l.get(inner..).unwrap_or("").trim_end())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                        .trim_end()
+                        .to_string(),
+                )
+            }
+            _ if value.starts_with('[') => {
+                list(value).with_context(|| format!("line {}", number))?
+            }
+            _ if value.starts_with(['>', '{', '&', '*', '!']) => {
+                return Err(anyhow!(
+                    "line {}: only plain, quoted, `|` and `[]` values are supported",
+                    number
+                ))
+            }
+            _ => Value::String(scalar(value).with_context(|| format!("line {}", number))?),
+        };
+        poem.as_mut().unwrap().insert(key.trim().to_string(), value);
+    }
+    poems.extend(poem);
+    poems
+        .into_iter()
+        .enumerate()
+        .map(|(i, p)| {
+            serde_json::from_value(Value::Object(p)).with_context(|| format!("poem {}", i + 1))
+        })
+        .collect()
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn list(value: &str) -> Result<Value> {
+    let inner = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(|| anyhow!("a list has to be on one line and end with `]`"))?;
+    // 引号里的逗号不分开
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') => escaped = !escaped,
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        if c != '\\' {
+            escaped = false;
+        }
+    }
+    items.push(&inner[start..]);
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| scalar(item).map(Value::String))
+        .collect::<Result<_>>()
+        .map(Value::Array)
+}
+
+fn scalar(value: &str) -> Result<String> {
+    if value.starts_with('"') {
+        // 双引号字符串的转义和json的一样
+        return Ok(serde_json::from_str(value)?);
+    }
+    if let Some(quoted) = value.strip_prefix('\'') {
+        let quoted = quoted
+            .strip_suffix('\'')
+            .ok_or_else(|| anyhow!("a quote is not closed"))?;
+        return Ok(quoted.replace("''", "'"));
+    }
+    let value = match value.find(" #") {
+        Some(i) => &value[..i],
+        None => value,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+const FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "author",
+    "dynasty",
+    "content",
+    "form",
+    "source",
+    "tags",
+    "translation",
+    "appreciation",
+];
+
+fn write_yaml(poems: &[&Poem], mut out: impl Write) -> Result<()> {
+    for poem in poems {
+        let fields = match serde_json::to_value(poem)? {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        };
+        // json的Map按键排序 要按序列化的顺序写
+        let keys = FIELDS.iter().filter(|k| fields.contains_key(**k));
+        for (i, key) in keys.enumerate() {
+            write!(out, "{}{}:", if i == 0 { "- " } else { "  " }, key)?;
+            if let Some(items) = fields[*key].as_array() {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| {
+                        let item = item.as_str().unwrap_or_default();
+                        if is_plain(item) && !item.contains([',', ']']) {
+                            Ok(item.to_string())
+                        } else {
+                            serde_json::to_string(item)
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                writeln!(out, " [{}]", items.join(", "))?;
+                continue;
+            }
+            let value = fields[*key].as_str().unwrap_or_default();
+            if value.contains('\n') {
+                writeln!(out, " |-")?;
+                for line in value.lines() {
+                    writeln!(out, "    {}", line)?;
+                }
+            } else if is_plain(value) {
+                writeln!(out, " {}", value)?;
+            } else {
+                writeln!(out, " {}", serde_json::to_string(value)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// whether `value` reads back as the same string without quotes
+fn is_plain(value: &str) -> bool {
+    let special = value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@` ".contains(c));
+    // 全是数字的id不加引号会被别的yaml库读成数字
+    let not_string = value.parse::<f64>().is_ok()
+        || matches!(
+            value.to_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "~"
+        );
+    !value.is_empty()
+        && !special
+        && !not_string
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.ends_with([' ', ':'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    fn round_trip(poems: &[Poem]) -> (String, Vec<Poem>) {
+        let mut out = Vec::new();
+        write_yaml(&poems.iter().collect::<Vec<_>>(), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let read = read_yaml(&text).unwrap_or_else(|e| panic!("{:#}\n{}", e, text));
+        (text, read)
+    }
+
+    #[test]
+    fn multi_line_content_is_a_block() {
+        let poems = [
+            poem(
+                "静夜思",
+                "床前明月光，疑是地上霜。\n举头望明月，低头思故乡。",
+            ),
+            poem("春晓", "春眠不觉晓，处处闻啼鸟。"),
+        ];
+        let (text, read) = round_trip(&poems);
+        assert!(text.contains("  content: |-\n    床前明月光"), "{}", text);
+        assert_eq!(read, poems);
+    }
+
+    #[test]
+    fn values_looking_like_yaml_are_quoted() {
+        let mut poem = poem("key: value", "a # not a comment");
+        poem.author = "\"quoted\" and 'single'".to_string();
+        poem.dynasty = "#唐".to_string();
+        poem.source = Some("- 列表".to_string());
+        poem.translation = Some("ends with:".to_string());
+        let poems = [poem];
+        let (text, read) = round_trip(&poems);
+        assert!(text.contains(r#"title: "key: value""#), "{}", text);
+        assert_eq!(read, poems);
+    }
+
+    #[test]
+    fn numbers_are_read_as_strings() {
+        let mut poem = poem("1984", "3.14");
+        poem.dynasty = "true".to_string();
+        poem.tags = vec!["2".to_string(), "null".to_string()];
+        let poems = [poem];
+        let (text, read) = round_trip(&poems);
+        assert!(text.contains(r#"title: "1984""#), "{}", text);
+        assert!(text.contains(r#"tags: ["2", "null"]"#), "{}", text);
+        assert_eq!(read, poems);
+    }
+
+    #[test]
+    fn empty_lists_and_blank_lines() {
+        let poems = [poem("两段", "第一段。\n\n第二段。")];
+        let (_, read) = round_trip(&poems);
+        assert_eq!(read, poems);
+
+        let text = "# 注释\n---\n\n- title: 两段\n  author: 李白\n\n  dynasty: 唐\n  content: |\n    第一段。\n\n    第二段。\n\n  tags: []\n";
+        assert_eq!(read_yaml(text).unwrap(), poems);
+        assert_eq!(list("[]").unwrap(), Value::Array(Vec::new()));
+        assert_eq!(
+            list(r#"[a, "b, c", 'd''s']"#).unwrap(),
+            serde_json::json!(["a", "b, c", "d's"])
+        );
+    }
+
+    #[test]
+    fn chinese_punctuation_needs_no_quotes() {
+        let mut poem = poem("《将进酒》：其一", "君不见，黄河之水天上来！＃「奔流」");
+        poem.tags = vec!["劝酒，豪放".to_string(), "“乐府”".to_string()];
+        let poems = [poem];
+        let (text, read) = round_trip(&poems);
+        assert!(text.contains("title: 《将进酒》：其一\n"), "{}", text);
+        assert!(text.contains("tags: [劝酒，豪放, “乐府”]"), "{}", text);
+        assert_eq!(read, poems);
+    }
+
+    #[test]
+    fn plain_values() {
+        for (value, plain) in [
+            ("静夜思", true),
+            ("a: b", false),
+            ("a #b", false),
+            ("a#b", true),
+            ("", false),
+            ("12", false),
+            ("No", false),
+            (" 空格", false),
+            ("'引号", false),
+        ] {
+            assert_eq!(is_plain(value), plain, "{:?}", value);
+        }
+        assert_eq!(scalar("a #comment").unwrap(), "a");
+        assert_eq!(scalar(r#""a\nb""#).unwrap(), "a\nb");
+        assert!(scalar("'open").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{query::syntax_error, Hit, Line, LineHit, Poem};
+use anyhow::Result;
+use regex::Regex;
+use std::ops::Range;
+
+/// what `grep` looks for, without the index
+pub enum Pattern {
+    /// the text as it is
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self> {
+        if regex {
+            Regex::new(pattern)
+                .map(Self::Regex)
+                .map_err(|e| syntax_error(pattern, e))
+        } else {
+            Ok(Self::Literal(pattern.to_string()))
+        }
+    }
+
+    /// byte ranges of the matches in `text`
+    fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        match self {
+            // 空的字面量哪里都能匹配上 不算
+            Self::Literal(s) if s.is_empty() => Vec::new(),
+            Self::Literal(s) => text
+                .match_indices(&s[..])
+                .map(|(i, m)| i..i + m.len())
+                .collect(),
+            Self::Regex(r) => r
+                .find_iter(text)
+                .filter(|m| !m.range().is_empty())
+                .map(|m| m.range())
+                .collect(),
+        }
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        !self.find_all(text).is_empty()
+    }
+}
+
+/// the poems with a match in the title or content, the matches in the content highlighted
+pub fn poems<'a>(poems: impl Iterator<Item = &'a Poem>, pattern: &Pattern) -> Vec<Hit> {
+    poems
+        .filter_map(|poem| {
+            let highlights = pattern.find_all(&poem.content);
+            if highlights.is_empty() && !pattern.is_match(&poem.title) {
+                return None;
+            }
+            Some(Hit {
+                score: highlights.len() as f32,
+                poem: poem.clone(),
+                highlights,
+                snippet: None,
+                explanation: None,
+                source: None,
+            })
+        })
+        .collect()
+}
+
+/// the single lines with a match, a match can not span lines
+pub fn lines<'a>(poems: impl Iterator<Item = &'a Poem>, pattern: &Pattern) -> Vec<LineHit> {
+    let mut hits = Vec::new();
+    for poem in poems {
+        for (position, text) in poem.lines().into_iter().enumerate() {
+            let highlights = pattern.find_all(text);
+            if highlights.is_empty() {
+                continue;
+            }
+            hits.push(LineHit {
+                score: highlights.len() as f32,
+                line: Line {
+                    poem_id: poem.id(),
+                    text: text.to_string(),
+                    position: position as u64,
+                    title: poem.title.clone(),
+                    author: poem.author.clone(),
+                    dynasty: poem.dynasty.clone(),
+                },
+                highlights,
+            });
+        }
+    }
+    hits
+}
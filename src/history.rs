@@ -0,0 +1,36 @@
+use crate::profile;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const HISTORY_FILE: &str = "history.json";
+
+/// ids of the poems already shown by `random --no-repeat`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct History {
+    ids: Vec<String>,
+}
+
+impl History {
+    pub fn load() -> Result<Self> {
+        profile::load(HISTORY_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        profile::save(HISTORY_FILE, self)
+    }
+
+    pub fn add(&mut self, id: &str) {
+        if !self.contains(id) {
+            self.ids.push(id.to_string());
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.iter().any(|i| i == id)
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+}
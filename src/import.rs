@@ -0,0 +1,188 @@
+use crate::{Poem, PoemError};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// the source of the poems imported from the chinese-poetry repository, followed by the
+/// directory they came from, like `chinese-poetry/宋词`
+pub const CHINESE_POETRY: &str = "chinese-poetry";
+
+// 各个集子的字段不一样 词只有词牌 诗经楚辞的正文叫content 纳兰性德的叫para
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    rhythmic: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    dynasty: String,
+    #[serde(default, alias = "content", alias = "para")]
+    paragraphs: Vec<String>,
+}
+
+// 先看文件名再从里往外看目录名 全宋诗的poet.song.*.json放在全唐诗目录里
+const DYNASTIES: &[(&str, &str)] = &[
+    ("tang", "唐"),
+    ("song", "宋"),
+    ("yuan", "元"),
+    ("ming", "明"),
+    ("qing", "清"),
+    ("wudai", "五代"),
+    ("shijing", "先秦"),
+    ("chuci", "先秦"),
+    ("caocao", "两汉"),
+    ("唐", "唐"),
+    ("宋", "宋"),
+    ("元", "元"),
+    ("五代", "五代"),
+    ("诗经", "先秦"),
+    ("楚辞", "先秦"),
+    ("曹操", "两汉"),
+    ("纳兰性德", "清"),
+];
+
+/// the poems found in a checkout of the chinese-poetry repository
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub poems: Vec<Poem>,
+    /// the json files without any poem in them, like the lists of authors, or with poems of
+    /// no known dynasty
+    pub skipped: Vec<PathBuf>,
+}
+
+/// the poems of every json file under `dir`, a checkout of
+/// https://github.com/chinese-poetry/chinese-poetry
+///
+/// poems are tagged with the directory they came from, the dynasty is taken from the poem
+/// if it has one and from the names of the file and its directories otherwise;
+/// the texts are kept as they are, 全唐诗 is in traditional characters
+pub fn chinese_poetry(dir: impl AsRef<Path>) -> Result<Imported> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", dir.display()));
+    }
+    let mut files = Vec::new();
+    json_files(dir, &mut files)?;
+    if files.is_empty() {
+        return Err(anyhow!("no json files found in {}", dir.display()));
+    }
+
+    let mut imported = Imported::default();
+    for file in files {
+        let relative = file.strip_prefix(dir).unwrap_or(&file);
+        let poems = read_file(&file, relative)?;
+        if poems.is_empty() {
+            imported.skipped.push(file);
+        } else {
+            imported.poems.extend(poems);
+        }
+    }
+    Ok(imported)
+}
+
+fn json_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(PoemError::io(dir))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            json_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_file(file: &Path, relative: &Path) -> Result<Vec<Poem>> {
+    let s = fs::read_to_string(file).map_err(PoemError::io(file))?;
+    // 不是诗的文件格式各种各样 解析不了的当成没有诗
+    let entries: Vec<Value> = match serde_json::from_str(&s) {
+        Ok(Value::Array(entries)) => entries,
+        _ => return Ok(Vec::new()),
+    };
+    let source = match relative.iter().next() {
+        Some(top) if relative.parent().is_some_and(|p| !p.as_os_str().is_empty()) => {
+            format!("{}/{}", CHINESE_POETRY, top.to_string_lossy())
+        }
+        _ => CHINESE_POETRY.to_string(),
+    };
+    let dynasty = dynasty_of_path(relative);
+    Ok(entries
+        .into_iter()
+        .filter_map(|e| serde_json::from_value::<Entry>(e).ok())
+        .filter_map(|e| {
+            let content = e
+                .paragraphs
+                .iter()
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let title = if e.title.is_empty() {
+                e.rhythmic
+            } else {
+                e.title
+            };
+            let dynasty = dynasty_of(&e.dynasty).or(dynasty)?;
+            if content.is_empty() || title.is_empty() {
+                return None;
+            }
+            Some(Poem {
+                title,
+                author: if e.author.is_empty() {
+                    "佚名".to_string()
+                } else {
+                    e.author
+                },
+                dynasty: dynasty.to_string(),
+                content,
+                source: Some(source.clone()),
+                tags: Vec::new(),
+                translation: None,
+                appreciation: None,
+            })
+        })
+        .collect())
+}
+
+/// the dynasty a poem gives, in pinyin in some files
+fn dynasty_of(name: &str) -> Option<&str> {
+    if name.is_empty() {
+        return None;
+    }
+    let known = DYNASTIES
+        .iter()
+        .find(|(key, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, dynasty)| *dynasty);
+    Some(known.unwrap_or(name))
+}
+
+fn dynasty_of_path(relative: &Path) -> Option<&'static str> {
+    let names: Vec<String> = relative
+        .iter()
+        .rev()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .collect();
+    names.iter().find_map(|name| {
+        DYNASTIES
+            .iter()
+            .find(|(key, _)| name.contains(key))
+            .map(|(_, dynasty)| *dynasty)
+    })
+}
@@ -0,0 +1,942 @@
+use crate::{
+    error::{self, PoemError},
+    lines,
+    output::{Render, Style},
+    read_only::ReadOnlyDirectory,
+    status::dir_size,
+    Authors, Poem,
+};
+use anyhow::{anyhow, Context, Result};
+use cang_jie::{CangJieTokenizer, TokenizerOption, CANG_JIE};
+use colored::*;
+use futures::executor::block_on;
+use indicatif::ProgressBar;
+use jieba_rs::{Jieba, TokenizeMode};
+use once_cell::sync::Lazy;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, iter,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tantivy::{
+    directory::error::LockError,
+    fastfield::FastFieldReader,
+    schema::{
+        Field, IndexRecordOption, Schema, SchemaBuilder, TextFieldIndexing, TextOptions, FAST,
+        INDEXED, STORED,
+    },
+    tokenizer::{self, BoxTokenStream, PreTokenizedStream, PreTokenizedString, TextAnalyzer},
+    Document, Index, IndexWriter, TantivyError, Term,
+};
+
+/// how text is split into terms
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tokenizer {
+    /// every character is a term
+    Unicode,
+    /// words of the jieba dictionary are terms
+    Jieba,
+    /// like jieba, long words are also split into the shorter words in them
+    JiebaSearch,
+}
+
+impl fmt::Display for Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tokenizer::Unicode => "unicode",
+            Tokenizer::Jieba => "jieba",
+            Tokenizer::JiebaSearch => "jieba-search",
+        })
+    }
+}
+
+/// the tokenizer an index is built with, stored in the index directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenizerConfig {
+    pub tokenizer: Tokenizer,
+    /// the content of the user dictionary, one `word [freq] [tag]` per line
+    pub user_dict: Option<String>,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            tokenizer: Tokenizer::Unicode,
+            user_dict: None,
+        }
+    }
+}
+
+impl TokenizerConfig {
+    /// a config using the user dictionary at `user_dict`, which needs a jieba tokenizer
+    pub fn new(tokenizer: Tokenizer, user_dict: Option<&Path>) -> Result<Self> {
+        let user_dict = match user_dict {
+            Some(_) if tokenizer == Tokenizer::Unicode => {
+                return Err(anyhow!(
+                    "a user dictionary can not be used with the unicode tokenizer"
+                ))
+            }
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?,
+            ),
+            None => None,
+        };
+        let config = Self {
+            tokenizer,
+            user_dict,
+        };
+        // 词典有错时尽早报出来
+        config.build()?;
+        Ok(config)
+    }
+
+    fn build(&self) -> Result<TextAnalyzer> {
+        let mut worker = match self.tokenizer {
+            Tokenizer::Unicode => Jieba::empty(),
+            Tokenizer::Jieba | Tokenizer::JiebaSearch => Jieba::new(),
+        };
+        for (i, line) in self.user_dict.iter().flat_map(|d| d.lines()).enumerate() {
+            let mut parts = line.split_whitespace();
+            let word = match parts.next() {
+                Some(word) => word,
+                None => continue,
+            };
+            // 没有词频时由jieba估算一个能切分出该词的词频
+            let freq = parts
+                .next()
+                .map(|freq| {
+                    freq.parse().with_context(|| {
+                        format!("invalid frequency on line {} of the user dictionary", i + 1)
+                    })
+                })
+                .transpose()?;
+            worker.add_word(word, freq, parts.next());
+        }
+        let worker = Arc::new(worker);
+        Ok(match self.tokenizer {
+            Tokenizer::Unicode => TextAnalyzer::from(CangJieTokenizer {
+                worker,
+                option: TokenizerOption::Unicode,
+            }),
+            Tokenizer::Jieba => TextAnalyzer::from(CangJieTokenizer {
+                worker,
+                option: TokenizerOption::Default { hmm: false },
+            }),
+            Tokenizer::JiebaSearch => TextAnalyzer::from(SearchTokenizer(worker)),
+        })
+    }
+}
+
+/// a term of a text and where it is
+#[derive(Debug, Serialize)]
+pub struct Token {
+    pub text: String,
+    pub position: usize,
+    /// the byte offsets in the text
+    pub offset_from: usize,
+    pub offset_to: usize,
+}
+
+impl TokenizerConfig {
+    /// the terms `text` is split into, the same ones the index and the queries get
+    pub fn analyze(&self, text: &str) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        self.build()?.token_stream(text).process(&mut |t| {
+            tokens.push(Token {
+                text: t.text.clone(),
+                position: t.position,
+                offset_from: t.offset_from,
+                offset_to: t.offset_to,
+            })
+        });
+        Ok(tokens)
+    }
+}
+
+impl Render for Token {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}",
+            self.position,
+            self.text.cyan(),
+            format!("{}..{}", self.offset_from, self.offset_to).dimmed()
+        )
+    }
+}
+
+/// jieba in search mode
+///
+/// cang jie assumes the words do not overlap and gets the offsets of the short words wrong
+#[derive(Clone)]
+struct SearchTokenizer(Arc<Jieba>);
+
+impl tokenizer::Tokenizer for SearchTokenizer {
+    fn token_stream<'a>(&self, text: &'a str) -> BoxTokenStream<'a> {
+        // jieba给的是字符下标 tantivy要的是字节下标
+        let bytes: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(iter::once(text.len()))
+            .collect();
+        // 短词和包含它的长词在同一个位置 短语查询才能匹配上
+        let ends: Vec<usize> = self
+            .0
+            .tokenize(text, TokenizeMode::Default, false)
+            .iter()
+            .map(|t| t.end)
+            .collect();
+        let tokens = self
+            .0
+            .tokenize(text, TokenizeMode::Search, false)
+            .into_iter()
+            .map(|t| tokenizer::Token {
+                offset_from: bytes[t.start],
+                offset_to: bytes[t.end],
+                position: ends.partition_point(|&end| end < t.end),
+                text: t.word.to_string(),
+                position_length: 1,
+            })
+            .collect();
+        BoxTokenStream::from(PreTokenizedStream::from(PreTokenizedString {
+            text: text.to_string(),
+            tokens,
+        }))
+    }
+}
+
+/// the tokenizer config of the index in `path`, the default one if it has none
+pub fn tokenizer_config(path: impl AsRef<Path>) -> Result<TokenizerConfig> {
+    let path = path.as_ref().join(TOKENIZER_FILE);
+    if !path.exists() {
+        return Ok(TokenizerConfig::default());
+    }
+    let content = error::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| {
+        PoemError::IndexCorrupt {
+            path: path.parent().unwrap_or(&path).to_path_buf(),
+            reason: format!("{} is broken ({})", TOKENIZER_FILE, e),
+        }
+        .into()
+    })
+}
+
+pub(crate) const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// the memory each indexing thread buffers documents in
+const HEAP_PER_THREAD: usize = 1024 * 1024 * 10;
+
+/// the process writing an index, in the directory of the index
+const WRITER_PID_FILE: &str = "writer.pid";
+
+static LOCK_TIMEOUT_MS: AtomicU64 = AtomicU64::new(10_000);
+
+/// how many poems are turned into documents at once
+const BATCH: usize = 1024;
+
+/// how many poems indexing added, reindexed because they changed and removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Indexed {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl fmt::Display for Indexed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "added {}, updated {}, removed {}",
+            self.added, self.updated, self.removed
+        )
+    }
+}
+
+/// index the poems into `path` with `threads` threads, as many as there are cores if not given,
+/// returns how many documents were added, updated and removed
+///
+/// the years the authors lived are looked up in `authors`,
+/// only the poems not yet in the index or changed since they were indexed are added unless
+/// `force` is set,
+/// the index is rebuilt with `tokenizer` if it is given and differs from the current one
+pub fn build_index(
+    path: impl AsRef<Path>,
+    poems: Vec<Poem>,
+    authors: &Authors,
+    force: bool,
+    tokenizer: Option<TokenizerConfig>,
+    threads: Option<usize>,
+    bar: &ProgressBar,
+) -> Result<Indexed> {
+    bar.set_length(poems.len() as _);
+    let poems = poems.into_iter().map(|poem| {
+        bar.inc(1);
+        Ok(poem)
+    });
+    let counts = index_poems(path, poems, authors, force, tokenizer, threads)?;
+    bar.finish();
+    Ok(counts)
+}
+
+/// like `build_index` for poems read one at a time, like the ones of a json lines file,
+/// they are indexed a batch at a time so the memory needed does not grow with their number
+pub fn index_poems(
+    path: impl AsRef<Path>,
+    poems: impl Iterator<Item = Result<Poem>>,
+    authors: &Authors,
+    force: bool,
+    tokenizer: Option<TokenizerConfig>,
+    threads: Option<usize>,
+) -> Result<Indexed> {
+    let path = path.as_ref();
+    let fields = fields();
+    let lines_path = lines::index_path(path);
+    let current = match tokenizer_config(path) {
+        Ok(current) => current,
+        // 反正要重建 坏了的配置不要紧
+        Err(_) if force && tokenizer.is_some() => TokenizerConfig::default(),
+        Err(e) => return Err(e),
+    };
+    let tokenizer = tokenizer.unwrap_or_else(|| current.clone());
+    // 旧的索引没有id字段 无法增量 只能重建 逐句索引缺失或者分词方式变了也一起重建
+    let rebuild = force
+        || !path.exists()
+        || open_or_create_index(path, Access::Read, &current)?.schema() != *schema()
+        || !lines_path.exists()
+        || lines::open_or_create_index(&lines_path, Access::Read, &current)?.schema()
+            != *lines::schema()
+        || tokenizer != current;
+    // 重建时占着原来的索引 另一个进程不会同时重建或者写进去 原来的索引打不开就算了
+    let guard = match rebuild && path.exists() {
+        true => match open_or_create_index(path, Access::Write, &current) {
+            Ok(index) => Some(lock_writer(path, || {
+                index.writer_with_num_threads(1, HEAP_PER_THREAD)
+            })?),
+            Err(_) => None,
+        },
+        false => None,
+    };
+    // 重建时先建在旁边的临时目录里 提交成功后再换过去 中途失败不影响原来的索引
+    let target = if rebuild {
+        temp_path(path)
+    } else {
+        path.to_path_buf()
+    };
+    let access = match rebuild {
+        true => Access::Create,
+        false => Access::Write,
+    };
+    let index = open_or_create_index(&target, access, &tokenizer)?;
+    let lines_index = lines::open_or_create_index(lines::index_path(&target), access, &tokenizer)?;
+    if rebuild {
+        fs::write(
+            target.join(TOKENIZER_FILE),
+            serde_json::to_string(&tokenizer)?,
+        )?;
+    }
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or(0))
+        .build()?;
+    // tantivy最多用8个线程
+    let threads = pool.current_num_threads().min(8);
+    let mut writer = lock_writer(&target, || {
+        index.writer_with_num_threads(threads, threads * HEAP_PER_THREAD)
+    })?;
+    let mut lines_writer = lock_writer(&lines::index_path(&target), || {
+        lines_index.writer_with_num_threads(threads, threads * HEAP_PER_THREAD)
+    })?;
+    // 等到了锁再看已经有哪些诗 等的时候别的进程可能已经改了索引
+    let indexed = indexed_fingerprints(&index)?;
+    // 分句和转换成文档并行做 写入的线程由tantivy管
+    let add = |batch: Vec<Poem>| {
+        let documents: Vec<(Document, Vec<Document>)> = pool.install(|| {
+            batch
+                .into_par_iter()
+                .map(|poem| (lines::documents(&poem), poem))
+                .map(|(lines, poem)| (poem.into_document(authors), lines))
+                .collect()
+        });
+        for (doc, lines) in documents {
+            lines.into_iter().for_each(|doc| {
+                lines_writer.add_document(doc);
+            });
+            writer.add_document(doc);
+        }
+    };
+
+    let mut ids = HashSet::new();
+    let (mut added, mut updated) = (0, 0);
+    let mut batch = Vec::with_capacity(BATCH);
+    for poem in poems {
+        let poem = poem?;
+        let id = poem.raw_id();
+        if !ids.insert(id) {
+            continue;
+        }
+        // id一样但别的字段改了 删掉旧的文档再加 删除在加之前所以不会删掉新的
+        match indexed.get(&id) {
+            Some(&fingerprint) if fingerprint == poem.fingerprint(authors) => continue,
+            Some(_) => {
+                writer.delete_term(Term::from_field_u64(fields["id"], id));
+                lines_writer.delete_term(Term::from_field_u64(lines::fields()["poem_id"], id));
+                updated += 1;
+            }
+            None => added += 1,
+        }
+        batch.push(poem);
+        if batch.len() == BATCH {
+            add(std::mem::replace(&mut batch, Vec::with_capacity(BATCH)));
+        }
+    }
+    add(batch);
+
+    let stale: Vec<&u64> = indexed.keys().filter(|id| !ids.contains(id)).collect();
+    stale.iter().for_each(|&&id| {
+        writer.delete_term(Term::from_field_u64(fields["id"], id));
+        lines_writer.delete_term(Term::from_field_u64(lines::fields()["poem_id"], id));
+    });
+    lines_writer.commit()?;
+    writer.commit()?;
+    lines_writer.wait_merging_threads()?;
+    writer.wait_merging_threads()?;
+
+    if rebuild {
+        replace_dir(&target, path)?;
+    }
+    // 换好了再放开 等着的进程拿到锁时看到的就是新的索引
+    drop(guard);
+
+    Ok(Indexed {
+        added,
+        updated,
+        removed: stale.len(),
+    })
+}
+
+/// the sibling directory an index in `path` is rebuilt in
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// move the directory `from` to `to`, replacing what is in `to`
+pub(crate) fn replace_dir(from: &Path, to: &Path) -> Result<()> {
+    if !to.exists() {
+        fs::rename(from, to)?;
+        return Ok(());
+    }
+    // rename不能覆盖非空的目录 只能先把旧的挪开 两次rename之间中断时旧的索引还在backup里
+    let backup = to.with_file_name(format!(
+        ".{}.old",
+        to.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if backup.exists() {
+        fs::remove_dir_all(&backup)?;
+    }
+    fs::rename(to, &backup).with_context(|| format!("failed to move {} away", to.display()))?;
+    fs::rename(from, to).with_context(|| {
+        format!(
+            "failed to move the new index into {}, the old one is kept in {}",
+            to.display(),
+            backup.display()
+        )
+    })?;
+    fs::remove_dir_all(&backup)?;
+    Ok(())
+}
+
+/// add `poems` to the index in `path` without touching the others, with the years of their
+/// authors in `authors`, returns how many of them were not indexed yet
+pub fn add_to_index(path: impl AsRef<Path>, poems: &[Poem], authors: &Authors) -> Result<usize> {
+    let (index, mut writer, mut lines_writer) = writers(path.as_ref())?;
+    let indexed = indexed_ids(&index, fields()["id"])?;
+
+    let mut added = 0;
+    for poem in poems.iter().filter(|p| !indexed.contains(&p.raw_id())) {
+        lines::documents(poem).into_iter().for_each(|doc| {
+            lines_writer.add_document(doc);
+        });
+        writer.add_document(poem.clone().into_document(authors));
+        added += 1;
+    }
+    lines_writer.commit()?;
+    writer.commit()?;
+
+    Ok(added)
+}
+
+/// remove `poems` from the index in `path`
+pub fn remove_from_index(path: impl AsRef<Path>, poems: &[Poem]) -> Result<()> {
+    let (_, mut writer, mut lines_writer) = writers(path.as_ref())?;
+    for poem in poems {
+        writer.delete_term(Term::from_field_u64(fields()["id"], poem.raw_id()));
+        lines_writer.delete_term(Term::from_field_u64(
+            lines::fields()["poem_id"],
+            poem.raw_id(),
+        ));
+    }
+    lines_writer.commit()?;
+    writer.commit()?;
+    Ok(())
+}
+
+/// what `optimize` did to an index
+#[derive(Debug, Serialize)]
+pub struct Optimized {
+    pub path: String,
+    /// the segments of the poem index before and after
+    pub segments_before: usize,
+    pub segments_after: usize,
+    /// the documents deleted but still on disk before, there are none after
+    pub deleted_before: u32,
+    /// bytes on disk before and after, the line index included
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// merge the segments of the index in `path` and its line index down to one each,
+/// which also drops the documents deleted from them, and remove the files no longer used
+pub fn optimize(path: impl AsRef<Path>) -> Result<Optimized> {
+    let path = path.as_ref();
+    let size_before = dir_size(path)?;
+    let (index, mut writer, mut lines_writer) = writers(path)?;
+    let metas = index.searchable_segment_metas()?;
+    let (segments_before, deleted_before) = (
+        metas.len(),
+        metas.iter().map(|m| m.num_deleted_docs()).sum(),
+    );
+    let lines_index = lines::open_or_create_index(
+        lines::index_path(path),
+        Access::Read,
+        &tokenizer_config(path)?,
+    )?;
+    merge_all(&lines_index, &mut lines_writer)?;
+    merge_all(&index, &mut writer)?;
+    lines_writer.wait_merging_threads()?;
+    writer.wait_merging_threads()?;
+    // 合并用的writer还留着合并前的文件 换新的writer才删得掉
+    let (_, writer, lines_writer) = writers(path)?;
+    block_on(lines_writer.garbage_collect_files())?;
+    block_on(writer.garbage_collect_files())?;
+    Ok(Optimized {
+        path: path.display().to_string(),
+        segments_before,
+        segments_after: index.searchable_segment_ids()?.len(),
+        deleted_before,
+        size_before,
+        size_after: dir_size(path)?,
+    })
+}
+
+// 只有一段而且没有删掉的文档时不用合并
+fn merge_all(index: &Index, writer: &mut IndexWriter) -> Result<()> {
+    let ids = index.searchable_segment_ids()?;
+    let deleted = index
+        .searchable_segment_metas()?
+        .iter()
+        .any(|m| m.has_deletes());
+    if ids.len() > 1 || (ids.len() == 1 && deleted) {
+        block_on(writer.merge(&ids))?;
+    }
+    Ok(())
+}
+
+impl Render for Optimized {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        writeln!(f, "路径：{}", self.path)?;
+        writeln!(
+            f,
+            "段数：{} → {}",
+            self.segments_before, self.segments_after
+        )?;
+        writeln!(f, "清除已删除的文档：{}", self.deleted_before)?;
+        write!(
+            f,
+            "大小：{:.1} KiB → {:.1} KiB",
+            self.size_before as f64 / 1024.0,
+            self.size_after as f64 / 1024.0
+        )
+    }
+}
+
+/// an index of `poems` in memory, gone when the program exits, without the line index
+pub(crate) fn index_in_ram(
+    poems: &[Poem],
+    authors: &Authors,
+    tokenizer: &TokenizerConfig,
+) -> Result<Index> {
+    let index = Index::create_in_ram(schema().clone());
+    index.tokenizers().register(CANG_JIE, tokenizer.build()?);
+    let mut writer = index.writer(1024 * 1024 * 10)?;
+    let mut ids = HashSet::with_capacity(poems.len());
+    for poem in poems.iter().filter(|p| ids.insert(p.raw_id())) {
+        writer.add_document(poem.clone().into_document(authors));
+    }
+    writer.commit()?;
+    Ok(index)
+}
+
+/// writers of the existing index in `path` and its line index
+fn writers(path: &Path) -> Result<(Index, IndexWriter, IndexWriter)> {
+    let tokenizer = tokenizer_config(path)?;
+    let index = open_or_create_index(path, Access::Write, &tokenizer)?;
+    let lines_path = lines::index_path(path);
+    if index.schema() != *schema() || !lines_path.exists() {
+        return Err(PoemError::IndexCorrupt {
+            path: path.to_path_buf(),
+            reason: "it is outdated".to_string(),
+        }
+        .into());
+    }
+    let lines_index = lines::open_or_create_index(&lines_path, Access::Write, &tokenizer)?;
+    let writer = lock_writer(path, || index.writer(1024 * 1024 * 10))?;
+    let lines_writer = lock_writer(&lines_path, || lines_index.writer(1024 * 1024 * 10))?;
+    Ok((index, writer, lines_writer))
+}
+
+/// the writer `open` makes of the index in `path`, if another process is writing the index
+/// it waits as long as `set_lock_timeout` says for it to finish
+fn lock_writer(
+    path: &Path,
+    open: impl Fn() -> tantivy::Result<IndexWriter>,
+) -> Result<IndexWriter> {
+    let deadline = Instant::now() + Duration::from_millis(LOCK_TIMEOUT_MS.load(Ordering::Relaxed));
+    let pid_path = path.join(WRITER_PID_FILE);
+    let mut waiting = false;
+    loop {
+        match open() {
+            Ok(writer) => {
+                // tantivy的锁文件是空的 自己记下是谁在写
+                fs::write(&pid_path, process::id().to_string())?;
+                return Ok(writer);
+            }
+            Err(TantivyError::LockFailure(LockError::LockBusy, _)) if Instant::now() < deadline => {
+                if !waiting {
+                    log::info!("waiting for another process writing {}", path.display());
+                    waiting = true;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            Err(TantivyError::LockFailure(LockError::LockBusy, _)) => {
+                let pid = fs::read_to_string(&pid_path)
+                    .ok()
+                    .and_then(|s| s.trim().parse().ok());
+                return Err(PoemError::IndexLocked {
+                    path: path.to_path_buf(),
+                    pid,
+                }
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// how long to wait for another process writing an index to finish before giving up,
+/// 10 seconds if it is not set
+pub fn set_lock_timeout(timeout: Duration) {
+    LOCK_TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// how an index is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    /// only searched, nothing in the directory is created or changed
+    Read,
+    /// an existing index a writer is made of
+    Write,
+    /// wipe the directory and create an empty index in it
+    Create,
+}
+
+pub(crate) fn open_or_create_index(
+    path: impl AsRef<Path>,
+    access: Access,
+    tokenizer: &TokenizerConfig,
+) -> Result<Index> {
+    open_or_create(path, schema(), access, tokenizer)
+}
+
+/// open the index in `path` the way `access` says, `schema` is the one of a created index
+pub(crate) fn open_or_create(
+    path: impl AsRef<Path>,
+    schema: &Schema,
+    access: Access,
+    tokenizer: &TokenizerConfig,
+) -> Result<Index> {
+    let path = path.as_ref();
+
+    let opened = match access {
+        Access::Read => ReadOnlyDirectory::open(path)
+            .map_err(TantivyError::from)
+            .and_then(Index::open),
+        Access::Write => Index::open_in_dir(path),
+        Access::Create => {
+            if path.exists() {
+                fs::remove_dir_all(path)?;
+            }
+            fs::create_dir_all(path)?;
+            Index::create_in_dir(path, schema.clone())
+        }
+    };
+    let index = opened.map_err(|e| match path.exists() {
+        true => PoemError::IndexCorrupt {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        },
+        false => PoemError::IndexMissing(path.to_path_buf()),
+    })?;
+    // 分词器只注册在内存里 只读打开也没关系
+    index.tokenizers().register(CANG_JIE, tokenizer.build()?);
+
+    Ok(index)
+}
+
+// schema只需要构建一次 每个文档的转换都复用它
+static SCHEMA: Lazy<(Schema, HashMap<&'static str, Field>)> = Lazy::new(build_schema);
+
+/// the schema of the index
+pub(crate) fn schema() -> &'static Schema {
+    &SCHEMA.0
+}
+
+/// every field of the schema by name
+pub(crate) fn fields() -> &'static HashMap<&'static str, Field> {
+    &SCHEMA.1
+}
+
+fn build_schema() -> (Schema, HashMap<&'static str, Field>) {
+    let mut schema_builder = SchemaBuilder::default();
+
+    let text_options = text_options();
+
+    let id = schema_builder.add_u64_field("id", INDEXED | STORED | FAST);
+    let title = schema_builder.add_text_field("title", text_options.clone());
+    let author = schema_builder.add_text_field("author", text_options.clone());
+    let dynasty = schema_builder.add_text_field("dynasty", text_options.clone());
+    let content = schema_builder.add_text_field("content", text_options.clone());
+    let dynasty_facet = schema_builder.add_facet_field("dynasty_facet", INDEXED);
+    let author_facet = schema_builder.add_facet_field("author_facet", INDEXED);
+    let chars = schema_builder.add_u64_field("chars", INDEXED | FAST);
+    let lines = schema_builder.add_u64_field("lines", INDEXED | FAST);
+    let form = schema_builder.add_facet_field("form", INDEXED);
+    let source = schema_builder.add_text_field("source", STORED);
+    let tags = schema_builder.add_facet_field("tags", INDEXED | STORED);
+    let active_from = schema_builder.add_i64_field("active_from", INDEXED | FAST);
+    let active_to = schema_builder.add_i64_field("active_to", INDEXED | FAST);
+    let translation = schema_builder.add_text_field("translation", text_options.clone());
+    let appreciation = schema_builder.add_text_field("appreciation", text_options);
+    let fingerprint = schema_builder.add_u64_field("fingerprint", FAST);
+
+    let schema = schema_builder.build();
+
+    let mut fileds = HashMap::with_capacity(16);
+    fileds.insert("id", id);
+    fileds.insert("title", title);
+    fileds.insert("author", author);
+    fileds.insert("dynasty", dynasty);
+    fileds.insert("content", content);
+    fileds.insert("dynasty_facet", dynasty_facet);
+    fileds.insert("author_facet", author_facet);
+    fileds.insert("chars", chars);
+    fileds.insert("lines", lines);
+    fileds.insert("form", form);
+    fileds.insert("source", source);
+    fileds.insert("tags", tags);
+    fileds.insert("active_from", active_from);
+    fileds.insert("active_to", active_to);
+    fileds.insert("translation", translation);
+    fileds.insert("appreciation", appreciation);
+    fileds.insert("fingerprint", fingerprint);
+
+    (schema, fileds)
+}
+
+/// stored text fields tokenized by cang jie
+pub(crate) fn text_options() -> TextOptions {
+    let text_indexing = TextFieldIndexing::default()
+        .set_tokenizer(CANG_JIE) // Set custom tokenizer
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    TextOptions::default()
+        .set_indexing_options(text_indexing)
+        .set_stored()
+}
+
+/// ids of all alive documents in the index
+pub(crate) fn indexed_ids(index: &Index, id: Field) -> Result<HashSet<u64>> {
+    let searcher = index.reader()?.searcher();
+    let mut ids = HashSet::new();
+    for segment in searcher.segment_readers() {
+        let fast_field = segment.fast_fields().u64(id)?;
+        ids.extend(segment.doc_ids_alive().map(|doc_id| fast_field.get(doc_id)));
+    }
+    Ok(ids)
+}
+
+/// the fingerprint of every alive document by the id of its poem, see `Poem::fingerprint`
+pub(crate) fn indexed_fingerprints(index: &Index) -> Result<HashMap<u64, u64>> {
+    let searcher = index.reader()?.searcher();
+    let mut fingerprints = HashMap::new();
+    for segment in searcher.segment_readers() {
+        let ids = segment.fast_fields().u64(fields()["id"])?;
+        let prints = segment.fast_fields().u64(fields()["fingerprint"])?;
+        fingerprints.extend(
+            segment
+                .doc_ids_alive()
+                .map(|doc_id| (ids.get(doc_id), prints.get(doc_id))),
+        );
+    }
+    Ok(fingerprints)
+}
+
+/// the text of `field`, an error if the document has no such text field
+pub(crate) fn extract_field_text(doc: &Document, field: Field) -> Result<String> {
+    let name = schema().get_field_name(field);
+    let value = doc
+        .get_first(field)
+        .ok_or_else(|| anyhow!("document has no field `{}`", name))?;
+    let text = value
+        .text()
+        .ok_or_else(|| anyhow!("field `{}` of document is not text", name))?;
+    Ok(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    fn reindex(path: &Path, poems: &[Poem]) -> Indexed {
+        build_index(
+            path,
+            poems.to_vec(),
+            &Authors::bundled().unwrap(),
+            false,
+            None,
+            None,
+            &ProgressBar::hidden(),
+        )
+        .unwrap()
+    }
+
+    fn lines_count(path: &Path) -> u64 {
+        let lines = lines::open_or_create_index(
+            lines::index_path(path),
+            Access::Read,
+            &tokenizer_config(path).unwrap(),
+        )
+        .unwrap();
+        lines.reader().unwrap().searcher().num_docs()
+    }
+
+    #[test]
+    fn only_new_poems_are_added() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        let mut poems = vec![poem("静夜思", "床前明月光，疑是地上霜。")];
+        assert_eq!(reindex(&path, &poems).added, 1);
+        poems.push(poem("月下独酌", "花间一壶酒，独酌无相亲。"));
+        assert_eq!(
+            reindex(&path, &poems),
+            Indexed {
+                added: 1,
+                updated: 0,
+                removed: 0
+            }
+        );
+        assert_eq!(reindex(&path, &poems), Indexed::default());
+    }
+
+    #[test]
+    fn changed_poems_are_reindexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        let mut poems = vec![
+            poem("静夜思", "床前明月光，疑是地上霜。"),
+            poem("月下独酌", "花间一壶酒，独酌无相亲。"),
+        ];
+        reindex(&path, &poems);
+        // 只改不算在id里的字段
+        poems[0].dynasty = "盛唐".to_string();
+        poems[1].translation = Some("花丛中摆下一壶好酒".to_string());
+        assert_eq!(
+            reindex(&path, &poems),
+            Indexed {
+                added: 0,
+                updated: 2,
+                removed: 0
+            }
+        );
+        let index = Index::open_in_dir(&path).unwrap();
+        let indexed = indexed_fingerprints(&index).unwrap();
+        let authors = Authors::bundled().unwrap();
+        assert_eq!(indexed.len(), 2);
+        for poem in &poems {
+            assert_eq!(indexed[&poem.raw_id()], poem.fingerprint(&authors));
+        }
+        assert_eq!(lines_count(&path), 4);
+        let repo = crate::PoemRepository::open(&path)
+            .unwrap()
+            .with_poems(poems.clone());
+        let hits = repo.search("盛唐", &Default::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].poem.title, "静夜思");
+        assert_eq!(reindex(&path, &poems), Indexed::default());
+    }
+
+    #[test]
+    fn years_come_from_the_authors_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        let poems = vec![poem("静夜思", "床前明月光，疑是地上霜。")];
+        reindex(&path, &poems);
+        let file = dir.path().join("authors.json");
+        let author = r#"[{"name": "李白", "dynasty": "唐", "birth": 700, "death": 760}]"#;
+        fs::write(&file, author).unwrap();
+        let authors = Authors::load(Some(&file)).unwrap();
+        let bar = ProgressBar::hidden();
+        let indexed = build_index(&path, poems, &authors, false, None, None, &bar).unwrap();
+        assert_eq!(indexed.updated, 1);
+        let searcher = Index::open_in_dir(&path)
+            .unwrap()
+            .reader()
+            .unwrap()
+            .searcher();
+        let years: Vec<i64> = searcher
+            .segment_readers()
+            .iter()
+            .flat_map(|segment| {
+                let from = segment.fast_fields().i64(fields()["active_from"]).unwrap();
+                segment.doc_ids_alive().map(move |doc| from.get(doc))
+            })
+            .collect();
+        assert_eq!(years, [700]);
+    }
+
+    #[test]
+    fn poems_gone_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        let mut poems = vec![
+            poem("静夜思", "床前明月光，疑是地上霜。"),
+            poem("月下独酌", "花间一壶酒，独酌无相亲。"),
+        ];
+        reindex(&path, &poems);
+        poems.pop();
+        assert_eq!(reindex(&path, &poems).removed, 1);
+        let index = Index::open_in_dir(&path).unwrap();
+        assert_eq!(indexed_fingerprints(&index).unwrap().len(), 1);
+        assert_eq!(lines_count(&path), 2);
+    }
+}
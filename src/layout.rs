@@ -0,0 +1,275 @@
+use crate::{annotate::pinyin_cells, output::Style, Poem};
+use colored::*;
+use std::{fmt, ops::Range};
+
+/// how a poem is laid out in the terminal
+pub trait Layout {
+    /// render `poem` with the given byte ranges of its content highlighted,
+    /// and the relevance score if there is one
+    fn render(
+        &self,
+        poem: &Poem,
+        highlights: &[Range<usize>],
+        score: Option<f32>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result;
+}
+
+fn paint(s: &str, highlighted: bool) -> ColoredString {
+    if highlighted {
+        s.red().bold()
+    } else {
+        s.cyan()
+    }
+}
+
+pub(crate) fn header(poem: &Poem, score: Option<f32>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "\t{} {}", poem.title.bright_cyan(), poem.id().dimmed())?;
+    if let Some(score) = score {
+        write!(f, " {}", format_score(score))?;
+    }
+    writeln!(f)?;
+    writeln!(f, "\t{}〔{}〕", poem.author.cyan(), poem.dynasty.cyan())
+}
+
+/// title, author and the content line by line
+pub struct Horizontal;
+
+impl Layout for Horizontal {
+    fn render(
+        &self,
+        poem: &Poem,
+        highlights: &[Range<usize>],
+        score: Option<f32>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        header(poem, score, f)?;
+        highlighted(&poem.content, highlights, f)?;
+        writeln!(f)
+    }
+}
+
+/// what goes below `poem`: the translation with `--show-translation`, the appreciation
+/// with `--full`, the notes of the user with `--with-notes` and the biography of the author
+/// with `--with-author-bio`
+pub(crate) fn below(poem: &Poem, style: &Style, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let translation = style.show_translation || style.full;
+    if let Some(translation) = poem.translation.as_ref().filter(|_| translation) {
+        section("译文", translation, style, f)?;
+    }
+    if let Some(appreciation) = poem.appreciation.as_ref().filter(|_| style.full) {
+        section("赏析", appreciation, style, f)?;
+    }
+    if style.with_notes {
+        let notes = style.notes.of(&poem.id());
+        if !notes.is_empty() {
+            let notes: Vec<String> = notes
+                .iter()
+                .enumerate()
+                .map(|(i, note)| format!("{}. {}", i + 1, note.text))
+                .collect();
+            section("注释", &notes.join("\n"), style, f)?;
+        }
+    }
+    if style.with_author_bio {
+        if let Some(author) = style.authors.get(&poem.author, &poem.dynasty) {
+            let mut about = author.name.clone();
+            if let Some(courtesy_name) = &author.courtesy_name {
+                about.push_str(&format!(" 字{}", courtesy_name));
+            }
+            if let Some(years) = author.years() {
+                about.push_str(&format!(" {}", years));
+            }
+            if !author.biography.is_empty() {
+                about.push_str(&format!("\n{}", author.biography));
+            }
+            section("作者", &about, style, f)?;
+        }
+    }
+    Ok(())
+}
+
+fn section(label: &str, text: &str, style: &Style, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if style.markdown {
+        write!(f, "\n\n**{}**\n\n{}", label, text)
+    } else {
+        writeln!(f, "\t{}", label.dimmed())?;
+        writeln!(f, "{}", text)
+    }
+}
+
+pub(crate) fn format_score(score: f32) -> ColoredString {
+    format!("{:.2}", score).yellow()
+}
+
+/// `text` with the given byte ranges highlighted
+pub(crate) fn highlighted(
+    text: &str,
+    highlights: &[Range<usize>],
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    let mut start = 0;
+    for range in highlights {
+        write!(f, "{}", paint(&text[start..range.start], false))?;
+        write!(f, "{}", paint(&text[range.clone()], true))?;
+        start = range.end;
+    }
+    write!(f, "{}", paint(&text[start..], false))
+}
+
+/// markdown for notes apps: the title as a heading, the author and dynasty below it
+/// and the content as a blockquote, highlights in bold
+pub struct Markdown;
+
+impl Layout for Markdown {
+    fn render(
+        &self,
+        poem: &Poem,
+        highlights: &[Range<usize>],
+        score: Option<f32>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        markdown_header(poem, score, f)?;
+        markdown_quote(&poem.content, highlights, f)
+    }
+}
+
+pub(crate) fn markdown_header(
+    poem: &Poem,
+    score: Option<f32>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    write!(f, "## {}", poem.title)?;
+    if let Some(score) = score {
+        write!(f, " ({:.2})", score)?;
+    }
+    writeln!(f, "\n")?;
+    writeln!(f, "{}〔{}〕\n", poem.author, poem.dynasty)
+}
+
+/// `text` as a blockquote keeping its line breaks, the given byte ranges in bold
+pub(crate) fn markdown_quote(
+    text: &str,
+    highlights: &[Range<usize>],
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    // 相邻的高亮要合起来 不然`****`会被当成普通字符
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in highlights {
+        match merged.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => merged.push(range.clone()),
+        }
+    }
+    let mut marked = String::new();
+    let mut start = 0;
+    for range in merged {
+        marked.push_str(&text[start..range.start]);
+        marked.push_str(&format!("**{}**", &text[range.clone()]));
+        start = range.end;
+    }
+    marked.push_str(&text[start..]);
+    // 引用里的换行会被合并 行尾加反斜杠强制换行
+    let lines: Vec<String> = marked.split('\n').map(|l| format!("> {}", l)).collect();
+    write!(f, "{}", lines.join("\\\n"))
+}
+
+/// like `Horizontal` with the pinyin above every line, each character aligned with its pinyin
+pub struct Pinyin;
+
+impl Layout for Pinyin {
+    fn render(
+        &self,
+        poem: &Poem,
+        highlights: &[Range<usize>],
+        score: Option<f32>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        header(poem, score, f)?;
+        let mut offset = 0;
+        for line in poem.content.split('\n') {
+            let cells = pinyin_cells(line);
+            for (_, py, width) in &cells {
+                write!(f, "{:<width$}", py, width = width)?;
+            }
+            writeln!(f)?;
+            for (c, _, width) in &cells {
+                let highlighted = highlights.iter().any(|r| r.contains(&offset));
+                // 汉字占两列 其余的占一列
+                let pad = width - if c.is_ascii() { 1 } else { 2 };
+                write!(
+                    f,
+                    "{}{:pad$}",
+                    paint(&c.to_string(), highlighted),
+                    "",
+                    pad = pad
+                )?;
+                offset += c.len_utf8();
+            }
+            writeln!(f)?;
+            offset += 1;
+        }
+        Ok(())
+    }
+}
+
+/// the classical layout: columns from right to left, each read from top to bottom,
+/// the title and author take the rightmost columns
+pub struct Vertical;
+
+impl Layout for Vertical {
+    fn render(
+        &self,
+        poem: &Poem,
+        highlights: &[Range<usize>],
+        score: Option<f32>,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        // 分数放不进竖排的格子里 单独放在最上面
+        if let Some(score) = score {
+            writeln!(f, "{}", format_score(score))?;
+        }
+        let mut columns: Vec<Vec<ColoredString>> = vec![
+            poem.title
+                .chars()
+                .map(|c| c.to_string().bright_cyan())
+                .collect(),
+            format!("{}〔{}〕", poem.author, poem.dynasty)
+                .chars()
+                .map(|c| c.to_string().cyan())
+                .collect(),
+        ];
+        let mut offset = 0;
+        for line in poem.content.split('\n') {
+            let mut column = Vec::new();
+            for c in line.chars() {
+                let highlighted = highlights.iter().any(|r| r.contains(&offset));
+                column.push(paint(&full_width(c).to_string(), highlighted));
+                offset += c.len_utf8();
+            }
+            columns.push(column);
+            offset += 1;
+        }
+
+        let rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+        for row in 0..rows {
+            for column in columns.iter().rev() {
+                match column.get(row) {
+                    Some(cell) => write!(f, "{}　", cell)?,
+                    None => write!(f, "　　")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// the full width form of ascii characters so every cell is two columns wide
+fn full_width(c: char) -> char {
+    match c {
+        ' ' => '　',
+        '!'..='~' => char::from_u32(c as u32 - 0x21 + 0xFF01).unwrap_or(c),
+        _ => c,
+    }
+}
@@ -0,0 +1,95 @@
+//! 诗词仓库 可以被其他程序直接依赖
+//!
+//! ```no_run
+//! use poem::PoemRepository;
+//!
+//! let repo = PoemRepository::open(".poem_index")?;
+//! for p in repo.search("明月", &Default::default())? {
+//!     println!("{}", p);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+pub mod annotate;
+mod authors;
+pub mod backup;
+pub mod bench;
+pub mod card;
+pub mod classify;
+pub mod clipboard;
+pub mod collection;
+pub mod config;
+pub mod couplet;
+pub mod csv;
+pub mod dedup;
+pub mod epub;
+mod error;
+pub mod explain;
+pub mod export;
+mod favorites;
+pub mod feihua;
+pub mod fetch;
+mod filter;
+pub mod format;
+pub mod grep;
+mod history;
+pub mod import;
+mod index;
+pub mod layout;
+mod lines;
+pub mod memorize;
+mod notes;
+pub mod output;
+pub mod pick;
+mod poem;
+pub mod prebuilt;
+pub mod profile;
+mod query;
+pub mod quiz;
+mod read_only;
+pub mod repl;
+mod repository;
+pub mod rhyme;
+pub mod semantic;
+pub mod server;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stat;
+mod status;
+mod storage;
+mod store;
+mod tags;
+#[cfg(test)]
+mod testing;
+pub mod timeline;
+pub mod timings;
+pub mod tones;
+mod user_poems;
+pub mod validate;
+mod verify;
+pub mod watch;
+
+pub use authors::{Author, Authors};
+pub use classify::Form;
+pub use error::PoemError;
+pub use favorites::Favorites;
+pub use filter::{PoemFilter, ShapeFilter};
+pub use history::History;
+pub use index::{
+    add_to_index, build_index, index_poems, optimize, remove_from_index, set_lock_timeout,
+    tokenizer_config, Indexed, Optimized, Token, Tokenizer, TokenizerConfig,
+};
+pub use lines::{Line, LineHit};
+pub use notes::{Note, Notes, NumberedNote};
+pub use poem::Poem;
+pub use query::SearchOptions;
+pub use repository::{
+    bundled_poems, federated_search, load_poem, load_poems, load_poems_matching, Hit, ListOrder,
+    PoemRepository, Snippet, SortBy,
+};
+pub use stat::{frequencies, words_count, Count, FreqUnit, Stat};
+pub use status::{index_status, IndexStatus};
+pub use storage::{set_storage, Storage};
+pub use store::PoemStore;
+pub use tags::{tag_counts, TagCount, Tags};
+pub use user_poems::UserPoems;
+pub use verify::{verify_index, Verification};
@@ -0,0 +1,168 @@
+use crate::{
+    index::{extract_field_text, open_or_create, text_options, Access, TokenizerConfig},
+    layout::{format_score, markdown_quote},
+    output::{Render, Style},
+    Poem,
+};
+use anyhow::{anyhow, Result};
+use colored::*;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use tantivy::{
+    schema::{Field, Schema, SchemaBuilder, FAST, INDEXED, STORED},
+    Document, Index,
+};
+
+// 每一句诗是一个文档 和诗的索引放在一起 按诗的id增删
+
+static SCHEMA: Lazy<(Schema, HashMap<&'static str, Field>)> = Lazy::new(build_schema);
+
+/// the schema of the line index
+pub(crate) fn schema() -> &'static Schema {
+    &SCHEMA.0
+}
+
+/// every field of the line schema by name
+pub(crate) fn fields() -> &'static HashMap<&'static str, Field> {
+    &SCHEMA.1
+}
+
+fn build_schema() -> (Schema, HashMap<&'static str, Field>) {
+    let mut schema_builder = SchemaBuilder::default();
+
+    let poem_id = schema_builder.add_u64_field("poem_id", INDEXED | STORED | FAST);
+    let line = schema_builder.add_text_field("line", text_options());
+    let position = schema_builder.add_u64_field("position", STORED);
+    let title = schema_builder.add_text_field("title", STORED);
+    let author = schema_builder.add_text_field("author", STORED);
+    let dynasty = schema_builder.add_text_field("dynasty", STORED);
+
+    let schema = schema_builder.build();
+
+    let mut fileds = HashMap::with_capacity(6);
+    fileds.insert("poem_id", poem_id);
+    fileds.insert("line", line);
+    fileds.insert("position", position);
+    fileds.insert("title", title);
+    fileds.insert("author", author);
+    fileds.insert("dynasty", dynasty);
+
+    (schema, fileds)
+}
+
+/// where the line index of the poem index in `path` is stored
+pub(crate) fn index_path(path: &Path) -> PathBuf {
+    path.join("lines")
+}
+
+pub(crate) fn open_or_create_index(
+    path: impl AsRef<Path>,
+    access: Access,
+    tokenizer: &TokenizerConfig,
+) -> Result<Index> {
+    open_or_create(path, schema(), access, tokenizer)
+}
+
+/// one document for every line of `poem`
+pub(crate) fn documents(poem: &Poem) -> Vec<Document> {
+    let fields = fields();
+    poem.lines()
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut doc = Document::new();
+            doc.add_u64(fields["poem_id"], poem.raw_id());
+            doc.add_text(fields["line"], line);
+            doc.add_u64(fields["position"], i as u64);
+            doc.add_text(fields["title"], &poem.title);
+            doc.add_text(fields["author"], &poem.author);
+            doc.add_text(fields["dynasty"], &poem.dynasty);
+            doc
+        })
+        .collect()
+}
+
+/// a single line of a poem
+#[derive(Debug, Clone, Serialize)]
+pub struct Line {
+    /// the id of the poem the line is from
+    pub poem_id: String,
+    /// the line without punctuation
+    pub text: String,
+    /// the index of the line in the poem
+    pub position: u64,
+    pub title: String,
+    pub author: String,
+    pub dynasty: String,
+}
+
+impl TryFrom<Document> for Line {
+    type Error = anyhow::Error;
+
+    fn try_from(doc: Document) -> Result<Self, Self::Error> {
+        let fields = fields();
+        let u64_of = |name: &str| {
+            doc.get_first(fields[name])
+                .and_then(|v| v.u64_value())
+                .ok_or_else(|| anyhow!("document has no field `{}`", name))
+        };
+        Ok(Self {
+            poem_id: format!("{:016x}", u64_of("poem_id")?),
+            text: extract_field_text(&doc, fields["line"])?,
+            position: u64_of("position")?,
+            title: extract_field_text(&doc, fields["title"])?,
+            author: extract_field_text(&doc, fields["author"])?,
+            dynasty: extract_field_text(&doc, fields["dynasty"])?,
+        })
+    }
+}
+
+/// a line found by `search_lines` with its relevance score
+#[derive(Debug, Clone, Serialize)]
+pub struct LineHit {
+    pub score: f32,
+    #[serde(flatten)]
+    pub line: Line,
+    /// byte ranges of the matched terms in `line.text`
+    #[serde(skip)]
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl Render for LineHit {
+    fn render(&self, f: &mut fmt::Formatter<'_>, style: &Style) -> fmt::Result {
+        let text = &self.line.text;
+        if style.markdown {
+            markdown_quote(text, &self.highlights, f)?;
+            return write!(
+                f,
+                " ——{}〔{}〕《{}》",
+                self.line.author, self.line.dynasty, self.line.title
+            );
+        }
+        let mut start = 0;
+        for range in &self.highlights {
+            write!(f, "{}", text[start..range.start].cyan())?;
+            write!(f, "{}", text[range.clone()].red().bold())?;
+            start = range.end;
+        }
+        write!(f, "{}", text[start..].cyan())?;
+        write!(
+            f,
+            "\t——{}〔{}〕《{}》 {}",
+            self.line.author,
+            self.line.dynasty,
+            self.line.title.bright_cyan(),
+            self.line.poem_id.dimmed()
+        )?;
+        if style.scores {
+            write!(f, " {}", format_score(self.score))?;
+        }
+        Ok(())
+    }
+}
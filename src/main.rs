@@ -1,37 +1,110 @@
 // lazy_static 实际上是定义了一个新类型 这个类型里面保存指定的类型
 // 然后实现Deref<指定类型> 并且在第一次deref的时候实例化指定类型 并保存在static的新类型值中
-use anyhow::Result;
-use cang_jie::{CangJieTokenizer, TokenizerOption, CANG_JIE};
-use clap::{AppSettings, Parser};
-use colored::*;
-use indicatif::ProgressBar;
-use jieba_rs::Jieba;
-use lazy_static::{__Deref, lazy_static};
-use rand::Rng;
+use anyhow::{anyhow, Context, Result};
+use chrono::{Local, NaiveDate};
+use clap::{App, AppSettings, Arg, FromArgMatches, IntoApp, Parser};
+use clap_complete::Shell;
+use indicatif::{ProgressBar, ProgressStyle};
+use poem::{
+    add_to_index, backup,
+    bench::{self, BenchOptions},
+    build_index, bundled_poems,
+    card::{self, CardStyle},
+    clipboard,
+    collection::Collection,
+    config::Config,
+    couplet, csv, dedup, epub,
+    export::{self, CardKind, PoemField},
+    federated_search, feihua, fetch,
+    format::{self, Format},
+    frequencies, grep, import, index_poems, index_status, load_poem, load_poems,
+    load_poems_matching,
+    memorize::Memorize,
+    optimize,
+    output::{print_all, print_line, Output, Style, Styled},
+    pick, prebuilt, quiz, remove_from_index, repl, rhyme,
+    semantic::{Embedder, VectorIndex},
+    server, set_lock_timeout, set_storage, tag_counts, timeline, timings, tokenizer_config, tones,
+    validate, verify_index, watch, words_count, Authors, Count, Favorites, FreqUnit, History, Hit,
+    Indexed, ListOrder, Notes, Poem, PoemError, PoemFilter, PoemRepository, PoemStore,
+    SearchOptions, SortBy, Stat, Storage, Tags, Tokenizer, TokenizerConfig, UserPoems,
+};
+use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
-    fmt::Display,
+    collections::HashSet,
+    env,
     fs::{self, File},
+    io::{self, BufRead, BufWriter, IsTerminal, Write},
+    mem,
     path::{Path, PathBuf},
-    sync::{atomic::AtomicUsize, Arc},
-};
-use tantivy::{
-    collector::TopDocs,
-    doc,
-    query::QueryParser,
-    schema::{Field, IndexRecordOption, Schema, SchemaBuilder, TextFieldIndexing, TextOptions},
-    Document, Index,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
 };
 
-const POEMS_STR: &str = include_str!("../poems.json");
-
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
 #[clap(global_setting(AppSettings::AllowNegativeNumbers))]
 struct Args {
     #[clap(subcommand)]
     action: Action,
+    /// the output format of search, list and random
+    #[clap(long, global = true, arg_enum, default_value = "text")]
+    output: Output,
+    /// read the defaults of the options from this file instead of ~/.config/poem/config.toml
+    #[clap(long, global = true, parse(from_os_str))]
+    #[allow(dead_code)] // 解析参数之前就要用到 见config_path
+    config: Option<PathBuf>,
+    /// use the index and the poems of this collection, see `collection`
+    #[clap(long, global = true)]
+    #[allow(dead_code)] // 和config一样 见arg_value
+    collection: Option<String>,
+    /// read the authors from this json file instead of the bundled `authors.json`
+    #[clap(long, global = true, parse(from_os_str))]
+    authors_file: Option<PathBuf>,
+    /// log how long opening the index, parsing the query, searching and fetching the documents
+    /// take to stderr, -vv logs more about them and -vvv the logs of the libraries too
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    verbose: u64,
+    /// print how long each stage took to stderr when done
+    #[clap(long, global = true)]
+    timings: bool,
+    /// how many seconds to wait for another process writing the index to finish
+    #[clap(long, global = true, default_value = "10")]
+    lock_timeout: u64,
+    /// keep your poems and the ones of `fetch` as json files in the data dir, or with
+    /// `sqlite:<path>` in a sqlite database, which needs the `sqlite` feature and sqlite3
+    #[clap(long, global = true, default_value = "json")]
+    storage: Storage,
+    #[clap(flatten)]
+    style: Style,
+}
+
+/// whether to print the timings when done, see --timings
+static PRINT_TIMINGS: AtomicBool = AtomicBool::new(false);
+
+/// prints the logs to stderr, only the ones of this crate unless `all` is set
+struct Logger {
+    all: bool,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.all || metadata.target().starts_with("poem")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "[{}] {}",
+                record.level().as_str().to_lowercase(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -41,15 +114,302 @@ enum Action {
         /// the path index will be stored
         #[clap(long, parse(from_os_str), default_value = ".poem_index")]
         index_path: PathBuf,
+        /// wipe the index and rebuild it from scratch
+        #[clap(long)]
+        force: bool,
+        /// how text is split into terms, the index is rebuilt when it changes [default: unicode]
+        #[clap(long, arg_enum)]
+        tokenizer: Option<Tokenizer>,
+        /// a jieba dictionary with extra words, one `word [freq] [tag]` per line
+        #[clap(long, parse(from_os_str))]
+        user_dict: Option<PathBuf>,
+        /// the number of threads to index with [default: the number of cores]
+        #[clap(long)]
+        threads: Option<usize>,
+        /// read poems from this file instead of the bundled ones, a `.jsonl` file is indexed
+        /// as it is read so it can be as big as you like
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+        /// keep running and index the poems that changed every time the poems file is saved
+        #[clap(long, requires = "poems-file")]
+        watch: bool,
+    },
+
+    /// print the terms a text is split into by the tokenizer of the index
+    Analyze {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// use this tokenizer instead of the one of the index
+        #[clap(long, arg_enum)]
+        tokenizer: Option<Tokenizer>,
+        /// a jieba dictionary with extra words, one `word [freq] [tag]` per line
+        #[clap(long, parse(from_os_str))]
+        user_dict: Option<PathBuf>,
+        text: String,
+    },
+
+    /// add a poem of your own, read as json from stdin when --title is left out
+    Add {
+        /// the path index is stored, the poem is also indexed if there is one
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the title of the poem, --author, --dynasty and --content are needed with it
+        #[clap(long, requires_all = &["author", "dynasty", "content"])]
+        title: Option<String>,
+        #[clap(long, requires = "title")]
+        author: Option<String>,
+        #[clap(long, requires = "title")]
+        dynasty: Option<String>,
+        #[clap(long, requires = "title")]
+        content: Option<String>,
+        /// the meaning in modern chinese or english
+        #[clap(long, requires = "title")]
+        translation: Option<String>,
+    },
+
+    /// import the poems of another dataset as if they were added with `add`
+    Import {
+        #[clap(subcommand)]
+        source: ImportSource,
+    },
+
+    /// remove a poem added with `add`
+    Remove {
+        /// the path index is stored, the poem is also removed from it if there is one
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the id of the poem
+        id: String,
+    },
+
+    /// change a poem added with `add`, the poem gets a new id if it is changed
+    Edit {
+        /// the path index is stored, the poem is also updated in it if there is one
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the id of the poem
+        id: String,
+        #[clap(long)]
+        title: Option<String>,
+        #[clap(long)]
+        author: Option<String>,
+        #[clap(long)]
+        dynasty: Option<String>,
+        #[clap(long)]
+        content: Option<String>,
+        #[clap(long)]
+        translation: Option<String>,
+    },
+
+    /// show what the index looks like and whether it is up to date
+    Status {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// compare the index with this json file instead of the bundled poems
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// check that the files of the index can be read and that it has the poems it should,
+    /// exits with 1 if anything is wrong
+    Verify {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// compare the index with this file instead of the bundled poems
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+        /// fix what is wrong, a damaged index is rebuilt from the poems with the tokenizer it had
+        #[clap(long)]
+        repair: bool,
+    },
+
+    /// merge the segments of the index into one and drop the documents deleted from it,
+    /// searches get slower as adding and removing poems leaves more of them
+    Optimize {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+    },
+
+    /// time building the index, searching it and picking random poems, to compare releases
+    /// and tokenizers, the index is built in a temporary directory
+    Bench {
+        /// how many random words of the poems to search for
+        #[clap(long, default_value = "1000")]
+        queries: usize,
+        /// how many random poems to pick
+        #[clap(long, default_value = "1000")]
+        samples: usize,
+        /// how text is split into terms [default: unicode]
+        #[clap(long, arg_enum)]
+        tokenizer: Option<Tokenizer>,
+        /// a jieba dictionary with extra words, one `word [freq] [tag]` per line
+        #[clap(long, parse(from_os_str))]
+        user_dict: Option<PathBuf>,
+        /// the number of threads to index with [default: the number of cores]
+        #[clap(long)]
+        threads: Option<usize>,
+        /// the same seed searches for the same words and picks the same poems
+        #[clap(long, default_value = "0")]
+        seed: u64,
+        /// run with the poems of this file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// check the poems for empty fields, duplicates, stray whitespace and characters that are
+    /// not chinese, exits with 1 if there are any problems
+    Validate {
+        /// check this file instead of the bundled poems and the ones added with `add`
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// find the poems that are there more than once, differing only in punctuation, variant
+    /// characters or a few words, and keep one of each with --apply or --interactive
+    Dedup {
+        /// how many of the pairs of characters of two poems with the same title and author have
+        /// to be the same
+        #[clap(long, default_value = "0.8")]
+        threshold: f32,
+        /// keep the first poem of each group
+        #[clap(long, conflicts_with = "interactive")]
+        apply: bool,
+        /// ask which poem of each group to keep
+        #[clap(short, long)]
+        interactive: bool,
+        /// write the poems kept to this file, json, yaml or toml by the extension, instead of
+        /// changing --poems-file or the poems added with `add`
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the path index is stored, the poems removed from the ones added with `add` are also
+        /// removed from it
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// check this file instead of the bundled poems and the ones added with `add`
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// merge several files of poems into one, keeping one poem of each group of duplicates found
+    /// the way `dedup` finds them
+    Merge {
+        /// the files to merge, json, yaml or toml by the extension
+        #[clap(parse(from_os_str), required = true, min_values = 2)]
+        files: Vec<PathBuf>,
+        /// the file to write, json, yaml or toml by the extension
+        #[clap(long, parse(from_os_str))]
+        out: PathBuf,
+        /// keep the poem of the file given first, the default
+        #[clap(long, conflicts_with = "prefer-longer-content")]
+        prefer_first: bool,
+        /// keep the poem with the longest content
+        #[clap(long)]
+        prefer_longer_content: bool,
+        /// how many of the pairs of characters of two poems with the same title and author have
+        /// to be the same
+        #[clap(long, default_value = "0.8")]
+        threshold: f32,
     },
 
     /// search poems
     Search {
+        /// the path index is stored, given several times the results of all indexes are merged
+        #[clap(
+            long,
+            parse(from_os_str),
+            multiple_occurrences = true,
+            default_value = ".poem_index"
+        )]
+        index_path: Vec<PathBuf>,
+        /// search every collection and merge the results
+        #[clap(long, conflicts_with = "index-path")]
+        all_collections: bool,
+        /// build an index in memory from the poems if there is none at the index path
+        #[clap(long)]
+        in_memory: bool,
+        /// fail if there is no index at the index path instead of building it first
+        #[clap(long, conflicts_with = "in-memory")]
+        no_auto_index: bool,
+        /// read poems from this json file instead of the bundled ones when there is no index yet
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+        #[clap(flatten)]
+        opts: SearchOptions,
+        /// also print how many results each dynasty has
+        #[clap(long)]
+        facets: bool,
+        /// only print a short excerpt around the matched terms instead of the whole poem
+        #[clap(long)]
+        snippet: bool,
+        /// the order of the results
+        #[clap(long, arg_enum, default_value = "relevance")]
+        sort: SortBy,
+        /// also put the first result on the clipboard as plain text
+        #[clap(long)]
+        copy: bool,
+        /// print nothing, only exit with 1 if nothing is found
+        #[clap(short, long)]
+        quiet: bool,
+        /// only print how many poems are found
+        #[clap(long, conflicts_with_all = &["facets", "snippet", "copy"])]
+        count: bool,
+        /// print the parsed query and how the score of every poem is made up
+        #[clap(long, conflicts_with_all = &["facets", "snippet", "copy", "quiet", "count"])]
+        explain: bool,
+        /// search every line of stdin as a keyword with the index opened once, the results of
+        /// each printed as soon as it is searched
+        #[clap(long, conflicts_with_all = &["keyword", "facets", "copy", "explain"])]
+        stdin: bool,
+        /// the keyword, may be left out when searching with --all-of, --any-of or --none-of
+        keyword: Option<String>,
+    },
+
+    /// search every line typed at a prompt with the index opened once, `:help` lists the
+    /// commands to change the options and pick a result
+    Repl {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// read poems from this json file instead of the bundled ones when there is no index yet
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+        #[clap(flatten)]
+        opts: SearchOptions,
+        /// the number of results listed, changed with `:limit`
+        #[clap(long, default_value = "10")]
+        limit: usize,
+    },
+
+    /// search single lines of poems
+    Lines {
         /// the path index is stored
         #[clap(long, parse(from_os_str), default_value = ".poem_index")]
         index_path: PathBuf,
-        /// the keyword
-        keyword: String,
+        #[clap(flatten)]
+        opts: SearchOptions,
+        /// the keyword, may be left out when searching with --all-of, --any-of or --none-of
+        keyword: Option<String>,
+    },
+
+    /// scan the poems for a text or a regex without the index
+    Grep {
+        /// the text to look for in the titles and contents
+        pattern: String,
+        /// take the pattern as a regex
+        #[clap(short = 'E', long)]
+        regex: bool,
+        /// print the matching lines instead of the poems
+        #[clap(short = 'o', long)]
+        only_lines: bool,
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
     },
 
     /// list poems
@@ -57,244 +417,2577 @@ enum Action {
         /// the max count of poem list
         #[clap(long)]
         limit: Option<usize>,
+        /// skip this many poems first
+        #[clap(long, default_value = "0")]
+        offset: usize,
+        /// the order of the poems
+        #[clap(long, arg_enum, default_value = "dataset")]
+        sort: ListOrder,
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
     },
     /// get random poems
     Random {
         /// the count you need
         #[clap(long, default_value = "1")]
         count: usize,
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// only pick from the favorite poems
+        #[clap(long)]
+        from_favorites: bool,
+        /// always pick the same poems for the same seed
+        #[clap(long)]
+        seed: Option<u64>,
+        /// skip the poems shown before until all of them have been shown
+        #[clap(long)]
+        no_repeat: bool,
+        /// forget which poems were shown before
+        #[clap(long)]
+        reset_history: bool,
+        /// also put the poems on the clipboard as plain text
+        #[clap(long)]
+        copy: bool,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
     },
 
-    /// get stat of all poems
-    Stat {
-        /// sort by count desc
+    /// get the poem of the day
+    Daily {
+        /// the date to get the poem of, defaults to today
         #[clap(long)]
-        sort: bool,
+        date: Option<NaiveDate>,
+        /// pick a random poem instead
+        #[clap(long)]
+        seedless: bool,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
     },
-}
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+    /// pick a poem from a list of its titles, authors and dynasties with fzf or sk, or the
+    /// built in finder if neither is installed, and print it in full
+    Pick {
+        /// start with this query
+        query: Option<String>,
+        /// use the built in finder even if fzf or sk is installed
+        #[clap(long)]
+        builtin: bool,
+        /// also put the poem on the clipboard as plain text
+        #[clap(long)]
+        copy: bool,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-    match args.action {
-        Action::Index { index_path } => {
-            let index = open_or_create_index(index_path, false)?;
-            let mut writer = index.writer(1024 * 1024 * 10)?;
-            let poems: Vec<Poem> = serde_json::from_str(POEMS_STR)?;
-            let bar = ProgressBar::new(poems.len() as _);
-            poems.into_iter().map(Document::from).for_each(|doc| {
-                writer.add_document(doc);
-                bar.inc(1);
-            });
-            writer.commit()?;
-            bar.finish();
-        }
-        Action::Search {
-            index_path,
-            keyword,
-        } => {
-            let index = open_or_create_index(index_path, true)?;
-            let reader = index.reader()?;
-            let searcher = reader.searcher();
-            let (_, fields) = build_schema();
-
-            let query = QueryParser::for_index(&index, fields.into_values().into_iter().collect())
-                .parse_query(&keyword)?;
-            let top_docs = searcher.search(query.as_ref(), &TopDocs::with_limit(10000))?;
-            for (_, doc_address) in top_docs.into_iter() {
-                let poem: Poem = searcher.doc(doc_address)?.into();
-                println!("{}", poem);
-            }
-        }
-        Action::List { limit } => {
-            let poems: Vec<Poem> = serde_json::from_str(POEMS_STR)?;
-            let poems = match limit {
-                Some(l) => {
-                    if l > poems.len() {
-                        &poems[..]
-                    } else {
-                        &poems[..l]
-                    }
-                }
-                None => &poems[..],
-            };
-            poems.iter().for_each(|p| println!("{}", p));
-        }
-        Action::Random { mut count } => {
-            let poems: Vec<Poem> = serde_json::from_str(POEMS_STR)?;
-            if poems.is_empty() {
-                println!("no poem in repo");
-                return Ok(());
-            }
-            if count > poems.len() {
-                count = poems.len();
-            }
-            let mut rng = rand::thread_rng();
-            let mut set = HashSet::new();
-            while set.len() < count {
-                set.insert(&poems[rng.gen_range(0..poems.len())]);
-            }
-            set.into_iter().for_each(|p| println!("{}", p));
-        }
-        Action::Stat { sort } => {
-            let poems: Vec<Poem> = serde_json::from_str(POEMS_STR)?;
-            let dynasty: Vec<&str> = poems.iter().map(|p| &p.dynasty[..]).collect();
-            let author: Vec<&str> = poems.iter().map(|p| &p.author[..]).collect();
+    /// show one poem by its id or exact title
+    Show {
+        /// the id or the title of the poem
+        name: String,
+        /// the author of the poem when several have the same title
+        #[clap(long)]
+        author: Option<String>,
+        /// also put the poem on the clipboard as plain text
+        #[clap(long)]
+        copy: bool,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-            let stat = Stat::new(
-                poems.len() as _,
-                words_count(&author, sort),
-                words_count(&dynasty, sort),
-            );
-            println!("{}", stat);
-        }
-    }
+    /// show which rhyme group every line of a poem ends with
+    Rhyme {
+        /// the id of the poem
+        id: String,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-    Ok(())
-}
+    /// mark every character of a poem 平 or 仄, and check them against the templates of
+    /// 绝句 and 律诗 with --check
+    Tones {
+        /// the id of the poem
+        #[clap(required_unless_present = "text")]
+        id: Option<String>,
+        /// mark this text instead, lines split by punctuation
+        #[clap(long, conflicts_with = "id")]
+        text: Option<String>,
+        /// also check the tones against the closest template, the characters breaking it in red
+        #[clap(long)]
+        check: bool,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-fn open_or_create_index(path: impl AsRef<Path>, read_only: bool) -> Result<Index> {
-    let (schema, _) = build_schema();
+    /// draw a poem on an svg image to share, in columns with --vertical
+    Card {
+        /// the id of the poem
+        id: String,
+        /// the file to write, stdout if left out
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        #[clap(flatten)]
+        card: CardStyle,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-    let path = path.as_ref();
+    /// recite a poem with some lines blanked out
+    Quiz {
+        #[clap(subcommand)]
+        mode: Option<QuizMode>,
+        /// how many blanks
+        #[clap(long, default_value = "1")]
+        blanks: usize,
+        /// blank out single characters instead of whole lines
+        #[clap(long)]
+        chars: bool,
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-    let index = if read_only {
-        Index::open_in_dir(path)?
-    } else {
-        if path.exists() {
-            fs::remove_dir_all(path)?;
-        }
-        fs::create_dir_all(path)?;
-        Index::create_in_dir(path, schema)?
-    };
-    index.tokenizers().register(CANG_JIE, tokenizer());
+    /// play 飞花令: take turns with the program saying lines containing a character
+    Feihua {
+        /// the path index is stored, your lines are checked against it
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the character every line has to contain
+        key: char,
+    },
 
-    Ok(index)
-}
+    /// find lines of the same length with opposite tones to answer a line with, or practice
+    /// 对对子 without one
+    Couplet {
+        /// the path index is stored, the lines are taken from it
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the line to answer, practice with lines from the index if not given
+        line: Option<String>,
+        /// the number of candidates to print
+        #[clap(short, long, default_value = "10")]
+        limit: usize,
+    },
+
+    /// the poems most like a poem, by the vectors of `semantic index` if there are any
+    Similar {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the id of the poem
+        id: String,
+        /// the number of poems to print
+        #[clap(short, long, default_value = "10")]
+        limit: usize,
+        /// compare the terms of the poems even if there are vectors
+        #[clap(long)]
+        terms: bool,
+        /// the poems the index was built from, if not the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// manage named collections of poems, each with its own index, used with --collection
+    Collection {
+        #[clap(subcommand)]
+        action: CollectionAction,
+    },
+
+    /// search poems by meaning with vectors stored next to the index
+    Semantic {
+        #[clap(subcommand)]
+        action: SemanticAction,
+    },
+
+    /// print the completion script of a shell, the authors and dynasties of the poems included
+    Completions {
+        #[clap(arg_enum)]
+        shell: Shell,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// export poems for other programs
+    Export {
+        #[clap(subcommand)]
+        target: ExportTarget,
+    },
+
+    /// memorize poems, reviewing each when it is about to be forgotten
+    Memorize {
+        #[clap(subcommand)]
+        action: MemorizeAction,
+    },
+
+    /// list all authors with their poem counts
+    Authors {
+        #[clap(subcommand)]
+        action: Option<AuthorsAction>,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-fn build_schema() -> (Schema, HashMap<&'static str, Field>) {
-    let mut schema_builder = SchemaBuilder::default();
+    /// save the poems you added, the favorites, tags, notes, memorizing progress, history, config
+    /// and collections to one archive
+    Backup {
+        /// the gzipped tar archive to write
+        #[clap(long, parse(from_os_str), default_value = "poem-backup.tar.gz")]
+        out: PathBuf,
+    },
+
+    /// put the data in an archive of `backup` back, replacing the current files of the same name
+    Restore {
+        #[clap(parse(from_os_str))]
+        archive: PathBuf,
+    },
+
+    /// index the bundled poems and write the index to an archive, to be built into poem with
+    /// the `prebuilt-index` feature or downloaded from `POEM_PREBUILT_URL` on the first search
+    Pack {
+        /// the gzipped tar archive to write
+        #[clap(long, parse(from_os_str), default_value = "poem-index.tar.gz")]
+        out: PathBuf,
+        /// how text is split into terms [default: unicode]
+        #[clap(long, arg_enum)]
+        tokenizer: Option<Tokenizer>,
+        /// a jieba dictionary with extra words, one `word [freq] [tag]` per line
+        #[clap(long, parse(from_os_str))]
+        user_dict: Option<PathBuf>,
+        /// the number of threads to index with [default: the number of cores]
+        #[clap(long)]
+        threads: Option<usize>,
+    },
+
+    /// put the index in an archive of `pack` in place of the index, if it was built from the
+    /// bundled poems here, and index your own poems and tags in it
+    Unpack {
+        /// the path index will be stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        #[clap(parse(from_os_str))]
+        archive: PathBuf,
+    },
+
+    /// download a file of poems, check it and use it instead of the bundled poems
+    Fetch {
+        /// an https url of a json, yaml, toml or csv file in the format of `export`
+        #[clap(required_unless_present = "reset")]
+        url: Option<String>,
+        /// create a collection of this name with the poems instead, see `collection`
+        #[clap(long)]
+        as_collection: Option<String>,
+        /// use the file even if it has empty fields, control characters or bytes that are not
+        /// utf-8
+        #[clap(long)]
+        force: bool,
+        /// go back to the bundled poems
+        #[clap(long, conflicts_with_all = &["url", "as-collection"])]
+        reset: bool,
+        /// the columns of a csv file
+        #[clap(flatten)]
+        columns: csv::Columns,
+    },
+
+    /// lay out the authors of every dynasty by when they lived, with their poems
+    Timeline {
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+
+    /// manage favorite poems
+    Fav {
+        #[clap(subcommand)]
+        action: FavAction,
+    },
 
-    let text_indexing = TextFieldIndexing::default()
-        .set_tokenizer(CANG_JIE) // Set custom tokenizer
-        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
-    let text_options = TextOptions::default()
-        .set_indexing_options(text_indexing)
-        .set_stored();
+    /// tag poems, like 送别 or 边塞, to find them with --tag
+    Tag {
+        #[clap(subcommand)]
+        action: TagAction,
+    },
 
-    let title = schema_builder.add_text_field("title", text_options.clone());
-    let author = schema_builder.add_text_field("author", text_options.clone());
-    let dynasty = schema_builder.add_text_field("dynasty", text_options.clone());
-    let content = schema_builder.add_text_field("content", text_options);
+    /// write study notes on poems, printed below them with --with-notes
+    Note {
+        #[clap(subcommand)]
+        action: NoteAction,
+    },
 
-    let schema = schema_builder.build();
+    /// serve poems over http
+    Serve {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// the address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-    let mut fileds = HashMap::with_capacity(4);
-    fileds.insert("title", title);
-    fileds.insert("author", author);
-    fileds.insert("dynasty", dynasty);
-    fileds.insert("content", content);
+    /// the most frequent characters or words of the poems
+    Wordfreq {
+        /// what to count
+        #[clap(long, arg_enum, default_value = "char")]
+        by: FreqUnit,
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// how many to show
+        #[clap(long, default_value = "20")]
+        top: usize,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 
-    (schema, fileds)
+    /// get stat of all poems
+    #[clap(alias = "stats")]
+    Stat {
+        /// sort by count desc
+        #[clap(long)]
+        sort: bool,
+        /// only show the authors with the most poems
+        #[clap(long)]
+        top: Option<usize>,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 }
 
-fn tokenizer() -> CangJieTokenizer {
-    CangJieTokenizer {
-        worker: Arc::new(Jieba::empty()), // empty dictionary
-        option: TokenizerOption::Unicode,
-    }
+#[derive(clap::Subcommand, Debug)]
+enum AuthorsAction {
+    /// show all poems by an author sorted by title
+    Show {
+        /// the name of the author
+        name: String,
+    },
+    /// show the years, the courtesy name and the biography of an author
+    Info {
+        /// the name of the author
+        name: String,
+    },
 }
 
-#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
-struct Poem {
-    title: String,
-    author: String,
-    dynasty: String,
-    content: String,
+#[derive(clap::Subcommand, Debug)]
+enum ImportSource {
+    /// a file of poems in the format of `export poems`
+    File {
+        #[clap(parse(from_os_str))]
+        file: PathBuf,
+        /// the format of the file [default: by the extension, json if it is not yaml or toml]
+        #[clap(long, arg_enum)]
+        format: Option<Format>,
+        /// write the poems to this file for --poems-file instead of importing them, json, yaml or
+        /// toml by the extension
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the path index is stored, the poems are also indexed if there is one
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+    },
+    /// a checkout of https://github.com/chinese-poetry/chinese-poetry, every poem tagged with
+    /// the directory it came from
+    ChinesePoetry {
+        /// the directory of the checkout
+        #[clap(parse(from_os_str))]
+        dir: PathBuf,
+        /// write the poems to this file for --poems-file instead of importing them, json, yaml or
+        /// toml by the extension
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the path index is stored, the poems are also indexed if there is one
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+    },
+    /// a csv file like the ones spreadsheets save, one poem per row
+    Csv {
+        #[clap(parse(from_os_str))]
+        file: PathBuf,
+        #[clap(flatten)]
+        columns: csv::Columns,
+        /// the character between the cells
+        #[clap(long, default_value = ",")]
+        delimiter: char,
+        /// the first row is a poem too, the columns have to be numbers then
+        #[clap(long)]
+        no_header: bool,
+        /// write the poems to this file for --poems-file instead of importing them, json, yaml or
+        /// toml by the extension
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the path index is stored, the poems are also indexed if there is one
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+    },
 }
 
-impl Display for Poem {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "\t{}", self.title.bright_cyan()).unwrap();
-        writeln!(f, "\t{}〔{}〕", self.author.cyan(), self.dynasty.cyan()).unwrap();
-        writeln!(f, "{}", self.content.cyan())
-    }
+#[derive(clap::Subcommand, Debug)]
+enum CollectionAction {
+    /// list the collections
+    List,
+    /// create a collection, run `poem --collection <name> index` afterwards
+    Create {
+        name: String,
+        /// the poems of the collection, a copy of the bundled ones if left out
+        #[clap(long, parse(from_os_str))]
+        from: Option<PathBuf>,
+    },
+    /// delete a collection with its poems and index
+    Delete { name: String },
 }
 
-impl From<Poem> for Document {
-    fn from(p: Poem) -> Self {
-        let (_, fields) = build_schema();
-        let mut doc = Document::new();
-        doc.add_text(*fields.get("title").unwrap(), p.title);
-        doc.add_text(*fields.get("author").unwrap(), p.author);
-        doc.add_text(*fields.get("dynasty").unwrap(), p.dynasty);
-        doc.add_text(*fields.get("content").unwrap(), p.content);
-        doc
-    }
+#[derive(clap::Subcommand, Debug)]
+enum SemanticAction {
+    /// embed the poems in the index, only the new ones unless the embedder changed
+    Index {
+        /// the path index is stored, the vectors are stored in it
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// a shell command reading a json array of texts on stdin and printing a json array
+        /// of vectors, the built in embedding only compares characters if not given
+        #[clap(long)]
+        embed_command: Option<String>,
+        /// embed all poems again
+        #[clap(long)]
+        force: bool,
+        /// the poems the index was built from, if not the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// the poems closest in meaning to a query, embedded the way the poems were
+    Search {
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        query: String,
+        /// the number of poems to print
+        #[clap(short, long, default_value = "10")]
+        limit: usize,
+        /// the poems the index was built from, if not the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 }
 
-impl From<Document> for Poem {
-    fn from(doc: Document) -> Self {
-        let (_, fields) = build_schema();
-        Self {
-            title: extract_field_text(&doc, *fields.get("title").unwrap()),
-            author: extract_field_text(&doc, *fields.get("author").unwrap()),
-            dynasty: extract_field_text(&doc, *fields.get("dynasty").unwrap()),
-            content: extract_field_text(&doc, *fields.get("content").unwrap()),
-        }
-    }
+#[derive(clap::Subcommand, Debug)]
+enum ExportTarget {
+    /// a tab separated deck for `File > Import` in anki
+    Anki {
+        /// the file to write, stdout if left out
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the kinds of cards
+        #[clap(long, arg_enum, use_delimiter = true, default_value = "poem")]
+        cards: Vec<CardKind>,
+        /// the fields on the front of the poem cards
+        #[clap(long, arg_enum, use_delimiter = true, default_value = "title,author")]
+        front: Vec<PoemField>,
+        /// the fields on the back of the poem cards
+        #[clap(long, arg_enum, use_delimiter = true, default_value = "content")]
+        back: Vec<PoemField>,
+        #[clap(flatten)]
+        selection: Selection,
+    },
+    /// a standalone html page with links to every dynasty and author
+    Html {
+        /// the file to write, stdout if left out
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the title of the page
+        #[clap(long, default_value = "诗选")]
+        title: String,
+        #[clap(flatten)]
+        selection: Selection,
+    },
+    /// a latex document with one poem per page for printing, pinyin above the characters
+    /// with --pinyin, compile it with xelatex
+    Latex {
+        /// the file to write, stdout if left out
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        #[clap(flatten)]
+        selection: Selection,
+    },
+    /// the poems themselves, to edit them by hand or read them back with --poems-file
+    Poems {
+        /// the file to write, stdout if left out
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// the format to write [default: by the extension of --out, json if it is not yaml or toml]
+        #[clap(long, arg_enum)]
+        format: Option<Format>,
+        #[clap(flatten)]
+        selection: Selection,
+    },
+    /// a csv file for spreadsheets, with a header row of the names of the columns
+    Csv {
+        /// the file to write, stdout if left out
+        #[clap(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        #[clap(flatten)]
+        columns: csv::Columns,
+        /// the character between the cells
+        #[clap(long, default_value = ",")]
+        delimiter: char,
+        /// start with a byte order mark so excel reads the file as utf-8
+        #[clap(long)]
+        bom: bool,
+        #[clap(flatten)]
+        selection: Selection,
+    },
+    /// an epub book for e-readers with a chapter for each author
+    Epub {
+        /// the file to write
+        #[clap(long, parse(from_os_str))]
+        out: PathBuf,
+        /// the title of the book
+        #[clap(long, default_value = "诗选")]
+        title: String,
+        #[clap(flatten)]
+        selection: Selection,
+    },
 }
 
-#[derive(Debug)]
-struct Stat<'a> {
-    total: i32,
-    author: Vec<(&'a str, i32)>,
-    dynasty: Vec<(&'a str, i32)>,
+/// the poems to export, all of them if neither --query nor --favorites is given
+#[derive(clap::Args, Debug)]
+struct Selection {
+    /// only the poems found by this search, most relevant first
+    #[clap(long)]
+    query: Option<String>,
+    /// the path index is stored, only used by --query
+    #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+    index_path: PathBuf,
+    /// only the favorite poems
+    #[clap(long)]
+    favorites: bool,
+    #[clap(flatten)]
+    filter: PoemFilter,
+    /// read poems from this json file instead of the bundled ones
+    #[clap(long, parse(from_os_str))]
+    poems_file: Option<PathBuf>,
 }
 
-impl<'a> Stat<'a> {
-    fn new(total: i32, author: Vec<(&'a str, i32)>, dynasty: Vec<(&'a str, i32)>) -> Self {
-        Self {
-            total,
-            author,
-            dynasty,
+impl Selection {
+    fn poems(self) -> Result<Vec<Poem>> {
+        let mut poems = match &self.query {
+            Some(query) => PoemRepository::open(&self.index_path)?
+                .with_poems(load_poems(self.poems_file.as_deref())?)
+                .search(query, &SearchOptions::default())?
+                .into_iter()
+                .map(|h| h.poem)
+                .collect(),
+            None => load_poems(self.poems_file.as_deref())?,
+        };
+        if self.favorites {
+            let favorites = Favorites::load()?;
+            poems.retain(|p| favorites.contains(&p.id()));
         }
+        poems.retain(|p| self.filter.matches(p));
+        Ok(poems)
     }
 }
 
-impl<'a> Display for Stat<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "总数：{}", self.total).unwrap();
-        writeln!(f, "朝代：").unwrap();
-        self.dynasty
-            .iter()
-            .for_each(|d| writeln!(f, "{:>7}：{}", d.0, d.1).unwrap());
-        writeln!(f, "作者：").unwrap();
-        self.author
-            .iter()
-            .for_each(|d| writeln!(f, "{:>7}： {}", d.0, d.1).unwrap());
-        Ok(())
-    }
+#[derive(clap::Subcommand, Debug)]
+enum MemorizeAction {
+    /// start memorizing a poem
+    Add {
+        /// the id of the poem
+        id: String,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// stop memorizing a poem
+    Remove {
+        /// the id of the poem
+        id: String,
+    },
+    /// list the poems to review today
+    Due {
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// review the poems due today by filling in their blanked out lines
+    Review {
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 }
 
-fn extract_field_text(doc: &Document, field: Field) -> String {
-    doc.get_all(field)
-        .next()
-        .unwrap()
-        .text()
-        .unwrap()
-        .to_string()
+#[derive(clap::Subcommand, Debug)]
+enum TagAction {
+    /// give a poem tags, it is indexed again if there is an index
+    Add {
+        /// the id of the poem
+        id: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// take tags given with `tag add` from a poem
+    Remove {
+        /// the id of the poem
+        id: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+        /// the path index is stored
+        #[clap(long, parse(from_os_str), default_value = ".poem_index")]
+        index_path: PathBuf,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// list the tags of a poem, or every tag with how many poems have it
+    List {
+        /// the id of the poem
+        id: Option<String>,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
 }
 
-fn words_count<'a>(words: &[&'a str], sort: bool) -> Vec<(&'a str, i32)> {
-    let mut map = HashMap::new();
-    for w in words {
-        let entry = map.entry(*w).or_insert(0);
-        *entry += 1;
-    }
-    let mut pairs = map.into_iter().collect::<Vec<_>>();
-    if sort {
-        pairs.sort_by_key(|p| -p.1);
-    }
-    pairs
+#[derive(clap::Subcommand, Debug)]
+enum NoteAction {
+    /// write a note on a poem
+    Add {
+        /// the id of the poem
+        id: String,
+        text: String,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// list the notes of a poem with their numbers
+    List {
+        /// the id of the poem
+        id: String,
+    },
+    /// remove a note of a poem
+    Remove {
+        /// the id of the poem
+        id: String,
+        /// the number of the note, see `note list`
+        number: usize,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum FavAction {
+    /// add a poem to favorites
+    Add {
+        /// the id of the poem
+        id: String,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// list favorite poems
+    List {
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+    /// remove a poem from favorites
+    Remove {
+        /// the id of the poem
+        id: String,
+    },
+}
+
+/// how long the excerpts of `search --snippet` are, about 40 chinese characters
+const SNIPPET_BYTES: usize = 120;
+
+/// one poem or a list of them, as read by `add`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(Poem),
+    Many(Vec<Poem>),
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum QuizMode {
+    /// say the line after a random line
+    NextLine {
+        /// stop after this many questions, otherwise stop at an empty answer
+        #[clap(long)]
+        rounds: Option<usize>,
+        #[clap(flatten)]
+        filter: PoemFilter,
+        /// read poems from this json file instead of the bundled ones
+        #[clap(long, parse(from_os_str))]
+        poems_file: Option<PathBuf>,
+    },
+}
+
+fn main() {
+    // 和grep一样 出错时是2 没搜到是1 能说清楚的错误各有各的
+    timings::start();
+    let result = run();
+    print_timings();
+    if let Err(e) = result {
+        eprintln!("Error: {:?}", e);
+        process::exit(PoemError::exit_code_of(&e));
+    }
+}
+
+fn run() -> Result<()> {
+    let config = Config::load(config_path().as_deref())?;
+    config.apply_color();
+    // 配置里的值作为参数的默认值 命令行上给了的还是以命令行为准
+    let mut defaults = config.defaults();
+    // 集合的索引和诗库比配置里的优先
+    if let Some(name) = arg_value("collection") {
+        let collection = Collection::open(&name)?;
+        defaults.push(("index-path", collection.index_path().display().to_string()));
+        defaults.push(("poems-file", collection.poems_path().display().to_string()));
+    }
+    let app = defaults
+        .iter()
+        .fold(Args::into_app(), |app, (name, value)| {
+            mut_args(app, name, &|a| a.default_value(value))
+        });
+    let args = Args::from_arg_matches(&app.get_matches()).unwrap_or_else(|e| e.exit());
+    let level = match args.verbose {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Info,
+        _ => log::LevelFilter::Debug,
+    };
+    let logger = Logger {
+        all: args.verbose > 2,
+    };
+    log::set_logger(Box::leak(Box::new(logger)))?;
+    log::set_max_level(level);
+    PRINT_TIMINGS.store(args.timings, Ordering::Relaxed);
+    set_lock_timeout(Duration::from_secs(args.lock_timeout));
+    set_storage(args.storage.clone());
+    // 建索引时也要查作者的生卒年
+    let authors = Authors::load(args.authors_file.as_deref())?;
+    let mut style = args.style;
+    if style.with_notes {
+        style.notes = Notes::load()?;
+    }
+    if style.with_author_bio {
+        style.authors = authors.clone();
+    }
+
+    match args.action {
+        Action::Index {
+            index_path,
+            force,
+            tokenizer,
+            user_dict,
+            threads,
+            poems_file,
+            watch,
+        } => {
+            let tokenizer = choose_tokenizer(&index_path, tokenizer, user_dict)?;
+            let indexed = index_file(
+                &index_path,
+                poems_file.as_deref(),
+                &authors,
+                force,
+                tokenizer.clone(),
+                threads,
+            )?;
+            println!("{}", indexed);
+            if let Some(path) = poems_file.filter(|_| watch) {
+                eprintln!("watching {} for changes, ctrl-c to stop", path.display());
+                // 改到一半的文件可能解析不了 等下次保存
+                watch::watch(&path, || {
+                    let indexed = PoemStore::global().reload(&path).and_then(|_| {
+                        index_file(
+                            &index_path,
+                            Some(&path),
+                            &authors,
+                            false,
+                            tokenizer.clone(),
+                            threads,
+                        )
+                    });
+                    match indexed {
+                        Ok(indexed) => println!("{}", indexed),
+                        Err(e) => eprintln!("warning: {:#}", e),
+                    }
+                })?;
+            }
+        }
+        Action::Analyze {
+            index_path,
+            tokenizer,
+            user_dict,
+            text,
+        } => {
+            let config = match choose_tokenizer(&index_path, tokenizer, user_dict)? {
+                Some(config) => config,
+                None => tokenizer_config(&index_path)?,
+            };
+            print_all(&config.analyze(&text)?, args.output, &style)?;
+        }
+        Action::Add {
+            index_path,
+            title,
+            author,
+            dynasty,
+            content,
+            translation,
+        } => {
+            let poems = match (title, author, dynasty, content) {
+                (Some(title), Some(author), Some(dynasty), Some(content)) => vec![Poem {
+                    title,
+                    author,
+                    dynasty,
+                    content,
+                    source: None,
+                    tags: Vec::new(),
+                    translation,
+                    appreciation: None,
+                }],
+                _ => match serde_json::from_reader(io::stdin().lock())
+                    .context("failed to parse the poems from stdin")?
+                {
+                    OneOrMany::One(poem) => vec![poem],
+                    OneOrMany::Many(poems) => poems,
+                },
+            };
+            let added: HashSet<String> = UserPoems::insert(poems.clone())?
+                .iter()
+                .map(Poem::id)
+                .collect();
+            for poem in &poems {
+                if added.contains(&poem.id()) {
+                    println!("added {}", poem.id());
+                } else {
+                    println!("{} is already added", poem.id());
+                }
+            }
+            if index_path.exists() {
+                add_to_index(&index_path, &poems, &authors)?;
+            } else {
+                eprintln!(
+                    "no index found at {}, run `poem index` to make the poems searchable",
+                    index_path.display()
+                );
+            }
+        }
+        Action::Import { source } => match source {
+            ImportSource::File {
+                file,
+                format,
+                out,
+                index_path,
+            } => {
+                let text = fs::read_to_string(&file).map_err(PoemError::io(&file))?;
+                let poems = format
+                    .unwrap_or_else(|| Format::of(&file))
+                    .read(&text)
+                    .with_context(|| format!("failed to parse {}", file.display()))?;
+                import_poems(poems, out, &index_path, &authors)?;
+            }
+            ImportSource::ChinesePoetry {
+                dir,
+                out,
+                index_path,
+            } => {
+                let imported = import::chinese_poetry(&dir)?;
+                if !imported.skipped.is_empty() {
+                    eprintln!("skipped {} files without poems", imported.skipped.len());
+                }
+                import_poems(imported.poems, out, &index_path, &authors)?;
+            }
+            ImportSource::Csv {
+                file,
+                columns,
+                delimiter,
+                no_header,
+                out,
+                index_path,
+            } => {
+                let text = fs::read_to_string(&file).map_err(PoemError::io(&file))?;
+                let poems = csv::read(&text, &columns, delimiter, no_header)
+                    .with_context(|| format!("failed to parse {}", file.display()))?;
+                import_poems(poems, out, &index_path, &authors)?;
+            }
+        },
+        Action::Remove { index_path, id } => {
+            let poem = UserPoems::delete(std::slice::from_ref(&id))?
+                .pop()
+                .ok_or_else(|| not_added(&id))?;
+            if index_path.exists() {
+                remove_from_index(&index_path, &[poem])?;
+            }
+        }
+        Action::Edit {
+            index_path,
+            id,
+            title,
+            author,
+            dynasty,
+            content,
+            translation,
+        } => {
+            let old = UserPoems::find(&id)?.ok_or_else(|| not_added(&id))?;
+            let poem = Poem {
+                title: title.unwrap_or_else(|| old.title.clone()),
+                author: author.unwrap_or_else(|| old.author.clone()),
+                dynasty: dynasty.unwrap_or_else(|| old.dynasty.clone()),
+                content: content.unwrap_or_else(|| old.content.clone()),
+                source: old.source.clone(),
+                tags: old.tags.clone(),
+                translation: translation.or_else(|| old.translation.clone()),
+                appreciation: old.appreciation.clone(),
+            };
+            let moved = poem.id() != id;
+            // 先查重 查出来了原来那首还在
+            if moved && load_poem(None, &poem.id())?.is_some() {
+                return Err(anyhow!("there is already a poem with id {}", poem.id()));
+            }
+            UserPoems::update(&id, &poem)?;
+            if moved {
+                move_user_data(&id, &poem.id())?;
+            }
+            if index_path.exists() {
+                // id由内容算出 修改就是删掉旧的再加上新的
+                remove_from_index(&index_path, &[old])?;
+                // 标签跟着移过来了 索引里也要有
+                let mut indexed = [poem.clone()];
+                Tags::load()?.apply(&mut indexed);
+                add_to_index(&index_path, &indexed, &authors)?;
+            }
+            println!("{}", poem.id());
+        }
+        Action::Status {
+            index_path,
+            poems_file,
+        } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let status = index_status(index_path, &poems, &authors)?;
+            print_all(&[status], args.output, &style)?;
+        }
+        Action::Verify {
+            index_path,
+            poems_file,
+            repair,
+        } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let verification = verify_index(&index_path, &poems)?;
+            print_all(&[&verification], args.output, &style)?;
+            if !verification.is_ok() && !repair {
+                eprintln!("run `poem verify --repair` to fix the index");
+                print_timings();
+                process::exit(1);
+            }
+            if !verification.is_ok() {
+                // 文件坏了或者有重复只能整个重建 只是少了或多了诗就增量补上
+                let force = !verification.damage.is_empty() || verification.duplicates > 0;
+                let tokenizer = force.then(|| tokenizer_config(&index_path).unwrap_or_default());
+                let bar = ProgressBar::new(0);
+                let indexed = build_index(
+                    &index_path,
+                    poems.clone(),
+                    &authors,
+                    force,
+                    tokenizer,
+                    None,
+                    &bar,
+                )?;
+                println!("{}", indexed);
+                let verification = verify_index(&index_path, &poems)?;
+                print_all(&[&verification], args.output, &style)?;
+            }
+        }
+        Action::Optimize { index_path } => {
+            if !index_path.exists() {
+                return Err(PoemError::IndexMissing(index_path).into());
+            }
+            let optimized = optimize(&index_path)?;
+            print_all(&[optimized], args.output, &style)?;
+        }
+        Action::Bench {
+            queries,
+            samples,
+            tokenizer,
+            user_dict,
+            threads,
+            seed,
+            poems_file,
+        } => {
+            let opts = BenchOptions {
+                queries,
+                samples,
+                tokenizer: choose_tokenizer(Path::new(""), tokenizer, user_dict)?
+                    .unwrap_or_default(),
+                threads,
+                seed,
+            };
+            let workloads = bench::bench(load_poems(poems_file.as_deref())?, &authors, &opts)?;
+            print_all(&workloads, args.output, &style)?;
+        }
+        Action::Validate { poems_file } => {
+            let (poems, mut issues) = match &poems_file {
+                Some(path) => {
+                    let bytes = fs::read(path).map_err(PoemError::io(&path))?;
+                    let (text, issues) = validate::check_encoding(&bytes);
+                    let poems = Format::of(path)
+                        .read(&text)
+                        .with_context(|| format!("failed to parse {}", path.display()))?;
+                    (poems, issues)
+                }
+                None => (load_poems(None)?, Vec::new()),
+            };
+            issues.extend(validate::validate(&poems));
+            print_all(&issues, args.output, &style)?;
+            if !issues.is_empty() {
+                eprintln!("{} problems in {} poems", issues.len(), poems.len());
+                print_timings();
+                process::exit(1);
+            }
+        }
+        Action::Dedup {
+            threshold,
+            apply,
+            interactive,
+            out,
+            index_path,
+            poems_file,
+        } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let duplicates = dedup::find(&poems, threshold);
+            if !apply && !interactive {
+                print_all(&duplicates, args.output, &style)?;
+                eprintln!("{} groups of duplicates", duplicates.len());
+                return Ok(());
+            }
+            let mut dropped = HashSet::new();
+            for group in &duplicates {
+                let keep = if interactive {
+                    choose_duplicate(group, &style)?
+                } else {
+                    Some(0)
+                };
+                if let Some(keep) = keep {
+                    dropped.extend(group.others(keep));
+                }
+            }
+            dedup_poems(&poems, &dropped, out, poems_file, &index_path)?;
+        }
+        Action::Merge {
+            files,
+            out,
+            prefer_first: _,
+            prefer_longer_content,
+            threshold,
+        } => {
+            let mut poems = Vec::new();
+            for file in &files {
+                let found = load_poems(Some(file))?;
+                eprintln!("{}: {} poems", file.display(), found.len());
+                poems.extend(found);
+            }
+            let prefer = if prefer_longer_content {
+                dedup::Prefer::LongerContent
+            } else {
+                dedup::Prefer::First
+            };
+            let duplicates = dedup::find(&poems, threshold);
+            let dropped: HashSet<usize> = duplicates
+                .iter()
+                .flat_map(|group| group.others(group.keep(prefer)))
+                .collect();
+            let same = duplicates.iter().filter(|d| d.similarity >= 1.0).count();
+            eprintln!(
+                "{} groups of the same poem, {} differing in content",
+                same,
+                duplicates.len() - same
+            );
+            let kept: Vec<&Poem> = (0..poems.len())
+                .filter(|i| !dropped.contains(i))
+                .map(|i| &poems[i])
+                .collect();
+            let format = Format::of(&out);
+            write_to(Some(out), |w| format.write(&kept, w))?;
+            println!(
+                "wrote {} poems, {} duplicates left out",
+                kept.len(),
+                dropped.len()
+            );
+        }
+        Action::Search {
+            index_path,
+            all_collections,
+            in_memory,
+            no_auto_index,
+            poems_file,
+            opts,
+            facets,
+            snippet,
+            sort,
+            copy,
+            quiet,
+            count,
+            explain,
+            stdin,
+            keyword,
+        } => {
+            let keyword = keyword.unwrap_or_default();
+            let sources: Vec<(String, PathBuf)> = if all_collections {
+                Collection::list()?
+                    .into_iter()
+                    .map(|c| (c.name.clone(), c.index_path()))
+                    .collect()
+            } else {
+                index_path
+                    .into_iter()
+                    .map(|p| (p.display().to_string(), p))
+                    .collect()
+            };
+            if all_collections || sources.len() > 1 {
+                if facets || snippet || explain {
+                    return Err(anyhow!(
+                        "--facets, --snippet and --explain only work with a single index"
+                    ));
+                }
+                if sources.is_empty() {
+                    return Err(anyhow!(
+                        "no collections yet, create one with `poem collection create`"
+                    ));
+                }
+                let repos = sources
+                    .into_iter()
+                    .map(|(name, path)| {
+                        let repo = open_for_search(
+                            &path,
+                            in_memory,
+                            !no_auto_index,
+                            poems_file.as_deref(),
+                            &authors,
+                        )?;
+                        Ok((name, repo))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let batch = Batch {
+                    snippet,
+                    sort,
+                    count,
+                    quiet,
+                };
+                if stdin {
+                    let found = search_batch(&repos, &opts, &batch, args.output, &style)?;
+                    exit_unless(found);
+                    return Ok(());
+                }
+                warn_all(repos[0].1.query_warnings(&keyword, &opts)?);
+                let mut hits = federated_search(&repos, &keyword, &opts)?;
+                if count {
+                    if !quiet {
+                        println!("{}", hits.len());
+                    }
+                } else {
+                    sort.sort(&mut hits);
+                    if copy {
+                        if let Some(hit) = hits.first() {
+                            clipboard::copy(&clipboard::plain_text(&[&hit.poem]))?;
+                        }
+                    }
+                    if !quiet {
+                        print_all(&hits, args.output, &style)?;
+                    }
+                }
+                exit_unless(!hits.is_empty());
+                return Ok(());
+            }
+            let (name, path) = sources.into_iter().next().unwrap();
+            let repo = open_for_search(
+                &path,
+                in_memory,
+                !no_auto_index,
+                poems_file.as_deref(),
+                &authors,
+            )?;
+            if stdin {
+                let batch = Batch {
+                    snippet,
+                    sort,
+                    count,
+                    quiet,
+                };
+                let found = search_batch(&[(name, repo)], &opts, &batch, args.output, &style)?;
+                exit_unless(found);
+                return Ok(());
+            }
+            warn_all(repo.query_warnings(&keyword, &opts)?);
+            if count {
+                let count = repo.count(&keyword, &opts)?;
+                if !quiet {
+                    println!("{}", count);
+                }
+                exit_unless(count > 0);
+                return Ok(());
+            }
+            if explain {
+                let query = repo.parsed_query(&keyword, &opts)?;
+                let mut hits = repo.search_explained(&keyword, &opts)?;
+                sort.sort(&mut hits);
+                match args.output {
+                    Output::Text | Output::Markdown => {
+                        println!("{}\n", query);
+                        print_all(&hits, args.output, &style)?;
+                    }
+                    Output::Json | Output::Ndjson => {
+                        #[derive(Serialize)]
+                        struct Explained<'a> {
+                            query: &'a str,
+                            hits: &'a [Hit],
+                        }
+                        let explained = Explained {
+                            query: &query,
+                            hits: &hits,
+                        };
+                        match args.output {
+                            Output::Ndjson => print_line(&explained)?,
+                            _ => println!("{}", serde_json::to_string_pretty(&explained)?),
+                        }
+                    }
+                }
+                exit_unless(!hits.is_empty());
+                return Ok(());
+            }
+            // 按相关度排的时候不用等所有结果 边搜边输出
+            if args.output == Output::Ndjson
+                && sort == SortBy::Relevance
+                && !(facets || snippet || copy || quiet)
+            {
+                let mut found = false;
+                repo.search_each(&keyword, &opts, |hit| {
+                    found = true;
+                    print_line(hit)
+                })?;
+                exit_unless(found);
+                return Ok(());
+            }
+            let mut hits = if snippet {
+                repo.search_snippets(&keyword, &opts, SNIPPET_BYTES)?
+            } else {
+                repo.search(&keyword, &opts)?
+            };
+            sort.sort(&mut hits);
+            let found = !hits.is_empty();
+            if copy {
+                if let Some(hit) = hits.first() {
+                    clipboard::copy(&clipboard::plain_text(&[&hit.poem]))?;
+                }
+            }
+            if !quiet && !facets {
+                print_all(&hits, args.output, &style)?;
+            } else if !quiet {
+                let facets = repo.dynasty_facets(&keyword, &opts)?;
+                let counts: Vec<Count> = facets
+                    .iter()
+                    .map(|(name, count)| Count {
+                        name,
+                        count: *count as _,
+                    })
+                    .collect();
+                match args.output {
+                    Output::Text | Output::Markdown => {
+                        print_all(&hits, args.output, &style)?;
+                        println!("朝代：");
+                        print_all(&counts, args.output, &style)?;
+                    }
+                    Output::Json | Output::Ndjson => {
+                        #[derive(Serialize)]
+                        struct Faceted<'a> {
+                            hits: Vec<Hit>,
+                            facets: Vec<Count<'a>>,
+                        }
+                        let faceted = Faceted {
+                            hits,
+                            facets: counts,
+                        };
+                        match args.output {
+                            Output::Ndjson => print_line(&faceted)?,
+                            _ => println!("{}", serde_json::to_string_pretty(&faceted)?),
+                        }
+                    }
+                }
+            }
+            exit_unless(found);
+        }
+        Action::Repl {
+            index_path,
+            poems_file,
+            opts,
+            limit,
+        } => {
+            let repo = open_for_search(&index_path, false, true, poems_file.as_deref(), &authors)?;
+            repl::run(&repo, opts, limit, &style, io::stdin().lock(), io::stdout())?;
+        }
+        Action::Lines {
+            index_path,
+            opts,
+            keyword,
+        } => {
+            if opts.field.is_some() {
+                return Err(anyhow!("--field can not be used with lines"));
+            }
+            if opts.tag.is_some() {
+                return Err(anyhow!("--tag can not be used with lines"));
+            }
+            if opts.year_from.is_some() || opts.year_to.is_some() {
+                return Err(anyhow!(
+                    "--year-from and --year-to can not be used with lines"
+                ));
+            }
+            let keyword = keyword.unwrap_or_default();
+            let repo = PoemRepository::open(index_path)?;
+            warn_all(repo.line_query_warnings(&keyword, &opts)?);
+            let hits = repo.search_lines(&keyword, &opts)?;
+            print_all(&hits, args.output, &style)?;
+        }
+        Action::Grep {
+            pattern,
+            regex,
+            only_lines,
+            filter,
+            poems_file,
+        } => {
+            let pattern = grep::Pattern::new(&pattern, regex)?;
+            let poems = load_poems_matching(poems_file.as_deref(), &filter)?;
+            let poems = poems.iter();
+            if only_lines {
+                print_all(&grep::lines(poems, &pattern), args.output, &style)?;
+            } else {
+                print_all(&grep::poems(poems, &pattern), args.output, &style)?;
+            }
+        }
+        Action::List {
+            limit,
+            offset,
+            sort,
+            filter,
+            poems_file,
+        } => {
+            let repo =
+                PoemRepository::from_poems(load_poems_matching(poems_file.as_deref(), &filter)?);
+            let poems = repo.list_by(&filter, sort, offset, limit);
+            print_all(&poems, args.output, &style)?;
+        }
+        Action::Random {
+            count,
+            filter,
+            from_favorites,
+            seed,
+            no_repeat,
+            reset_history,
+            copy,
+            poems_file,
+        } => {
+            let mut poems = load_poems_matching(poems_file.as_deref(), &filter)?;
+            if from_favorites {
+                let favorites = Favorites::load()?;
+                poems.retain(|p| favorites.contains(&p.id()));
+            }
+            let mut history = History::load()?;
+            if reset_history {
+                history.clear();
+            }
+            if no_repeat {
+                // 都看过了就从头再来
+                if !poems
+                    .iter()
+                    .any(|p| filter.matches(p) && !history.contains(&p.id()))
+                {
+                    history.clear();
+                }
+                poems.retain(|p| !history.contains(&p.id()));
+            }
+            let repo = PoemRepository::from_poems(poems);
+            let poems = match seed {
+                Some(seed) => repo.random_with(count, &filter, &mut StdRng::seed_from_u64(seed)),
+                None => repo.random(count, &filter),
+            };
+            if no_repeat {
+                poems.iter().for_each(|p| history.add(&p.id()));
+            }
+            if no_repeat || reset_history {
+                history.save()?;
+            }
+            if copy && !poems.is_empty() {
+                clipboard::copy(&clipboard::plain_text(&poems))?;
+            }
+            if poems.is_empty() && args.output == Output::Text {
+                println!("no poem in repo");
+                return Ok(());
+            }
+            print_all(&poems, args.output, &style)?;
+        }
+        Action::Show {
+            name,
+            author,
+            copy,
+            poems_file,
+        } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let poem = match repo.get(&name) {
+                Some(poem) => poem,
+                None => {
+                    let found: Vec<&Poem> = repo
+                        .list(None)
+                        .iter()
+                        .filter(|p| p.title == name)
+                        .filter(|p| author.as_ref().is_none_or(|a| &p.author == a))
+                        .collect();
+                    choose(&name, &found)?
+                }
+            };
+            if copy {
+                clipboard::copy(&clipboard::plain_text(&[poem]))?;
+            }
+            print_all(&[poem], args.output, &style)?;
+        }
+        Action::Pick {
+            query,
+            builtin,
+            copy,
+            poems_file,
+        } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            // 选的过程写到stderr stdout只有选中的诗
+            let picked = pick::pick(
+                &poems,
+                query.as_deref(),
+                builtin,
+                io::stdin().lock(),
+                io::stderr(),
+            )?;
+            if let Some(poem) = picked {
+                if copy {
+                    clipboard::copy(&clipboard::plain_text(&[poem]))?;
+                }
+                print_all(&[poem], args.output, &style)?;
+            }
+            exit_unless(picked.is_some());
+        }
+        Action::Rhyme { id, poems_file } => {
+            let poem = load_poem(poems_file.as_deref(), &id)?
+                .ok_or_else(|| anyhow!("no poem with id {}", id))?;
+            print_all(&[rhyme::Report::new(&poem)], args.output, &style)?;
+        }
+        Action::Tones {
+            id,
+            text,
+            check,
+            poems_file,
+        } => {
+            let poem = match (id, text) {
+                (Some(id), _) => load_poem(poems_file.as_deref(), &id)?
+                    .ok_or_else(|| anyhow!("no poem with id {}", id))?,
+                (None, text) => Poem {
+                    title: String::new(),
+                    author: String::new(),
+                    dynasty: String::new(),
+                    content: text.unwrap_or_default(),
+                    source: None,
+                    tags: Vec::new(),
+                    translation: None,
+                    appreciation: None,
+                },
+            };
+            print_all(&[tones::Report::new(&poem, check)?], args.output, &style)?;
+        }
+        Action::Card {
+            id,
+            out,
+            card: card_style,
+            poems_file,
+        } => {
+            let poem = load_poem(poems_file.as_deref(), &id)?
+                .ok_or_else(|| anyhow!("no poem with id {}", id))?;
+            let svg = card::svg(&poem, &card_style, style.vertical);
+            write_to(out, |w| Ok(w.write_all(svg.as_bytes())?))?;
+        }
+        Action::Daily {
+            date,
+            seedless,
+            poems_file,
+        } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let poem = if seedless {
+                repo.random(1, &PoemFilter::default()).pop()
+            } else {
+                repo.daily(date.unwrap_or_else(|| Local::today().naive_local()))
+            };
+            match poem {
+                Some(p) => print_all(&[p], args.output, &style)?,
+                None => println!("no poem in repo"),
+            }
+        }
+        Action::Quiz {
+            mode:
+                Some(QuizMode::NextLine {
+                    rounds,
+                    filter,
+                    poems_file,
+                }),
+            ..
+        } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let poems: Vec<&Poem> = repo
+                .list(None)
+                .iter()
+                .filter(|p| filter.matches(p))
+                .collect();
+            quiz::next_line(
+                &poems,
+                rounds,
+                &mut rand::thread_rng(),
+                io::stdin().lock(),
+                io::stdout(),
+            )?;
+        }
+        Action::Quiz {
+            mode: None,
+            blanks,
+            chars,
+            filter,
+            poems_file,
+        } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let poem = match repo.random(1, &filter).pop() {
+                Some(p) => p,
+                None => {
+                    println!("no poem in repo");
+                    return Ok(());
+                }
+            };
+            let cloze = quiz::cloze(poem, blanks, chars, &mut rand::thread_rng());
+            quiz::run(poem, &cloze, io::stdin().lock(), io::stdout())?;
+        }
+        Action::Feihua { index_path, key } => {
+            let repo = PoemRepository::open(index_path)?;
+            feihua::run(
+                &repo,
+                key,
+                &mut rand::thread_rng(),
+                io::stdin().lock(),
+                io::stdout(),
+            )?;
+        }
+        Action::Couplet {
+            index_path,
+            line,
+            limit,
+        } => {
+            let repo = PoemRepository::open(index_path)?;
+            let lines = repo.all_lines()?;
+            match line {
+                Some(line) => {
+                    let hits = couplet::candidates(&lines, &couplet::clean(&line), limit);
+                    print_all(&hits, args.output, &style)?;
+                    exit_unless(!hits.is_empty());
+                }
+                None => {
+                    couplet::practice(
+                        &lines,
+                        &mut rand::thread_rng(),
+                        io::stdin().lock(),
+                        io::stdout(),
+                    )?;
+                }
+            }
+        }
+        Action::Similar {
+            index_path,
+            id,
+            limit,
+            terms,
+            poems_file,
+        } => {
+            let repo =
+                PoemRepository::open(&index_path)?.with_poems(load_poems(poems_file.as_deref())?);
+            let vectors = if terms || !VectorIndex::exists(&index_path) {
+                None
+            } else {
+                Some(VectorIndex::open(&index_path)?)
+            };
+            // 向量里没有这首诗时退回到按词比较
+            let hits = match vectors.as_ref().and_then(|v| v.similar(&id, limit)) {
+                Some(nearest) => vector_hits(&repo, nearest),
+                None => repo.similar(&id, limit)?,
+            };
+            print_all(&hits, args.output, &style)?;
+            exit_unless(!hits.is_empty());
+        }
+        Action::Collection { action } => match action {
+            CollectionAction::List => {
+                let summaries = Collection::list()?
+                    .iter()
+                    .map(Collection::summary)
+                    .collect::<Result<Vec<_>>>()?;
+                print_all(&summaries, args.output, &style)?;
+            }
+            CollectionAction::Create { name, from } => {
+                let poems = load_poems(from.as_deref())?;
+                let collection = Collection::create(&name, &poems)?;
+                println!(
+                    "created {} with {} poems, index it with `poem --collection {} index`",
+                    collection.name,
+                    poems.len(),
+                    collection.name
+                );
+            }
+            CollectionAction::Delete { name } => {
+                Collection::open(&name)?.delete()?;
+                println!("deleted {}", name);
+            }
+        },
+        Action::Semantic { action } => match action {
+            SemanticAction::Index {
+                index_path,
+                embed_command,
+                force,
+                poems_file,
+            } => {
+                let repo = PoemRepository::open(&index_path)?
+                    .with_poems(load_poems(poems_file.as_deref())?);
+                let embedder = Embedder::new(embed_command);
+                let embedded = VectorIndex::build(&index_path, repo.list(None), embedder, force)?;
+                println!("embedded {}", embedded);
+            }
+            SemanticAction::Search {
+                index_path,
+                query,
+                limit,
+                poems_file,
+            } => {
+                let repo = PoemRepository::open(&index_path)?
+                    .with_poems(load_poems(poems_file.as_deref())?);
+                let vectors = VectorIndex::open(&index_path)?;
+                let hits = vector_hits(&repo, vectors.search(&query, limit)?);
+                print_all(&hits, args.output, &style)?;
+                exit_unless(!hits.is_empty());
+            }
+        },
+        Action::Authors { action, poems_file } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            match action {
+                Some(AuthorsAction::Show { name }) => {
+                    print_all(&repo.by_author(&name), args.output, &style)?
+                }
+                Some(AuthorsAction::Info { name }) => {
+                    let named = authors.named(&name);
+                    if named.is_empty() {
+                        return Err(anyhow!("nothing is known about {}", name));
+                    }
+                    print_all(&named, args.output, &style)?
+                }
+                None => {
+                    let poems = repo.list(None);
+                    let authors: Vec<&str> = poems.iter().map(|p| &p.author[..]).collect();
+                    let counts: Vec<Count> = words_count(&authors, true)
+                        .into_iter()
+                        .map(|(name, count)| Count { name, count })
+                        .collect();
+                    print_all(&counts, args.output, &style)?;
+                }
+            }
+        }
+        Action::Backup { out } => {
+            let files = backup::backup(&out)?;
+            println!("saved {} to {}", files.join(" "), out.display());
+        }
+        Action::Restore { archive } => {
+            let files = backup::restore(&archive)?;
+            println!(
+                "restored {} files, rebuild the index with `poem index --force`",
+                files.len()
+            );
+        }
+        Action::Pack {
+            out,
+            tokenizer,
+            user_dict,
+            threads,
+        } => {
+            let tokenizer = choose_tokenizer(Path::new(""), tokenizer, user_dict)?;
+            let bar = ProgressBar::new(0);
+            let manifest =
+                prebuilt::pack(bundled_poems()?, &authors, tokenizer, threads, &out, &bar)?;
+            println!(
+                "packed the index of {} poems into {}, build poem with \
+                 `POEM_PREBUILT_INDEX={} cargo build --release --features prebuilt-index` to ship it",
+                manifest.poems,
+                out.display(),
+                out.display()
+            );
+        }
+        Action::Unpack {
+            index_path,
+            archive,
+        } => {
+            let (manifest, indexed) =
+                prebuilt::unpack(&archive, &index_path, load_poems(None)?, &authors)?;
+            println!(
+                "unpacked the index of {} poems into {}, {}",
+                manifest.poems,
+                index_path.display(),
+                indexed
+            );
+        }
+        Action::Fetch {
+            url,
+            as_collection,
+            force,
+            reset,
+            columns,
+        } => {
+            if reset {
+                if !fetch::uninstall()? {
+                    return Err(anyhow!("the bundled poems are already used"));
+                }
+                println!(
+                    "using the bundled poems again, rebuild the index with `poem index --force`"
+                );
+                return Ok(());
+            }
+            let url = url.unwrap();
+            let bytes = fetch::download(&url)?;
+            let (text, mut issues) = validate::check_encoding(&bytes);
+            let path = fetch::path_of(&url);
+            let poems = match path.extension().and_then(|e| e.to_str()) {
+                Some("csv") => csv::read(&text, &columns, ',', false),
+                _ => Format::of(path).read(&text),
+            }
+            .with_context(|| format!("failed to parse {}", url))?;
+            if poems.is_empty() {
+                return Err(anyhow!("no poems in {}", url));
+            }
+            issues.extend(validate::validate(&poems));
+            // 重复和空白这种小问题公开的数据里很常见 只提醒
+            let (serious, minor): (Vec<_>, Vec<_>) = issues.into_iter().partition(|i| {
+                matches!(
+                    i.problem,
+                    validate::Problem::Encoding
+                        | validate::Problem::Empty
+                        | validate::Problem::Control
+                )
+            });
+            if !serious.is_empty() && !force {
+                print_all(&serious, args.output, &style)?;
+                return Err(anyhow!(
+                    "{} problems in {}, use it anyway with --force",
+                    serious.len(),
+                    url
+                ));
+            }
+            if !minor.is_empty() {
+                eprintln!(
+                    "{} smaller problems like duplicates or stray whitespace, see `poem validate`",
+                    minor.len()
+                );
+            }
+            match as_collection {
+                Some(name) => {
+                    let collection = Collection::create(&name, &poems)?;
+                    println!(
+                        "created {} with {} poems, index it with `poem --collection {} index`",
+                        collection.name,
+                        poems.len(),
+                        collection.name
+                    );
+                }
+                None => {
+                    fetch::install(&poems)?;
+                    println!(
+                        "using {} poems from {} instead of the bundled ones, rebuild the index with `poem index --force`",
+                        poems.len(),
+                        url
+                    );
+                }
+            }
+        }
+        Action::Timeline { filter, poems_file } => {
+            let mut poems = load_poems(poems_file.as_deref())?;
+            poems.retain(|p| filter.matches(p));
+            let eras = timeline::timeline(&poems, &authors);
+            print_all(&eras, args.output, &style)?;
+            exit_unless(!eras.is_empty());
+        }
+        Action::Fav { action } => fav(action, args.output, &style)?,
+        Action::Tag { action } => tag(action, args.output, &style, &authors)?,
+        Action::Note { action } => note(action, args.output, &style)?,
+        Action::Completions { shell, poems_file } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let values = |f: fn(&Poem) -> &str| {
+                let mut values: Vec<&str> = poems.iter().map(f).collect();
+                values.sort_unstable();
+                values.dedup();
+                values
+            };
+            let (authors, dynasties) = (values(|p| &p.author), values(|p| &p.dynasty));
+            let mut app = with_values(Args::into_app(), &authors, &dynasties);
+            clap_complete::generate(shell, &mut app, "poem", &mut io::stdout());
+        }
+        Action::Export { target } => match target {
+            ExportTarget::Anki {
+                out,
+                cards,
+                front,
+                back,
+                selection,
+            } => {
+                let poems = selection.poems()?;
+                let poems: Vec<&Poem> = poems.iter().collect();
+                write_to(out, |w| export::anki(&poems, &cards, &front, &back, w))?;
+            }
+            ExportTarget::Html {
+                out,
+                title,
+                selection,
+            } => {
+                let poems = selection.poems()?;
+                let poems: Vec<&Poem> = poems.iter().collect();
+                write_to(out, |w| export::html(&poems, &title, w))?;
+            }
+            ExportTarget::Latex { out, selection } => {
+                let poems = selection.poems()?;
+                let poems: Vec<&Poem> = poems.iter().collect();
+                write_to(out, |w| export::latex(&poems, style.pinyin, w))?;
+            }
+            ExportTarget::Poems {
+                out,
+                format,
+                selection,
+            } => {
+                let poems = selection.poems()?;
+                let poems: Vec<&Poem> = poems.iter().collect();
+                let format = format
+                    .or_else(|| out.as_deref().map(Format::of))
+                    .unwrap_or(Format::Json);
+                write_to(out, |w| format.write(&poems, w))?;
+            }
+            ExportTarget::Csv {
+                out,
+                columns,
+                delimiter,
+                bom,
+                selection,
+            } => {
+                let poems = selection.poems()?;
+                let poems: Vec<&Poem> = poems.iter().collect();
+                write_to(out, |w| csv::write(&poems, &columns, delimiter, bom, w))?;
+            }
+            ExportTarget::Epub {
+                out,
+                title,
+                selection,
+            } => {
+                let poems = selection.poems()?;
+                let poems: Vec<&Poem> = poems.iter().collect();
+                write_to(Some(out), |w| epub::write(&poems, &title, w))?;
+            }
+        },
+        Action::Memorize { action } => memorize(action, args.output, &style)?,
+        Action::Serve {
+            index_path,
+            addr,
+            poems_file,
+        } => {
+            let repo =
+                PoemRepository::open(index_path)?.with_poems(load_poems(poems_file.as_deref())?);
+            println!("listening on http://{}", addr);
+            server::serve(&addr, &repo)?;
+        }
+        Action::Wordfreq {
+            by,
+            filter,
+            top,
+            poems_file,
+        } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let counts = frequencies(poems.iter().filter(|p| filter.matches(p)), by, top);
+            print_all(&counts, args.output, &style)?;
+        }
+        Action::Stat {
+            sort,
+            top,
+            poems_file,
+        } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let stat = Stat::from_poems(&poems, sort, top);
+            println!("{}", stat);
+        }
+    }
+
+    Ok(())
+}
+
+/// give the tags, notes, favorite and memorizing progress of poem `from` to poem `to`
+fn move_user_data(from: &str, to: &str) -> Result<()> {
+    let mut tags = Tags::load()?;
+    if tags.rename(from, to) {
+        tags.save()?;
+    }
+    let mut notes = Notes::load()?;
+    if notes.rename(from, to) {
+        notes.save()?;
+    }
+    let mut favorites = Favorites::load()?;
+    if favorites.rename(from, to) {
+        favorites.save()?;
+    }
+    let mut memorize = Memorize::load()?;
+    if memorize.rename(from, to) {
+        memorize.save()?;
+    }
+    Ok(())
+}
+
+/// the error for `id` not being a poem added with `add`
+fn not_added(id: &str) -> anyhow::Error {
+    match bundled_poems() {
+        Ok(poems) if poems.iter().any(|p| p.id() == id) => {
+            anyhow!(
+                "poem {} is bundled, only poems added with `add` can be changed",
+                id
+            )
+        }
+        _ => anyhow!("no poem with id {} was added", id),
+    }
+}
+
+/// the value of `--config`, which has to be known before the arguments are parsed
+fn config_path() -> Option<PathBuf> {
+    arg_value("config").map(PathBuf::from)
+}
+
+/// the value of the option `--name` on the command line, before the arguments are parsed
+fn arg_value(name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("{}=", flag);
+    let mut args = env::args();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// change the argument `name` of `app` and all its subcommands with `f`
+fn mut_args<'a>(mut app: App<'a>, name: &str, f: &dyn Fn(Arg<'a>) -> Arg<'a>) -> App<'a> {
+    // mut_arg会加上不存在的参数 所以先看看有没有
+    let found = app.get_arguments().map(Arg::get_name).find(|n| *n == name);
+    if let Some(name) = found {
+        app = app.mut_arg(name, f);
+    }
+    for sub in app.get_subcommands_mut() {
+        *sub = mut_args(mem::take(sub), name, f);
+    }
+    app
+}
+
+/// offer `authors` and `dynasties` as the values of every --author and --dynasty
+fn with_values<'a>(app: App<'a>, authors: &[&'a str], dynasties: &[&'a str]) -> App<'a> {
+    let app = mut_args(app, "author", &|a| {
+        a.possible_values(authors.iter().copied())
+    });
+    mut_args(app, "dynasty", &|a| {
+        a.possible_values(dynasties.iter().copied())
+    })
+}
+
+/// the tokenizer config asked for by `--tokenizer` and `--user-dict`, none if neither is given
+fn choose_tokenizer(
+    index_path: &Path,
+    tokenizer: Option<Tokenizer>,
+    user_dict: Option<PathBuf>,
+) -> Result<Option<TokenizerConfig>> {
+    // 都没给时沿用索引原来的分词方式 只给词典时按字切分的索引改用jieba
+    match (tokenizer, user_dict) {
+        (None, None) => Ok(None),
+        (tokenizer, user_dict) => {
+            let tokenizer = match tokenizer {
+                Some(tokenizer) => tokenizer,
+                None => match tokenizer_config(index_path)?.tokenizer {
+                    Tokenizer::Unicode => Tokenizer::Jieba,
+                    current => current,
+                },
+            };
+            Ok(Some(TokenizerConfig::new(tokenizer, user_dict.as_deref())?))
+        }
+    }
+}
+
+/// the poems of the ids found in the vectors, the ones deleted since they were embedded skipped
+fn vector_hits(repo: &PoemRepository, nearest: Vec<(&str, f32)>) -> Vec<Hit> {
+    nearest
+        .into_iter()
+        .filter_map(|(id, score)| {
+            Some(Hit {
+                score,
+                poem: repo.get(id)?.clone(),
+                highlights: Vec::new(),
+                snippet: None,
+                explanation: None,
+                source: None,
+            })
+        })
+        .collect()
+}
+
+/// index the poems of `poems_file`, or the bundled ones, in the index at `path`
+fn index_file(
+    path: &Path,
+    poems_file: Option<&Path>,
+    authors: &Authors,
+    force: bool,
+    tokenizer: Option<TokenizerConfig>,
+    threads: Option<usize>,
+) -> Result<Indexed> {
+    match poems_file {
+        // json lines边读边索引 进度按读了多少字节算
+        Some(poems_file) if Format::of(poems_file) == Format::JsonLines => {
+            let file = File::open(poems_file).map_err(PoemError::io(poems_file))?;
+            let bar = ProgressBar::new(file.metadata()?.len());
+            bar.set_style(
+                ProgressStyle::default_bar()
+                    .template("{bar:40} {bytes}/{total_bytes} {bytes_per_sec} eta {eta}"),
+            );
+            let tags = Tags::load()?;
+            let poems = format::json_lines(io::BufReader::new(bar.wrap_read(file))).map(|poem| {
+                let mut poem =
+                    poem.with_context(|| format!("failed to parse {}", poems_file.display()))?;
+                tags.apply(std::slice::from_mut(&mut poem));
+                Ok(poem)
+            });
+            let counts = index_poems(path, poems, authors, force, tokenizer, threads)?;
+            bar.finish();
+            Ok(counts)
+        }
+        poems_file => {
+            let poems = load_poems(poems_file)?;
+            let bar = ProgressBar::new(0);
+            build_index(path, poems, authors, force, tokenizer, threads, &bar)
+        }
+    }
+}
+
+/// the repository with the index at `path`, if there is none yet it is unpacked from the
+/// prebuilt index of the binary, built in memory with `in_memory`, or at `path` with `auto_index`
+fn open_for_search(
+    path: &Path,
+    in_memory: bool,
+    auto_index: bool,
+    poems_file: Option<&Path>,
+    authors: &Authors,
+) -> Result<PoemRepository> {
+    // 从打好的包解开的索引 换了schema的版本后也换成新的包
+    let outdated = prebuilt::outdated(path);
+    if path.exists() && !outdated {
+        return PoemRepository::open(path);
+    }
+    if poems_file.is_none() && prebuilt::available() {
+        match prebuilt::install(path, load_poems(None)?, authors) {
+            Ok((manifest, indexed)) => {
+                eprintln!(
+                    "unpacked the prebuilt index of {} poems into {}, {}",
+                    manifest.poems,
+                    path.display(),
+                    indexed
+                );
+                return PoemRepository::open(path);
+            }
+            Err(e) => eprintln!("warning: {:#}", e),
+        }
+    }
+    if outdated {
+        return PoemRepository::open(path);
+    }
+    if in_memory {
+        return PoemRepository::in_memory(load_poems(poems_file)?, authors);
+    }
+    if !auto_index {
+        eprintln!("hint: search with --in-memory to search the poems without an index");
+        return Err(PoemError::IndexMissing(path.to_path_buf()).into());
+    }
+    eprintln!(
+        "no index found at {}, indexing the poems first",
+        path.display()
+    );
+    let bar = ProgressBar::new(0);
+    build_index(
+        path,
+        load_poems(poems_file)?,
+        authors,
+        false,
+        None,
+        None,
+        &bar,
+    )?;
+    PoemRepository::open(path)
+}
+
+/// how `search --stdin` searches and prints every keyword
+struct Batch {
+    snippet: bool,
+    sort: SortBy,
+    count: bool,
+    quiet: bool,
+}
+
+/// search `sources` for every line of stdin, printing the results of each before reading the
+/// next: a json line with the keyword for json and ndjson, the keyword and the count separated by
+/// a tab with `count`, or the results under a line with the keyword, returns whether anything was
+/// found
+///
+/// the results of several sources are merged like `federated_search` does
+fn search_batch(
+    sources: &[(String, PoemRepository)],
+    opts: &SearchOptions,
+    batch: &Batch,
+    output: Output,
+    style: &Style,
+) -> Result<bool> {
+    #[derive(Serialize)]
+    struct Searched<'a> {
+        query: &'a str,
+        count: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hits: Option<Vec<Hit>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    }
+    let mut found = false;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let query = line.trim();
+        if query.is_empty() {
+            continue;
+        }
+        let searched = match sources {
+            [(_, repo)] if batch.count => repo.count(query, opts).map(|n| (n, None)),
+            sources if batch.count => {
+                federated_search(sources, query, opts).map(|h| (h.len(), None))
+            }
+            sources => {
+                let hits = match sources {
+                    [(_, repo)] if batch.snippet => {
+                        repo.search_snippets(query, opts, SNIPPET_BYTES)
+                    }
+                    [(_, repo)] => repo.search(query, opts),
+                    sources => federated_search(sources, query, opts),
+                };
+                hits.map(|mut hits| {
+                    batch.sort.sort(&mut hits);
+                    (hits.len(), Some(hits))
+                })
+            }
+        };
+        // 一个关键词出错不影响后面的
+        let (count, hits, error) = match searched {
+            Ok((count, hits)) => (count, hits, None),
+            Err(e) => (0, None, Some(format!("{:#}", e))),
+        };
+        found |= count > 0;
+        if batch.quiet {
+            continue;
+        }
+        if let Output::Json | Output::Ndjson = output {
+            print_line(Searched {
+                query,
+                count,
+                hits,
+                error,
+            })?;
+            continue;
+        }
+        match (&error, &hits) {
+            (Some(error), _) => eprintln!("{}\terror: {}", query, error),
+            (None, None) => println!("{}\t{}", query, count),
+            (None, Some(hits)) => {
+                match output {
+                    Output::Markdown => println!("## {} ({})\n", query, count),
+                    _ => println!("==> {} ({}) <==", query, count),
+                }
+                print_all(hits, output, style)?;
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// print `warnings` to stderr, the results still go to stdout
+fn warn_all(warnings: Vec<String>) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+/// exit with 1 if nothing is `found`, like grep, so scripts can use a search as a condition
+fn exit_unless(found: bool) {
+    if !found {
+        print_timings();
+        process::exit(1);
+    }
+}
+
+/// print how long each stage took if --timings is given
+fn print_timings() {
+    if PRINT_TIMINGS.load(Ordering::Relaxed) {
+        eprintln!("{}", timings::summary());
+    }
+}
+
+/// the one of the poems titled `title`, asks which one on a terminal if there are several
+fn choose<'a>(title: &str, poems: &[&'a Poem]) -> Result<&'a Poem> {
+    match poems {
+        [] => Err(anyhow!("no poem titled {}", title)),
+        [poem] => Ok(poem),
+        _ if !io::stdin().is_terminal() => Err(anyhow!(
+            "{} poems are titled {}, choose one with --author: {}",
+            poems.len(),
+            title,
+            poems
+                .iter()
+                .map(|p| &p.author[..])
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        _ => {
+            for (i, p) in poems.iter().enumerate() {
+                eprintln!("{}. {}〔{}〕 {}", i + 1, p.author, p.dynasty, p.id());
+            }
+            loop {
+                eprint!("which one: ");
+                let mut line = String::new();
+                if io::stdin().read_line(&mut line)? == 0 {
+                    return Err(anyhow!("no poem chosen"));
+                }
+                match line.trim().parse::<usize>() {
+                    Ok(i) if (1..=poems.len()).contains(&i) => return Ok(poems[i - 1]),
+                    _ => eprintln!("enter a number from 1 to {}", poems.len()),
+                }
+            }
+        }
+    }
+}
+
+/// which poem of `group` to keep, none to keep them all
+fn choose_duplicate(group: &dedup::Duplicates, style: &Style) -> Result<Option<usize>> {
+    eprintln!("{}", Styled(group, style));
+    loop {
+        eprint!("keep which one, enter for the first, a for all: ");
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Err(anyhow!("no poem chosen"));
+        }
+        match line.trim() {
+            "" => return Ok(Some(0)),
+            "a" => return Ok(None),
+            n => match n.parse::<usize>() {
+                Ok(i) if (1..=group.poems.len()).contains(&i) => return Ok(Some(i - 1)),
+                _ => eprintln!("enter a number from 1 to {}", group.poems.len()),
+            },
+        }
+    }
+}
+
+/// write `poems` without the `dropped` ones to `out` or `poems_file`, or remove the dropped ones
+/// added by the user from them and the index if neither is given
+fn dedup_poems(
+    poems: &[Poem],
+    dropped: &HashSet<usize>,
+    out: Option<PathBuf>,
+    poems_file: Option<PathBuf>,
+    index_path: &Path,
+) -> Result<()> {
+    let kept: Vec<&Poem> = (0..poems.len())
+        .filter(|i| !dropped.contains(i))
+        .map(|i| &poems[i])
+        .collect();
+    if let Some(path) = out.or(poems_file) {
+        let format = Format::of(&path);
+        write_to(Some(path.clone()), |w| format.write(&kept, w))?;
+        println!(
+            "wrote {} poems to {}, {} duplicates removed",
+            kept.len(),
+            path.display(),
+            dropped.len()
+        );
+        return Ok(());
+    }
+    // 用户的诗排在自带的后面 自带的删不掉
+    let bundled = bundled_poems()?.len();
+    let ids: Vec<String> = dropped
+        .iter()
+        .filter(|&&i| i >= bundled)
+        .map(|&i| poems[i].id())
+        .collect();
+    let mut removed = UserPoems::delete(&ids)?;
+    let left = dropped.iter().filter(|&&i| i < bundled).count();
+    println!("removed {} duplicates", removed.len());
+    if left > 0 {
+        eprintln!(
+            "{} bundled poems can not be removed, keep them instead of the ones added",
+            left
+        );
+    }
+    // 一模一样的两首id相同 留下了一首就不能从索引里删
+    let kept_ids: HashSet<String> = kept.iter().map(|p| p.id()).collect();
+    removed.retain(|p| !kept_ids.contains(&p.id()));
+    if index_path.exists() && !removed.is_empty() {
+        remove_from_index(index_path, &removed)?;
+    }
+    Ok(())
+}
+
+/// write `poems` to `out` if given, add them to the poems of the user otherwise
+fn import_poems(
+    poems: Vec<Poem>,
+    out: Option<PathBuf>,
+    index_path: &Path,
+    authors: &Authors,
+) -> Result<()> {
+    let found = poems.len();
+    if let Some(path) = out {
+        let format = Format::of(&path);
+        let poems: Vec<&Poem> = poems.iter().collect();
+        write_to(Some(path), |w| format.write(&poems, w))?;
+        println!("wrote {} poems", found);
+        return Ok(());
+    }
+    let added = UserPoems::insert(poems)?;
+    println!(
+        "imported {} poems, {} were already there",
+        added.len(),
+        found - added.len()
+    );
+    if index_path.exists() {
+        add_to_index(index_path, &added, authors)?;
+    } else {
+        eprintln!(
+            "no index found at {}, run `poem index` to make the poems searchable",
+            index_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// run `write` on the file at `out`, or stdout if there is none
+fn write_to(out: Option<PathBuf>, write: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<()> {
+    match out {
+        Some(path) => {
+            let file = File::create(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            let mut w = BufWriter::new(file);
+            write(&mut w)?;
+            w.flush()?;
+            Ok(())
+        }
+        None => write(&mut io::stdout().lock()),
+    }
+}
+
+fn memorize(action: MemorizeAction, output: Output, style: &Style) -> Result<()> {
+    let mut cards = Memorize::load()?;
+    let today = Local::today().naive_local();
+    match action {
+        MemorizeAction::Add { id, poems_file } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            if repo.get(&id).is_none() {
+                return Err(anyhow!("no poem with id {}", id));
+            }
+            if cards.add(&id, today) {
+                cards.save()?;
+            }
+        }
+        MemorizeAction::Remove { id } => {
+            if !cards.remove(&id) {
+                return Err(anyhow!("poem {} is not being memorized", id));
+            }
+            cards.save()?;
+        }
+        MemorizeAction::Due { poems_file } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let poems: Vec<&Poem> = cards
+                .due(today)
+                .into_iter()
+                .filter_map(|id| repo.get(id))
+                .collect();
+            print_all(&poems, output, style)?;
+        }
+        MemorizeAction::Review { poems_file } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let due: Vec<String> = cards.due(today).into_iter().map(String::from).collect();
+            if due.is_empty() {
+                println!("nothing to review today");
+            }
+            let mut rng = rand::thread_rng();
+            for id in due {
+                let poem = match repo.get(&id) {
+                    Some(poem) => poem,
+                    None => continue,
+                };
+                // 空出一半的句子 答对的比例就是0到5的记忆程度
+                let cloze = quiz::cloze(poem, (poem.lines().len() / 2).max(1), false, &mut rng);
+                let right = quiz::run(poem, &cloze, io::stdin().lock(), io::stdout())?;
+                let quality = (5 * right as u32 + cloze.answers.len() as u32 / 2)
+                    / (cloze.answers.len() as u32).max(1);
+                let card = cards.get_mut(&id).unwrap();
+                card.review(quality as u8, today);
+                println!("下次复习：{}\n", card.due);
+                cards.save()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn tag(action: TagAction, output: Output, style: &Style, authors: &Authors) -> Result<()> {
+    let mut tags = Tags::load()?;
+    match action {
+        TagAction::Add {
+            id,
+            tags: added,
+            index_path,
+            poems_file,
+        } => {
+            find_poem(&load_poems(poems_file.as_deref())?, &id)?;
+            let mut changed = false;
+            for tag in &added {
+                changed |= tags.add(&id, tag);
+            }
+            if changed {
+                tags.save()?;
+                retag(&index_path, poems_file.as_deref(), &id, authors)?;
+            }
+        }
+        TagAction::Remove {
+            id,
+            tags: removed,
+            index_path,
+            poems_file,
+        } => {
+            let mut changed = false;
+            for tag in &removed {
+                changed |= tags.remove(&id, tag);
+            }
+            if !changed {
+                return Err(anyhow!("poem {} has none of these tags from `tag add`", id));
+            }
+            tags.save()?;
+            retag(&index_path, poems_file.as_deref(), &id, authors)?;
+        }
+        TagAction::List { id, poems_file } => {
+            let poems = load_poems(poems_file.as_deref())?;
+            let poems = match &id {
+                Some(id) => std::slice::from_ref(find_poem(&poems, id)?),
+                None => &poems[..],
+            };
+            print_all(&tag_counts(poems), output, style)?;
+        }
+    }
+    Ok(())
+}
+
+fn find_poem<'a>(poems: &'a [Poem], id: &str) -> Result<&'a Poem> {
+    poems
+        .iter()
+        .find(|p| p.id() == id)
+        .ok_or_else(|| anyhow!("no poem with id {}", id))
+}
+
+/// index the poem of `id` again with the tags it has now, if there is an index
+fn retag(index_path: &Path, poems_file: Option<&Path>, id: &str, authors: &Authors) -> Result<()> {
+    if !index_path.exists() {
+        return Ok(());
+    }
+    let poems = load_poems(poems_file)?;
+    let poem = std::slice::from_ref(find_poem(&poems, id)?);
+    // id不变 先删掉旧的文档再加
+    remove_from_index(index_path, poem)?;
+    add_to_index(index_path, poem, authors)?;
+    Ok(())
+}
+
+fn note(action: NoteAction, output: Output, style: &Style) -> Result<()> {
+    let mut notes = Notes::load()?;
+    match action {
+        NoteAction::Add {
+            id,
+            text,
+            poems_file,
+        } => {
+            find_poem(&load_poems(poems_file.as_deref())?, &id)?;
+            notes.add(&id, &text, Local::today().naive_local());
+            notes.save()?;
+        }
+        NoteAction::List { id } => print_all(&notes.numbered(&id), output, style)?,
+        NoteAction::Remove { id, number } => {
+            if notes.remove(&id, number).is_none() {
+                return Err(anyhow!("poem {} has no note {}", id, number));
+            }
+            notes.save()?;
+        }
+    }
+    Ok(())
+}
+
+fn fav(action: FavAction, output: Output, style: &Style) -> Result<()> {
+    let mut favorites = Favorites::load()?;
+    match action {
+        FavAction::Add { id, poems_file } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            if repo.get(&id).is_none() {
+                return Err(anyhow!("no poem with id {}", id));
+            }
+            if favorites.add(&id) {
+                favorites.save()?;
+            }
+        }
+        FavAction::List { poems_file } => {
+            let repo = PoemRepository::from_poems(load_poems(poems_file.as_deref())?);
+            let poems: Vec<&Poem> = favorites
+                .ids()
+                .iter()
+                .filter_map(|id| repo.get(id))
+                .collect();
+            print_all(&poems, output, style)?;
+        }
+        FavAction::Remove { id } => {
+            if !favorites.remove(&id) {
+                return Err(anyhow!("poem {} is not a favorite", id));
+            }
+            favorites.save()?;
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,172 @@
+use crate::profile;
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub(crate) const MEMORIZE_FILE: &str = "memorize.json";
+
+/// the review state of one poem, see the sm-2 algorithm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Card {
+    /// how easy the poem is, never below 1.3
+    pub ease: f64,
+    /// days until the next review
+    pub interval: i64,
+    /// right reviews in a row
+    pub repetitions: u32,
+    /// when the poem is to be reviewed
+    pub due: NaiveDate,
+}
+
+impl Card {
+    fn new(today: NaiveDate) -> Self {
+        Self {
+            ease: 2.5,
+            interval: 0,
+            repetitions: 0,
+            due: today,
+        }
+    }
+
+    /// schedule the next review after a review of `quality` from 0 to 5 on `today`,
+    /// 3 and above is remembered
+    pub fn review(&mut self, quality: u8, today: NaiveDate) {
+        let q = quality.min(5) as f64;
+        if quality >= 3 {
+            self.interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval as f64 * self.ease).round() as i64,
+            };
+            self.repetitions += 1;
+        } else {
+            // 忘了就从头开始 但ease照样更新
+            self.repetitions = 0;
+            self.interval = 1;
+        }
+        self.ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(1.3);
+        self.due = today + Duration::days(self.interval);
+    }
+}
+
+/// the poems being memorized by id
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Memorize {
+    cards: BTreeMap<String, Card>,
+}
+
+impl Memorize {
+    pub fn load() -> Result<Self> {
+        profile::load(MEMORIZE_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        profile::save(MEMORIZE_FILE, self)
+    }
+
+    /// start memorizing a poem, it is due on `today`, returns false if it is already there
+    pub fn add(&mut self, id: &str, today: NaiveDate) -> bool {
+        if self.cards.contains_key(id) {
+            return false;
+        }
+        self.cards.insert(id.to_string(), Card::new(today));
+        true
+    }
+
+    /// returns false if the poem is not being memorized
+    pub fn remove(&mut self, id: &str) -> bool {
+        self.cards.remove(id).is_some()
+    }
+
+    /// go on memorizing poem `from` as poem `to`, returns false if `from` is not being memorized
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        match self.cards.remove(from) {
+            Some(card) => {
+                self.cards.insert(to.to_string(), card);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Card> {
+        self.cards.get_mut(id)
+    }
+
+    /// ids of the poems to review on `today`, the most overdue first
+    pub fn due(&self, today: NaiveDate) -> Vec<&str> {
+        let mut due: Vec<(&String, &Card)> =
+            self.cards.iter().filter(|(_, c)| c.due <= today).collect();
+        due.sort_by_key(|(_, c)| c.due);
+        due.into_iter().map(|(id, _)| &id[..]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(n: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2024, 3, 1) + Duration::days(n as i64)
+    }
+
+    #[test]
+    fn passes_grow_the_interval() {
+        let mut card = Card::new(day(0));
+        let mut intervals = Vec::new();
+        for _ in 0..5 {
+            card.review(4, card.due);
+            intervals.push(card.interval);
+        }
+        // 4分不改ease 第三次起乘2.5
+        assert_eq!(intervals, [1, 6, 15, 38, 95]);
+        assert_eq!(card.repetitions, 5);
+        assert_eq!(card.due, day(1 + 6 + 15 + 38 + 95));
+    }
+
+    #[test]
+    fn a_failure_starts_over() {
+        let mut card = Card::new(day(0));
+        for _ in 0..3 {
+            card.review(5, card.due);
+        }
+        assert!(card.interval > 6);
+        let ease = card.ease;
+        card.review(2, day(30));
+        assert_eq!((card.interval, card.repetitions), (1, 0));
+        assert_eq!(card.due, day(31));
+        assert!(card.ease < ease);
+        // 之后再从1天 6天开始
+        card.review(3, card.due);
+        card.review(3, card.due);
+        assert_eq!(card.interval, 6);
+    }
+
+    #[test]
+    fn ease_stays_above_the_floor() {
+        let mut card = Card::new(day(0));
+        for _ in 0..10 {
+            card.review(0, card.due);
+            assert!(card.ease >= 1.3, "{}", card.ease);
+        }
+        assert_eq!(card.ease, 1.3);
+        card.review(3, card.due);
+        assert_eq!(card.ease, 1.3);
+        card.review(5, card.due);
+        assert!((card.ease - 1.4).abs() < 1e-9, "{}", card.ease);
+    }
+
+    #[test]
+    fn due_cards_come_most_overdue_first() {
+        let mut memorize = Memorize::default();
+        memorize.add("a", day(2));
+        memorize.add("b", day(0));
+        memorize.add("c", day(5));
+        assert!(!memorize.add("a", day(0)));
+        assert_eq!(memorize.due(day(3)), ["b", "a"]);
+        assert!(memorize.rename("c", "d"));
+        assert_eq!(memorize.due(day(5)), ["b", "a", "d"]);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    output::{Render, Style},
+    profile,
+};
+use anyhow::Result;
+use chrono::NaiveDate;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+pub(crate) const NOTES_FILE: &str = "notes.json";
+
+/// a study note of the user on a poem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    /// the day it was written
+    pub date: NaiveDate,
+}
+
+/// the notes of the user by the id of the poem, in the order they were written
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Notes {
+    notes: BTreeMap<String, Vec<Note>>,
+}
+
+impl Notes {
+    pub fn load() -> Result<Self> {
+        profile::load(NOTES_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        profile::save(NOTES_FILE, self)
+    }
+
+    pub fn add(&mut self, id: &str, text: &str, date: NaiveDate) {
+        self.notes.entry(id.to_string()).or_default().push(Note {
+            text: text.to_string(),
+            date,
+        });
+    }
+
+    /// remove the note of the poem numbered `number` from 1, none if there is no such note
+    pub fn remove(&mut self, id: &str, number: usize) -> Option<Note> {
+        let notes = self.notes.get_mut(id)?;
+        if number == 0 || number > notes.len() {
+            return None;
+        }
+        let note = notes.remove(number - 1);
+        if notes.is_empty() {
+            self.notes.remove(id);
+        }
+        Some(note)
+    }
+
+    /// give the notes of poem `from` to poem `to`, after the ones it has, returns false if
+    /// `from` has none
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let notes = match self.notes.remove(from) {
+            Some(notes) => notes,
+            None => return false,
+        };
+        self.notes.entry(to.to_string()).or_default().extend(notes);
+        true
+    }
+
+    /// the notes of the poem, the first written first
+    pub fn of(&self, id: &str) -> &[Note] {
+        self.notes.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    /// the notes of the poem with their numbers, as `note list` prints them
+    pub fn numbered(&self, id: &str) -> Vec<NumberedNote<'_>> {
+        self.of(id)
+            .iter()
+            .enumerate()
+            .map(|(i, note)| NumberedNote {
+                number: i + 1,
+                note,
+            })
+            .collect()
+    }
+}
+
+/// a note with the number `note remove` takes
+#[derive(Debug, Serialize)]
+pub struct NumberedNote<'a> {
+    pub number: usize,
+    #[serde(flatten)]
+    pub note: &'a Note,
+}
+
+impl Render for NumberedNote<'_> {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(
+            f,
+            "{}. {} {}",
+            self.number,
+            self.note.text,
+            self.note.date.to_string().dimmed()
+        )
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    layout::{Horizontal, Layout, Markdown, Pinyin, Vertical},
+    Authors, Notes,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+/// how results are printed
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// colored text for the terminal
+    Text,
+    /// a json array
+    Json,
+    /// markdown to paste into notes
+    Markdown,
+    /// one json object per line, printed as soon as it is found
+    Ndjson,
+}
+
+/// how poems look in text output
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct Style {
+    /// print pinyin above every line
+    #[clap(long, global = true)]
+    pub pinyin: bool,
+    /// print poems top to bottom, right to left
+    #[clap(long, global = true, conflicts_with = "pinyin")]
+    pub vertical: bool,
+    /// print the relevance score of every search result
+    #[clap(long, global = true)]
+    pub scores: bool,
+    /// print the translation below the poems that have one
+    #[clap(long, global = true)]
+    pub show_translation: bool,
+    /// print the translation and the appreciation below the poems that have them
+    #[clap(long, global = true)]
+    pub full: bool,
+    /// print the notes written with `note add` below the poems
+    #[clap(long, global = true)]
+    pub with_notes: bool,
+    /// the notes printed with --with-notes, loaded by the caller
+    #[clap(skip)]
+    pub notes: Notes,
+    /// print what is known about the author below the poems, see `authors info`
+    #[clap(long, global = true)]
+    pub with_author_bio: bool,
+    /// the authors printed with --with-author-bio, loaded by the caller
+    #[clap(skip)]
+    pub authors: Authors,
+    /// render poems as markdown, set by `--output markdown`
+    #[clap(skip)]
+    pub markdown: bool,
+}
+
+impl Style {
+    /// the layout poems are rendered with
+    pub fn layout(&self) -> &'static dyn Layout {
+        if self.markdown {
+            &Markdown
+        } else if self.vertical {
+            &Vertical
+        } else if self.pinyin {
+            &Pinyin
+        } else {
+            &Horizontal
+        }
+    }
+}
+
+/// things that can be printed as text in a given style
+pub trait Render {
+    fn render(&self, f: &mut fmt::Formatter<'_>, style: &Style) -> fmt::Result;
+}
+
+impl<T: Render + ?Sized> Render for &T {
+    fn render(&self, f: &mut fmt::Formatter<'_>, style: &Style) -> fmt::Result {
+        (**self).render(f, style)
+    }
+}
+
+/// displays the item in the style
+pub struct Styled<'a, T: ?Sized>(pub &'a T, pub &'a Style);
+
+impl<'a, T: Render + ?Sized> Display for Styled<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.render(f, self.1)
+    }
+}
+
+/// print `item` as one line of json and flush it, so a pipe gets it right away
+pub fn print_line<T: Serialize>(item: T) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer(&mut stdout, &item)?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// print all `items` to stdout in the given format
+pub fn print_all<T: Serialize + Render>(items: &[T], output: Output, style: &Style) -> Result<()> {
+    match output {
+        Output::Text => items.iter().for_each(|i| println!("{}", Styled(i, style))),
+        Output::Json => println!("{}", serde_json::to_string_pretty(items)?),
+        Output::Ndjson => items.iter().try_for_each(print_line)?,
+        Output::Markdown => {
+            // 颜色的转义序列在markdown里是乱码
+            colored::control::set_override(false);
+            let style = Style {
+                markdown: true,
+                ..style.clone()
+            };
+            items
+                .iter()
+                .for_each(|i| println!("{}\n", Styled(i, &style)));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use crate::Poem;
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::{
+    io::{BufRead, Write},
+    process::{Command, Stdio},
+};
+
+// 按顺序试 第一个能运行的就用它 只显示tab前面的部分
+const FINDERS: &[&[&str]] = &[
+    &["fzf", "--delimiter=\t", "--with-nth=1"],
+    &["sk", "--delimiter=\t", "--with-nth=1"],
+];
+
+/// how many matches the built in finder lists
+const SHOWN: usize = 10;
+
+/// the line of `poem` in the list to pick from
+pub fn entry(poem: &Poem) -> String {
+    format!("{} — {} ({})", poem.title, poem.author, poem.dynasty)
+}
+
+/// let the user pick one of `poems` by their entries with fzf or sk, or the built in finder if
+/// neither is installed or `builtin`, none if nothing is picked
+///
+/// the built in finder reads from `input` and lists the matches on `output`
+pub fn pick<'a>(
+    poems: &'a [Poem],
+    query: Option<&str>,
+    builtin: bool,
+    input: impl BufRead,
+    output: impl Write,
+) -> Result<Option<&'a Poem>> {
+    let entries: Vec<String> = poems.iter().map(entry).collect();
+    let picked = match builtin {
+        true => None,
+        false => external(&entries, query)?,
+    };
+    let picked = match picked {
+        Some(picked) => picked,
+        None => finder(&entries, query, input, output)?,
+    };
+    Ok(picked.map(|i| &poems[i]))
+}
+
+/// the entry picked with the first finder program found, the outer none if there is none
+fn external(entries: &[String], query: Option<&str>) -> Result<Option<Option<usize>>> {
+    for command in FINDERS {
+        let mut finder = Command::new(command[0]);
+        finder.args(&command[1..]);
+        if let Some(query) = query {
+            finder.arg(format!("--query={}", query));
+        }
+        // 界面画在终端上 只有选中的那行从stdout出来
+        let mut child = match finder.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        let list: String = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| format!("{}\t{}\n", e, i))
+            .collect();
+        // 没选就退出时管道会断 不算错
+        let _ = child.stdin.take().unwrap().write_all(list.as_bytes());
+        let output = child.wait_with_output()?;
+        // 1是没有匹配的 130是按了esc或者ctrl-c
+        return match output.status.code() {
+            Some(0) => Ok(Some(
+                String::from_utf8_lossy(&output.stdout)
+                    .trim_end()
+                    .rsplit('\t')
+                    .next()
+                    .and_then(|i| i.parse().ok()),
+            )),
+            Some(1 | 130) => Ok(Some(None)),
+            _ => Err(anyhow!("{} failed: {}", command[0], output.status)),
+        };
+    }
+    Ok(None)
+}
+
+/// the built in finder: every line read is a query whose best matches are listed numbered,
+/// a number picks one of them and an empty line gives up
+fn finder(
+    entries: &[String],
+    query: Option<&str>,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<Option<usize>> {
+    let mut query = query.map(str::to_string);
+    let mut shown = Vec::new();
+    loop {
+        if let Some(query) = query.take() {
+            shown = matches(entries, &query);
+            for (n, i) in shown.iter().enumerate() {
+                writeln!(
+                    output,
+                    "{} {}",
+                    format!("{:>2}.", n + 1).dimmed(),
+                    entries[*i]
+                )?;
+            }
+            if shown.is_empty() {
+                writeln!(output, "{}", "没有匹配的".dimmed())?;
+            }
+        }
+        write!(output, "pick> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim() {
+            "" => return Ok(None),
+            line => match line.parse::<usize>() {
+                Ok(n) if (1..=shown.len()).contains(&n) => return Ok(Some(shown[n - 1])),
+                _ => query = Some(line.to_string()),
+            },
+        }
+    }
+}
+
+/// the entries matching `query` the best first, at most `SHOWN` of them
+fn matches(entries: &[String], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| score(query, e).map(|s| (s, i)))
+        .collect();
+    scored.sort_by_key(|(s, i)| (-s, *i));
+    scored.into_iter().take(SHOWN).map(|(_, i)| i).collect()
+}
+
+/// how well `query` matches `text` when its characters are all in `text` in order,
+/// higher when they are close together and near the start, like fzf does
+fn score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut next = 0;
+    for (n, c) in query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .enumerate()
+    {
+        let at = next + text[next..].iter().position(|t| *t == c)?;
+        // 连着的字加分 跳过的字和开头前的字扣分
+        score += match n {
+            0 => 10 - at as i64,
+            _ if at == next => 15,
+            _ => 10 - (at - next) as i64,
+        };
+        next = at + 1;
+    }
+    Some(score)
+}
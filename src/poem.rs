@@ -0,0 +1,215 @@
+use crate::{
+    authors::Authors,
+    classify::classify,
+    index::{extract_field_text, fields},
+    layout::below,
+    output::{Render, Style},
+};
+use fnv::FnvHasher;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use std::{
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+};
+use tantivy::{
+    schema::{Facet, Value},
+    Document,
+};
+
+#[derive(Debug, Deserialize, Hash, PartialEq, Eq, Clone)]
+pub struct Poem {
+    pub title: String,
+    pub author: String,
+    pub dynasty: String,
+    pub content: String,
+    /// where the poem was imported from, see `import`
+    #[serde(default)]
+    pub source: Option<String>,
+    /// like 送别 or 边塞, the ones given with `tag add` are added when loading
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// the meaning in modern chinese or english, shown with `--show-translation`
+    #[serde(default)]
+    pub translation: Option<String>,
+    /// a long essay on the poem, shown with `--full`
+    #[serde(default)]
+    pub appreciation: Option<String>,
+}
+
+impl Poem {
+    /// a stable id derived from the poem itself, the same poem always gets the same id
+    pub fn id(&self) -> String {
+        format!("{:016x}", self.raw_id())
+    }
+
+    /// the id as it is stored in the index
+    pub(crate) fn raw_id(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+        self.title.hash(&mut hasher);
+        self.author.hash(&mut hasher);
+        self.content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// a hash of everything indexed of the poem, the years of its author in `authors` too,
+    /// a poem whose fingerprint is not the one in the index changed since it was indexed
+    pub(crate) fn fingerprint(&self, authors: &Authors) -> u64 {
+        let mut hasher = FnvHasher::default();
+        self.hash(&mut hasher);
+        authors
+            .active_years(&self.author, &self.dynasty)
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// how many characters the content has, punctuation and whitespace not counted
+    pub fn char_count(&self) -> usize {
+        self.content
+            .chars()
+            .filter(|&c| !is_line_break(c) && !c.is_whitespace())
+            .count()
+    }
+
+    /// the lines of the content without punctuation, in order
+    pub fn lines(&self) -> Vec<&str> {
+        self.content
+            .split(is_line_break)
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+}
+
+/// the punctuation lines of a poem end with
+pub fn is_line_break(c: char) -> bool {
+    matches!(
+        c,
+        '，' | '。' | '！' | '？' | '；' | '、' | ',' | '.' | '!' | '?' | ';' | '\n'
+    )
+}
+
+// id和体裁是算出来的 序列化时一起输出 反序列化时忽略
+impl Serialize for Poem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = 6
+            + self.source.is_some() as usize
+            + !self.tags.is_empty() as usize
+            + self.translation.is_some() as usize
+            + self.appreciation.is_some() as usize;
+        let mut s = serializer.serialize_struct("Poem", len)?;
+        s.serialize_field("id", &self.id())?;
+        s.serialize_field("title", &self.title)?;
+        s.serialize_field("author", &self.author)?;
+        s.serialize_field("dynasty", &self.dynasty)?;
+        s.serialize_field("content", &self.content)?;
+        s.serialize_field("form", &classify(self).name())?;
+        if let Some(source) = &self.source {
+            s.serialize_field("source", source)?;
+        }
+        if !self.tags.is_empty() {
+            s.serialize_field("tags", &self.tags)?;
+        }
+        if let Some(translation) = &self.translation {
+            s.serialize_field("translation", translation)?;
+        }
+        if let Some(appreciation) = &self.appreciation {
+            s.serialize_field("appreciation", appreciation)?;
+        }
+        s.end()
+    }
+}
+
+impl Display for Poem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, &Style::default())
+    }
+}
+
+impl Render for Poem {
+    fn render(&self, f: &mut fmt::Formatter<'_>, style: &Style) -> fmt::Result {
+        style.layout().render(self, &[], None, f)?;
+        below(self, style, f)
+    }
+}
+
+impl Poem {
+    /// the document of the poem in the index, with the years its author in `authors` lived
+    pub(crate) fn into_document(self, authors: &Authors) -> Document {
+        let fields = fields();
+        let mut doc = Document::new();
+        doc.add_u64(*fields.get("id").unwrap(), self.raw_id());
+        doc.add_u64(
+            *fields.get("fingerprint").unwrap(),
+            self.fingerprint(authors),
+        );
+        doc.add_u64(*fields.get("chars").unwrap(), self.char_count() as u64);
+        doc.add_u64(*fields.get("lines").unwrap(), self.lines().len() as u64);
+        doc.add_facet(
+            *fields.get("form").unwrap(),
+            Facet::from_path([classify(&self).name()]),
+        );
+        // 作者的生卒年在建索引时查好 按年份搜时不用再查
+        if let Some((from, to)) = authors.active_years(&self.author, &self.dynasty) {
+            doc.add_i64(*fields.get("active_from").unwrap(), from as i64);
+            doc.add_i64(*fields.get("active_to").unwrap(), to as i64);
+        }
+        doc.add_text(*fields.get("title").unwrap(), self.title);
+        doc.add_facet(
+            *fields.get("author_facet").unwrap(),
+            Facet::from_path([&self.author]),
+        );
+        doc.add_text(*fields.get("author").unwrap(), self.author);
+        doc.add_facet(
+            *fields.get("dynasty_facet").unwrap(),
+            Facet::from_path([&self.dynasty]),
+        );
+        doc.add_text(*fields.get("dynasty").unwrap(), self.dynasty);
+        doc.add_text(*fields.get("content").unwrap(), self.content);
+        for tag in &self.tags {
+            doc.add_facet(*fields.get("tags").unwrap(), Facet::from_path([tag]));
+        }
+        if let Some(source) = self.source {
+            doc.add_text(*fields.get("source").unwrap(), source);
+        }
+        if let Some(translation) = self.translation {
+            doc.add_text(*fields.get("translation").unwrap(), translation);
+        }
+        if let Some(appreciation) = self.appreciation {
+            doc.add_text(*fields.get("appreciation").unwrap(), appreciation);
+        }
+        doc
+    }
+}
+
+impl TryFrom<Document> for Poem {
+    type Error = anyhow::Error;
+
+    fn try_from(doc: Document) -> Result<Self, Self::Error> {
+        let fields = fields();
+        Ok(Self {
+            title: extract_field_text(&doc, fields["title"])?,
+            author: extract_field_text(&doc, fields["author"])?,
+            dynasty: extract_field_text(&doc, fields["dynasty"])?,
+            content: extract_field_text(&doc, fields["content"])?,
+            source: doc
+                .get_first(fields["source"])
+                .and_then(|v| v.text())
+                .map(str::to_string),
+            tags: doc
+                .get_all(fields["tags"])
+                .filter_map(|v| match v {
+                    Value::Facet(facet) => facet.to_path().first().map(|t| t.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            translation: doc
+                .get_first(fields["translation"])
+                .and_then(|v| v.text())
+                .map(str::to_string),
+            appreciation: doc
+                .get_first(fields["appreciation"])
+                .and_then(|v| v.text())
+                .map(str::to_string),
+        })
+    }
+}
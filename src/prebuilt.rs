@@ -0,0 +1,278 @@
+use crate::{
+    backup::{path_str, tar},
+    build_index, bundled_poems, fetch,
+    index::{replace_dir, schema},
+    lines, Authors, Indexed, Poem, TokenizerConfig,
+};
+use anyhow::{anyhow, Context, Result};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Component, Path},
+    process,
+};
+
+/// the file of a packed index saying what it was built for, it stays in the index unpacked
+/// from the archive until the index is rebuilt
+pub(crate) const MANIFEST_FILE: &str = "prebuilt.json";
+
+/// the layout of the archive, bumped when it changes so older versions refuse the new ones
+const FORMAT: u32 = 1;
+
+/// the archive built into the binary with the `prebuilt-index` feature, read from the file
+/// `POEM_PREBUILT_INDEX` names when it is built
+#[cfg(feature = "prebuilt-index")]
+const EMBEDDED: Option<&[u8]> = Some(include_bytes!(env!("POEM_PREBUILT_INDEX")));
+#[cfg(not(feature = "prebuilt-index"))]
+const EMBEDDED: Option<&[u8]> = None;
+
+/// the https url of the archive to download when none is built in, `POEM_PREBUILT_URL`
+/// when the binary is built
+const URL: Option<&str> = option_env!("POEM_PREBUILT_URL");
+
+/// what a packed index was built for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    /// the version of poem that packed it
+    pub version: String,
+    /// a checksum of the schemas of the poem and line index
+    pub schema: u32,
+    /// a checksum of the poems in the index
+    pub checksum: u32,
+    pub poems: usize,
+}
+
+impl Manifest {
+    fn new(poems: &[Poem]) -> Result<Self> {
+        Ok(Self {
+            format: FORMAT,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            schema: schema_checksum()?,
+            checksum: poems_checksum(poems)?,
+            poems: poems.len(),
+        })
+    }
+
+    /// why the index of the manifest can not be used with `poems` by this version, none if it can
+    fn mismatch(&self, poems: &[Poem]) -> Result<Option<String>> {
+        if self.format > FORMAT {
+            return Ok(Some(format!(
+                "it was packed by poem {} in a newer format",
+                self.version
+            )));
+        }
+        if self.schema != schema_checksum()? {
+            return Ok(Some(format!(
+                "it was built by poem {} with a different schema",
+                self.version
+            )));
+        }
+        if self.checksum != poems_checksum(poems)? {
+            return Ok(Some(
+                "it was built from other poems than the ones here".to_string(),
+            ));
+        }
+        Ok(None)
+    }
+}
+
+fn schema_checksum() -> Result<u32> {
+    let schemas = serde_json::to_string(&(schema(), lines::schema()))?;
+    Ok(crc32fast::hash(schemas.as_bytes()))
+}
+
+fn poems_checksum(poems: &[Poem]) -> Result<u32> {
+    Ok(crc32fast::hash(&serde_json::to_vec(poems)?))
+}
+
+/// index `poems` with the years of their authors in `authors` and write the index to the
+/// gzipped tar archive `out`, with a manifest checked when it is unpacked
+pub fn pack(
+    poems: Vec<Poem>,
+    authors: &Authors,
+    tokenizer: Option<TokenizerConfig>,
+    threads: Option<usize>,
+    out: &Path,
+    bar: &ProgressBar,
+) -> Result<Manifest> {
+    if poems.is_empty() {
+        return Err(anyhow!("there are no poems to pack"));
+    }
+    let manifest = Manifest::new(&poems)?;
+    let out = env::current_dir()?.join(out);
+    let dir = env::temp_dir().join(format!("poem-pack-{}", process::id()));
+    // 不管成败都把临时索引删掉
+    let result = build_index(&dir, poems, authors, true, tokenizer, threads, bar)
+        .and_then(|_| write_manifest(&dir.join(MANIFEST_FILE), &manifest))
+        .and_then(|_| {
+            tar(&[
+                "-czf",
+                path_str(&out)?,
+                "--exclude=.tantivy-*.lock",
+                "--exclude=writer.pid",
+                "-C",
+                path_str(&dir)?,
+                ".",
+            ])
+        });
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    result?;
+    Ok(manifest)
+}
+
+fn write_manifest(path: &Path, manifest: &Manifest) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(manifest)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// whether a prebuilt index was built into the binary or can be downloaded
+pub fn available() -> bool {
+    EMBEDDED.is_some() || URL.is_some()
+}
+
+/// whether the index in `path` was unpacked from an archive of an older version whose
+/// schema is not the current one, it is replaced by the prebuilt index of this version
+pub fn outdated(path: &Path) -> bool {
+    let manifest = path.join(MANIFEST_FILE);
+    if !manifest.exists() {
+        return false;
+    }
+    let schema = fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|s| serde_json::from_str::<Manifest>(&s).ok())
+        .map(|m| m.schema);
+    schema.is_none() || schema != schema_checksum().ok()
+}
+
+/// put the prebuilt index of the binary in `path` in place of the index there, see `unpack`
+pub fn install(path: &Path, poems: Vec<Poem>, authors: &Authors) -> Result<(Manifest, Indexed)> {
+    let bytes = match (EMBEDDED, URL) {
+        (Some(bytes), _) => bytes.to_vec(),
+        (None, Some(url)) => fetch::download(url)?,
+        (None, None) => return Err(anyhow!("no prebuilt index was built into poem")),
+    };
+    let archive = env::temp_dir().join(format!("poem-prebuilt-{}.tar.gz", process::id()));
+    fs::write(&archive, bytes).with_context(|| format!("failed to write {}", archive.display()))?;
+    let result = unpack_as(&archive, "the prebuilt index", path, poems, authors);
+    fs::remove_file(&archive)?;
+    result
+}
+
+/// put the index packed in `archive` in `path` in place of the index there if it was built
+/// from the bundled poems with the schema of this version
+///
+/// `poems` are indexed in it before, the ones of your own and the tags are not in the archive,
+/// with the years of their authors in `authors`
+pub fn unpack(
+    archive: &Path,
+    path: &Path,
+    poems: Vec<Poem>,
+    authors: &Authors,
+) -> Result<(Manifest, Indexed)> {
+    let archive = env::current_dir()?.join(archive);
+    unpack_as(
+        &archive,
+        &archive.display().to_string(),
+        path,
+        poems,
+        authors,
+    )
+}
+
+/// `unpack` with `name` for the archive in the errors
+fn unpack_as(
+    archive: &Path,
+    name: &str,
+    path: &Path,
+    poems: Vec<Poem>,
+    authors: &Authors,
+) -> Result<(Manifest, Indexed)> {
+    let listing = tar(&["-tzf", path_str(archive)?])?;
+    // 只能解到索引目录里面
+    for entry in String::from_utf8_lossy(&listing.stdout).lines() {
+        let inside = Path::new(entry)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            return Err(anyhow!("{} is not a packed index, it has {}", name, entry));
+        }
+    }
+    // 先解到旁边 检查过了再换进去 同一个文件系统上才能改名
+    let staging = path.with_file_name(format!(
+        ".{}.prebuilt",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    // 包是用没打标签的内置诗建的 自己的诗和标签换进去之前增量加上
+    let result = tar(&["-xzf", path_str(archive)?, "-C", path_str(&staging)?])
+        .and_then(|_| check(&staging, name, &bundled_poems()?))
+        .and_then(|manifest| {
+            let bar = ProgressBar::hidden();
+            let indexed = build_index(&staging, poems, authors, false, None, None, &bar)?;
+            replace_dir(&staging, path)?;
+            Ok((manifest, indexed))
+        });
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    result
+}
+
+fn check(dir: &Path, name: &str, poems: &[Poem]) -> Result<Manifest> {
+    let manifest = dir.join(MANIFEST_FILE);
+    if !manifest.exists() {
+        return Err(anyhow!("{} is not a packed index", name));
+    }
+    let manifest: Manifest = serde_json::from_str(&fs::read_to_string(&manifest)?)
+        .with_context(|| format!("failed to parse the manifest of {}", name))?;
+    match manifest.mismatch(poems)? {
+        Some(reason) => Err(anyhow!("{} can not be used, {}", name, reason)),
+        None => Ok(manifest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    fn poems() -> Vec<Poem> {
+        vec![poem("静夜思", "床前明月光，疑是地上霜。")]
+    }
+
+    #[test]
+    fn manifest_matches_the_poems_it_was_built_for() {
+        let poems = poems();
+        let manifest = Manifest::new(&poems).unwrap();
+        assert_eq!(manifest.mismatch(&poems).unwrap(), None);
+    }
+
+    #[test]
+    fn manifest_refuses_other_poems() {
+        let mut poems = poems();
+        let manifest = Manifest::new(&poems).unwrap();
+        poems[0].tags.push("思乡".to_string());
+        assert!(manifest.mismatch(&poems).unwrap().is_some());
+        assert!(manifest.mismatch(&[]).unwrap().is_some());
+    }
+
+    #[test]
+    fn manifest_refuses_other_schemas_and_newer_formats() {
+        let poems = poems();
+        let mut manifest = Manifest::new(&poems).unwrap();
+        manifest.schema = manifest.schema.wrapping_add(1);
+        let reason = manifest.mismatch(&poems).unwrap().unwrap();
+        assert!(reason.contains("schema"), "{}", reason);
+        let mut manifest = Manifest::new(&poems).unwrap();
+        manifest.format = FORMAT + 1;
+        let reason = manifest.mismatch(&poems).unwrap().unwrap();
+        assert!(reason.contains("newer format"), "{}", reason);
+    }
+}
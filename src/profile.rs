@@ -0,0 +1,39 @@
+use crate::error::{self, PoemError};
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{env, fs, path::PathBuf};
+
+/// the directory user data is kept in, `$XDG_CONFIG_HOME/poem` or `~/.config/poem`
+pub fn data_dir() -> Result<PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".config"))
+            .ok_or_else(|| anyhow!("can not find the home directory"))?,
+    };
+    Ok(config.join("poem"))
+}
+
+/// load `name` from the data dir, a missing file is the default value
+pub(crate) fn load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let path = data_dir()?.join(name);
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let s = error::read_to_string(&path)?;
+    serde_json::from_str(&s)
+        .map_err(PoemError::from)
+        .with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// save `value` as `name` in the data dir, the old file stays whole if the program stops midway
+pub(crate) fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(name);
+    // 先写到临时文件再改名 改名是原子的
+    let tmp = dir.join(format!(".{}.tmp", name));
+    fs::write(&tmp, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,533 @@
+use crate::{error::PoemError, index::fields, rhyme::rhyme_of, ShapeFilter};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::{collections::HashSet, iter, ops::Bound};
+use tantivy::{
+    query::{
+        AllQuery, BooleanQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, QueryParser, RangeQuery,
+        TermQuery,
+    },
+    schema::{Facet, Field, IndexRecordOption},
+    Index, Term,
+};
+
+/// how the keyword of `search` is interpreted
+#[derive(clap::Args, Debug, Clone)]
+pub struct SearchOptions {
+    /// only search in the given field
+    #[clap(long, possible_values = &["title", "author", "dynasty", "content", "translation", "appreciation"])]
+    pub field: Option<String>,
+    /// tolerate typos in the keyword, see `--distance`, only words of more than one character
+    /// can have typos so it needs an index built with a jieba tokenizer
+    #[clap(long)]
+    pub fuzzy: bool,
+    /// match the keyword as one contiguous phrase
+    #[clap(long, conflicts_with = "fuzzy")]
+    pub exact: bool,
+    /// the max edit distance of a fuzzy match
+    #[clap(long, default_value = "1")]
+    pub distance: u8,
+    /// the results must contain all of these
+    #[clap(long, multiple_occurrences = true)]
+    pub all_of: Vec<String>,
+    /// the results must contain at least one of these
+    #[clap(long, multiple_occurrences = true)]
+    pub any_of: Vec<String>,
+    /// the results must contain none of these
+    #[clap(long, multiple_occurrences = true)]
+    pub none_of: Vec<String>,
+    /// leave out the results scoring lower than this
+    #[clap(long)]
+    pub min_score: Option<f32>,
+    /// take the keyword as a regex over the whole content, like `明月.{0,2}照`
+    #[clap(long, conflicts_with_all = &["fuzzy", "exact", "field"])]
+    pub regex: bool,
+    /// search the keyword as plain text, characters like `:`, `-` and `"` are not query syntax
+    #[clap(long, conflicts_with_all = &["fuzzy", "exact", "regex"])]
+    pub literal: bool,
+    /// only poems rhyming with this character, lines ending with a rhyme of it for `lines`
+    #[clap(long)]
+    pub rhymes_with: Option<char>,
+    /// only poems by this author, the keyword is then only searched in the content
+    #[clap(long)]
+    pub author: Option<String>,
+    /// only poems of this dynasty, the keyword is then only searched in the content
+    #[clap(long)]
+    pub dynasty: Option<String>,
+    /// only poems with this tag, see `tag`
+    #[clap(long)]
+    pub tag: Option<String>,
+    /// only poems by authors still alive in this year, see `authors info`
+    #[clap(long)]
+    pub year_from: Option<i32>,
+    /// only poems by authors already born in this year
+    #[clap(long)]
+    pub year_to: Option<i32>,
+    #[clap(flatten)]
+    pub shape: ShapeFilter,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            field: None,
+            fuzzy: false,
+            exact: false,
+            distance: 1,
+            all_of: Vec::new(),
+            any_of: Vec::new(),
+            none_of: Vec::new(),
+            min_score: None,
+            regex: false,
+            literal: false,
+            rhymes_with: None,
+            author: None,
+            dynasty: None,
+            tag: None,
+            year_from: None,
+            year_to: None,
+            shape: ShapeFilter::default(),
+        }
+    }
+}
+
+impl SearchOptions {
+    /// whether a result with `score` is good enough
+    pub(crate) fn accepts(&self, score: f32) -> bool {
+        self.min_score.is_none_or(|min| score >= min)
+    }
+
+    /// whether `rhyme` is the one --rhymes-with asks for
+    pub(crate) fn rhymes(&self, rhyme: Option<&str>) -> bool {
+        self.rhymes_with
+            .is_none_or(|c| rhyme.is_some() && rhyme == rhyme_of(c))
+    }
+
+    /// whether some results can only be left out after reading the documents,
+    /// so the index can not count them
+    pub(crate) fn reads_documents(&self, keyword: &str) -> Result<bool> {
+        Ok(
+            self.min_score.is_some()
+                || self.rhymes_with.is_some()
+                || self.regex(keyword)?.is_some(),
+        )
+    }
+
+    /// the regex of `keyword` if it is searched as one
+    pub(crate) fn regex(&self, keyword: &str) -> Result<Option<Regex>> {
+        if !self.regex || keyword.trim().is_empty() {
+            return Ok(None);
+        }
+        Regex::new(keyword)
+            .map(Some)
+            .map_err(|e| syntax_error(keyword, e))
+    }
+
+    /// whether `author` and `dynasty` are the ones asked for
+    pub(crate) fn by(&self, author: &str, dynasty: &str) -> bool {
+        self.author.as_ref().is_none_or(|a| a == author)
+            && self.dynasty.as_ref().is_none_or(|d| d == dynasty)
+    }
+
+    fn has_metadata(&self) -> bool {
+        self.author.is_some()
+            || self.dynasty.is_some()
+            || self.tag.is_some()
+            || self.year_from.is_some()
+            || self.year_to.is_some()
+    }
+
+    /// the fields the keyword is searched in
+    pub(crate) fn fields(&self) -> Result<Vec<Field>> {
+        let fields = fields();
+        match &self.field {
+            Some(f) => Ok(vec![*fields
+                .get(&f[..])
+                .ok_or_else(|| anyhow!("unknown field `{}`", f))?]),
+            // 作者和朝代已经限定了 关键词只在正文里找
+            None if self.author.is_some() || self.dynasty.is_some() => Ok(vec![fields["content"]]),
+            // id 指纹 分面 长度 体裁和年份不是文本 来源只存不索引 都不参与关键词搜索
+            // 译文和赏析只在--field指定时搜 不然结果里全是白话
+            None => Ok(fields
+                .iter()
+                .filter(|(name, _)| {
+                    !matches!(
+                        **name,
+                        "id" | "fingerprint"
+                            | "dynasty_facet"
+                            | "author_facet"
+                            | "chars"
+                            | "lines"
+                            | "form"
+                            | "source"
+                            | "tags"
+                            | "active_from"
+                            | "active_to"
+                            | "translation"
+                            | "appreciation"
+                    )
+                })
+                .map(|(_, field)| *field)
+                .collect()),
+        }
+    }
+}
+
+/// build the query for `keyword` and the boolean options of `opts` over `fields`,
+/// an empty keyword only uses the boolean options
+pub(crate) fn build_query(
+    index: &Index,
+    fields: &[Field],
+    keyword: &str,
+    opts: &SearchOptions,
+) -> Result<Box<dyn Query>> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    if !keyword.trim().is_empty() {
+        // 正则可能跨好几个词 RegexQuery只能匹配单个词 所以取出文档以后再匹配全文
+        let query = if opts.regex {
+            Box::new(AllQuery)
+        } else {
+            keyword_query(index, fields, keyword, opts)?
+        };
+        clauses.push((Occur::Must, query));
+    }
+    for k in &opts.all_of {
+        clauses.push((Occur::Must, keyword_query(index, fields, k, opts)?));
+    }
+    // should和must同时出现时should只影响打分 所以any_of要单独组成一个must子句
+    if !opts.any_of.is_empty() {
+        let any = opts
+            .any_of
+            .iter()
+            .map(|k| Ok((Occur::Should, keyword_query(index, fields, k, opts)?)))
+            .collect::<Result<_>>()?;
+        clauses.push((Occur::Must, Box::new(BooleanQuery::new(any))));
+    }
+    if clauses.is_empty() && opts.none_of.is_empty() && !opts.has_metadata() {
+        return Err(anyhow!("nothing to search for"));
+    }
+    if clauses.is_empty() {
+        clauses.push((Occur::Must, Box::new(AllQuery)));
+    }
+    for k in &opts.none_of {
+        clauses.push((Occur::MustNot, keyword_query(index, fields, k, opts)?));
+    }
+
+    if clauses.len() == 1 {
+        return Ok(clauses.pop().unwrap().1);
+    }
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// the query of `keyword` and `opts` over the poem index
+pub(crate) fn poem_query(
+    index: &Index,
+    keyword: &str,
+    opts: &SearchOptions,
+) -> Result<Box<dyn Query>> {
+    let query = with_shape(
+        build_query(index, &opts.fields()?, keyword, opts)?,
+        &opts.shape,
+    );
+    Ok(with_metadata(query, opts))
+}
+
+/// `query` limited to the author, dynasty and tag of `opts` by term queries on their facets,
+/// and to the years of `opts` by range queries on the years the authors lived
+fn with_metadata(query: Box<dyn Query>, opts: &SearchOptions) -> Box<dyn Query> {
+    if !opts.has_metadata() {
+        return query;
+    }
+    let fields = fields();
+    let mut clauses = vec![(Occur::Must, query)];
+    for (field, value) in [
+        ("author_facet", &opts.author),
+        ("dynasty_facet", &opts.dynasty),
+        ("tags", &opts.tag),
+    ] {
+        if let Some(value) = value {
+            let term = Term::from_facet(fields[field], &Facet::from_path([value]));
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+    }
+    // 生平和年份范围有交集就算 生卒年不详的作者一律不算
+    if let Some(to) = opts.year_to {
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new_i64_bounds(
+                fields["active_from"],
+                Bound::Unbounded,
+                Bound::Included(to as i64),
+            )),
+        ));
+    }
+    if let Some(from) = opts.year_from {
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new_i64_bounds(
+                fields["active_to"],
+                Bound::Included(from as i64),
+                Bound::Unbounded,
+            )),
+        ));
+    }
+    Box::new(BooleanQuery::new(clauses))
+}
+
+/// `query` limited to the poems of the lengths and form in `shape`, using the numeric
+/// and facet fields of the poem index
+fn with_shape(query: Box<dyn Query>, shape: &ShapeFilter) -> Box<dyn Query> {
+    if shape.is_empty() {
+        return query;
+    }
+    let fields = fields();
+    let mut clauses = vec![(Occur::Must, query)];
+    if shape.min_chars.is_some() || shape.max_chars.is_some() {
+        let min = shape.min_chars.map_or(Bound::Unbounded, Bound::Included);
+        let max = shape.max_chars.map_or(Bound::Unbounded, Bound::Included);
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new_u64_bounds(fields["chars"], min, max)),
+        ));
+    }
+    if let Some(lines) = shape.lines {
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new_u64(fields["lines"], lines..lines + 1)),
+        ));
+    }
+    if let Some(form) = shape.form {
+        let term = Term::from_facet(fields["form"], &Facet::from_path([form.name()]));
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+    Box::new(BooleanQuery::new(clauses))
+}
+
+/// the error of searching for `query` that `e` says is not a valid regex
+pub(crate) fn syntax_error(query: &str, e: regex::Error) -> anyhow::Error {
+    PoemError::QuerySyntax {
+        query: query.to_string(),
+        reason: e.to_string(),
+    }
+    .into()
+}
+
+/// what the query parser makes of the keywords of `opts` that may not be what was meant,
+/// one message for each
+pub(crate) fn syntax_warnings(
+    index: &Index,
+    fields: &[Field],
+    keyword: &str,
+    opts: &SearchOptions,
+) -> Vec<String> {
+    // 模糊 短语 正则和字面搜索都不经过查询解析
+    if opts.fuzzy || opts.exact || opts.regex || opts.literal {
+        return Vec::new();
+    }
+    let parser = QueryParser::for_index(index, fields.to_vec());
+    let keywords = iter::once(keyword)
+        .chain(opts.all_of.iter().map(String::as_str))
+        .chain(opts.any_of.iter().map(String::as_str))
+        .chain(opts.none_of.iter().map(String::as_str))
+        .filter(|k| !k.trim().is_empty());
+    let mut warnings = Vec::new();
+    for k in keywords {
+        if let Err(e) = parser.parse_query(k) {
+            warnings.push(format!(
+                "`{}` is not a valid query ({}), searched as plain text",
+                k, e
+            ));
+            continue;
+        }
+        let words: Vec<&str> = k.split_whitespace().collect();
+        let mut syntax = Vec::new();
+        if k.contains(':') {
+            syntax.push("`:` makes the part before it a field name");
+        }
+        if words.iter().any(|w| w.starts_with('-')) {
+            syntax.push("`-` before a word leaves out the poems with it");
+        }
+        if words.iter().any(|w| w.starts_with('+')) {
+            syntax.push("`+` before a word requires it");
+        }
+        if k.contains('"') {
+            syntax.push("quotes make a phrase");
+        }
+        if k.contains(['(', ')']) {
+            syntax.push("parentheses group words");
+        }
+        if k.contains(['[', ']', '{', '}']) {
+            syntax.push("brackets make a range");
+        }
+        if k.contains('^') {
+            syntax.push("`^` boosts the word before it");
+        }
+        if words.iter().any(|w| matches!(*w, "AND" | "OR")) {
+            syntax.push("AND and OR combine the words around them");
+        }
+        if words.contains(&"*") {
+            syntax.push("`*` matches every poem");
+        }
+        warnings.extend(syntax.into_iter().map(|m| {
+            format!(
+                "in `{}`, {}, use --literal to search it as plain text",
+                k, m
+            )
+        }));
+    }
+    warnings
+}
+
+/// the query for a single keyword
+fn keyword_query(
+    index: &Index,
+    fields: &[Field],
+    keyword: &str,
+    opts: &SearchOptions,
+) -> Result<Box<dyn Query>> {
+    if opts.fuzzy {
+        fuzzy_query(index, keyword, fields, opts.distance)
+    } else if opts.exact {
+        phrase_query(index, keyword, fields)
+    } else if opts.literal {
+        literal_query(index, keyword, fields)
+    } else {
+        // 解析不了的当作普通文字 syntax_warnings会告诉用户
+        QueryParser::for_index(index, fields.to_vec())
+            .parse_query(keyword)
+            .or_else(|_| literal_query(index, keyword, fields))
+    }
+}
+
+/// any of the whitespace separated words of `keyword` as a phrase, like the query parser
+/// does with words without syntax
+fn literal_query(index: &Index, keyword: &str, fields: &[Field]) -> Result<Box<dyn Query>> {
+    let mut words = keyword
+        .split_whitespace()
+        .map(|w| phrase_query(index, w, fields))
+        .collect::<Result<Vec<_>>>()?;
+    if words.len() == 1 {
+        return Ok(words.pop().unwrap());
+    }
+    Ok(Box::new(BooleanQuery::new(
+        words.into_iter().map(|q| (Occur::Should, q)).collect(),
+    )))
+}
+
+/// all tokens of `keyword` next to each other in the same order in any of `fields`,
+/// whitespace in the keyword is ignored
+fn phrase_query(index: &Index, keyword: &str, fields: &[Field]) -> Result<Box<dyn Query>> {
+    let mut subqueries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    for &field in fields {
+        let mut terms = Vec::new();
+        let mut skipped = 0;
+        let mut tokens = index.tokenizer_for_field(field)?.token_stream(keyword);
+        while let Some(token) = tokens.next() {
+            // 按词搜索时短词和长词可能在同一个位置 所以用分词给的位置
+            if token.text.trim().is_empty() {
+                skipped += 1;
+            } else {
+                let term = Term::from_field_text(field, &token.text);
+                terms.push((token.position - skipped, term));
+            }
+        }
+        let query: Box<dyn Query> = match terms.len() {
+            0 => continue,
+            1 => Box::new(TermQuery::new(
+                terms.pop().unwrap().1,
+                IndexRecordOption::WithFreqs,
+            )),
+            _ => Box::new(PhraseQuery::new_with_offset(terms)),
+        };
+        subqueries.push((Occur::Should, query));
+    }
+    Ok(Box::new(BooleanQuery::new(subqueries)))
+}
+
+/// any token of `keyword` within `distance` edits in any of `fields`, or any of its whitespace
+/// separated words as a whole, since a typo can split a word into characters matched exactly
+fn fuzzy_query(
+    index: &Index,
+    keyword: &str,
+    fields: &[Field],
+    distance: u8,
+) -> Result<Box<dyn Query>> {
+    let mut subqueries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    let mut fuzzy = |term: Term, distance: u8| {
+        subqueries.push((
+            Occur::Should,
+            Box::new(FuzzyTermQuery::new(term, distance, true)),
+        ))
+    };
+    for &field in fields {
+        let mut texts = HashSet::new();
+        let mut tokens = index.tokenizer_for_field(field)?.token_stream(keyword);
+        while let Some(token) = tokens.next() {
+            // 编辑距离不小于词长时任何词都能匹配上 单字的词只能精确匹配
+            let len = token.text.chars().count();
+            let distance = distance.min(len.saturating_sub(1) as u8);
+            texts.insert(token.text.clone());
+            fuzzy(Term::from_field_text(field, &token.text), distance);
+        }
+        // 打错的字会让jieba把词拆成单字 整个词再和索引里的词比一次
+        for word in keyword.split_whitespace() {
+            let word = word.to_lowercase();
+            let len = word.chars().count();
+            if len < 2 || texts.contains(&word) {
+                continue;
+            }
+            let distance = distance.min((len - 1) as u8);
+            fuzzy(Term::from_field_text(field, &word), distance);
+        }
+    }
+    Ok(Box::new(BooleanQuery::new(subqueries)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        index::{fields, index_in_ram},
+        testing::poem,
+        Authors, Tokenizer, TokenizerConfig,
+    };
+    use tantivy::collector::Count;
+
+    fn count(tokenizer: Tokenizer, keyword: &str) -> usize {
+        let poems = [poem(
+            "静夜思",
+            "床前明月光，疑是地上霜。举头望明月，低头思故乡。",
+        )];
+        let config = TokenizerConfig {
+            tokenizer,
+            user_dict: None,
+        };
+        let index = index_in_ram(&poems, &Authors::default(), &config).unwrap();
+        let query = fuzzy_query(&index, keyword, &[fields()["content"]], 1).unwrap();
+        index
+            .reader()
+            .unwrap()
+            .searcher()
+            .search(&query, &Count)
+            .unwrap()
+    }
+
+    #[test]
+    fn fuzzy_finds_a_typo_splitting_a_word() {
+        assert_eq!(count(Tokenizer::Jieba, "故香"), 1);
+        assert_eq!(count(Tokenizer::Jieba, "故乡"), 1);
+    }
+
+    #[test]
+    fn fuzzy_matches_single_characters_exactly() {
+        assert_eq!(count(Tokenizer::Unicode, "香"), 0);
+        assert_eq!(count(Tokenizer::Unicode, "乡"), 1);
+        assert_eq!(count(Tokenizer::Jieba, "香"), 0);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{poem::is_line_break, Poem};
+use anyhow::Result;
+use colored::*;
+use rand::{seq::index::sample, Rng};
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+};
+
+/// a poem with some parts blanked out
+#[derive(Debug)]
+pub struct Cloze {
+    /// the content with blanks replaced by `＿`
+    pub masked: String,
+    /// the blanked out parts in order
+    pub answers: Vec<String>,
+}
+
+/// blank out `blanks` random lines of `poem`, or single characters if `chars` is set
+pub fn cloze(poem: &Poem, blanks: usize, chars: bool, rng: &mut impl Rng) -> Cloze {
+    let total = if chars {
+        poem.content
+            .chars()
+            .filter(|c| !is_line_break(*c) && !c.is_whitespace())
+            .count()
+    } else {
+        poem.lines().len()
+    };
+    let hidden: HashSet<usize> = sample(rng, total, blanks.min(total)).into_iter().collect();
+
+    let mut masked = String::with_capacity(poem.content.len());
+    let mut answers: Vec<String> = Vec::new();
+    // 当前字或句在全诗中的序号 以及它是否刚刚开始
+    let mut unit = 0;
+    let mut fresh = true;
+    for c in poem.content.chars() {
+        if is_line_break(c) || c.is_whitespace() {
+            masked.push(c);
+            if !chars && !fresh {
+                unit += 1;
+                fresh = true;
+            }
+            continue;
+        }
+        if hidden.contains(&unit) {
+            masked.push('＿');
+            if fresh || chars {
+                answers.push(String::new());
+            }
+            answers.last_mut().unwrap().push(c);
+        } else {
+            masked.push(c);
+        }
+        if chars {
+            unit += 1;
+        } else {
+            fresh = false;
+        }
+    }
+
+    Cloze { masked, answers }
+}
+
+/// ask for every blank of `cloze` on `output` and read the answers from `input`,
+/// returns the number of right answers
+pub fn run(
+    poem: &Poem,
+    cloze: &Cloze,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<usize> {
+    writeln!(output, "\t{}", poem.title.bright_cyan())?;
+    writeln!(
+        output,
+        "\t{}〔{}〕",
+        poem.author.cyan(),
+        poem.dynasty.cyan()
+    )?;
+    writeln!(output, "{}\n", cloze.masked.cyan())?;
+
+    let mut right = 0;
+    for (i, answer) in cloze.answers.iter().enumerate() {
+        write!(output, "第{}空：", i + 1)?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let guess: String = line
+            .chars()
+            .filter(|c| !is_line_break(*c) && !c.is_whitespace())
+            .collect();
+        if guess == *answer {
+            right += 1;
+            writeln!(output, "{}", "正确".green())?;
+        } else {
+            writeln!(output, "{} {}", "错误 答案是".red(), answer)?;
+        }
+    }
+    writeln!(output, "\n得分：{}/{}", right, cloze.answers.len())?;
+
+    Ok(right)
+}
+
+/// show a random line of `poems` and ask for the line after it until the input ends,
+/// an empty answer or `rounds` questions, returns the longest streak of right answers
+///
+/// any line following the shown one in some poem is right
+pub fn next_line(
+    poems: &[&Poem],
+    rounds: Option<usize>,
+    rng: &mut impl Rng,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<usize> {
+    // 每一对相邻的句子
+    let pairs: Vec<(&str, &str, &Poem)> = poems
+        .iter()
+        .flat_map(|&p| {
+            let lines = p.lines();
+            (1..lines.len())
+                .map(|i| (lines[i - 1], lines[i], p))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if pairs.is_empty() {
+        writeln!(output, "no poem with more than one line")?;
+        return Ok(0);
+    }
+
+    let (mut streak, mut best, mut asked) = (0, 0, 0);
+    while rounds.is_none_or(|r| asked < r) {
+        let (line, next, poem) = pairs[rng.gen_range(0..pairs.len())];
+        writeln!(
+            output,
+            "{} {}",
+            line.cyan(),
+            format!("——{}《{}》", poem.author, poem.title).dimmed()
+        )?;
+        write!(output, "下一句：")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            break;
+        }
+        let guess: String = answer
+            .chars()
+            .filter(|c| !is_line_break(*c) && !c.is_whitespace())
+            .collect();
+        if guess.is_empty() {
+            break;
+        }
+        asked += 1;
+        if pairs.iter().any(|(l, n, _)| *l == line && *n == guess) {
+            streak += 1;
+            best = best.max(streak);
+            writeln!(output, "{} 连对{}句\n", "正确".green(), streak)?;
+        } else {
+            streak = 0;
+            writeln!(output, "{} {}\n", "错误 答案是".red(), next)?;
+        }
+    }
+    writeln!(output, "最长连对：{}", best)?;
+
+    Ok(best)
+}
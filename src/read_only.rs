@@ -0,0 +1,86 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use tantivy::directory::{
+    error::{DeleteError, LockError, OpenDirectoryError, OpenReadError, OpenWriteError},
+    Directory, DirectoryLock, FileHandle, Lock, MmapDirectory, WatchCallback, WatchHandle,
+    WritePtr,
+};
+
+/// a directory an index is only read from, nothing in it is created, changed or deleted
+/// so it can be on a read-only file system or a shared mount
+///
+/// tantivy locks the index while it opens the segments so a process writing it does not delete
+/// them meanwhile, the lock is taken if it can be and skipped if the lock file can not be written
+#[derive(Debug, Clone)]
+pub(crate) struct ReadOnlyDirectory {
+    inner: MmapDirectory,
+    path: PathBuf,
+}
+
+impl ReadOnlyDirectory {
+    pub(crate) fn open(path: &Path) -> Result<Self, OpenDirectoryError> {
+        Ok(Self {
+            inner: MmapDirectory::open(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+fn read_only(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{} is opened read-only", path.display()),
+    )
+}
+
+impl Directory for ReadOnlyDirectory {
+    fn get_file_handle(&self, path: &Path) -> Result<Box<dyn FileHandle>, OpenReadError> {
+        self.inner.get_file_handle(path)
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), DeleteError> {
+        Err(DeleteError::IoError {
+            io_error: read_only(path),
+            filepath: path.to_path_buf(),
+        })
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
+        self.inner.exists(path)
+    }
+
+    fn open_write(&self, path: &Path) -> Result<WritePtr, OpenWriteError> {
+        Err(OpenWriteError::IoError {
+            io_error: read_only(path),
+            filepath: path.to_path_buf(),
+        })
+    }
+
+    fn atomic_read(&self, path: &Path) -> Result<Vec<u8>, OpenReadError> {
+        self.inner.atomic_read(path)
+    }
+
+    fn atomic_write(&self, path: &Path, _: &[u8]) -> io::Result<()> {
+        Err(read_only(path))
+    }
+
+    fn acquire_lock(&self, lock: &Lock) -> Result<DirectoryLock, LockError> {
+        // 写的锁拿不到 读的时候的锁文件写不了就不锁
+        if !lock.is_blocking {
+            return Err(LockError::IoError(read_only(&lock.filepath)));
+        }
+        if self.path.join(&lock.filepath).exists() {
+            match self.inner.acquire_lock(lock) {
+                Err(LockError::IoError(_)) => {}
+                locked => return locked,
+            }
+        }
+        Ok(DirectoryLock::from(Box::new(())))
+    }
+
+    fn watch(&self, watch_callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        self.inner.watch(watch_callback)
+    }
+}
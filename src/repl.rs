@@ -0,0 +1,170 @@
+use crate::{
+    output::{Style, Styled},
+    Favorites, Hit, PoemRepository, SearchOptions,
+};
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::io::{BufRead, Write};
+
+/// the fields `:field` can limit the search to, like --field
+const FIELDS: &[&str] = &[
+    "title",
+    "author",
+    "dynasty",
+    "content",
+    "translation",
+    "appreciation",
+];
+
+const HELP: &str = "输入关键词搜索，或者：
+  :limit 5        最多列出几首
+  :field title    只搜标题，不跟字段就搜全部
+  :fuzzy          容错搜索，再输一次关闭
+  :exact          整个短语搜索，再输一次关闭
+  :show 3         显示第3首的全文
+  :fav 3          收藏第3首
+  :help           显示这些命令
+  :quit           退出";
+
+/// what the results of a search look like in the list
+const SNIPPET_BYTES: usize = 60;
+
+/// search `repo` for every line read from `input` with `opts` until the input ends or `:quit`,
+/// the results are numbered so the colon commands can pick one of them
+///
+/// the index is opened once for all the searches, only the first one waits for it
+pub fn run(
+    repo: &PoemRepository,
+    mut opts: SearchOptions,
+    mut limit: usize,
+    style: &Style,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<()> {
+    writeln!(output, "{}", "输入关键词搜索，:help 查看命令".dimmed())?;
+    let mut hits: Vec<Hit> = Vec::new();
+    loop {
+        write!(output, "poem> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (command, arg) = match line.strip_prefix(':') {
+            Some(command) => match command.split_once(char::is_whitespace) {
+                Some((command, arg)) => (Some(command), arg.trim()),
+                None => (Some(command), ""),
+            },
+            None => (None, line),
+        };
+        // 命令出错只提示 不退出
+        let done = match command {
+            None => search(repo, arg, &opts, limit, &mut hits, &mut output),
+            Some("q" | "quit" | "exit") => break,
+            Some("h" | "help") => writeln!(output, "{}", HELP).map_err(Into::into),
+            Some("limit") => arg
+                .parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(|n| limit = n)
+                .ok_or_else(|| anyhow!("the limit has to be a number above 0")),
+            Some("field") => match arg {
+                "" | "all" => {
+                    opts.field = None;
+                    Ok(())
+                }
+                field if FIELDS.contains(&field) => {
+                    opts.field = Some(field.to_string());
+                    Ok(())
+                }
+                field => Err(anyhow!(
+                    "no field {}, it is one of {}",
+                    field,
+                    FIELDS.join(", ")
+                )),
+            },
+            Some("fuzzy") => {
+                opts.fuzzy = !opts.fuzzy;
+                opts.exact &= !opts.fuzzy;
+                writeln!(output, "fuzzy {}", on_off(opts.fuzzy)).map_err(Into::into)
+            }
+            Some("exact") => {
+                opts.exact = !opts.exact;
+                opts.fuzzy &= !opts.exact;
+                writeln!(output, "exact {}", on_off(opts.exact)).map_err(Into::into)
+            }
+            Some("show") => picked(&hits, arg).and_then(|hit| {
+                let full = repo.get(&hit.poem.id()).unwrap_or(&hit.poem);
+                writeln!(output, "{}", Styled(full, style)).map_err(Into::into)
+            }),
+            Some("fav") => picked(&hits, arg).and_then(|hit| {
+                let mut favorites = Favorites::load()?;
+                if favorites.add(&hit.poem.id()) {
+                    favorites.save()?;
+                }
+                writeln!(output, "收藏了《{}》", hit.poem.title).map_err(Into::into)
+            }),
+            Some(command) => Err(anyhow!("no command :{}, see :help", command)),
+        };
+        if let Err(e) = done {
+            writeln!(output, "{}", format!("{:#}", e).red())?;
+        }
+    }
+    Ok(())
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// search for `keyword` and list the first `limit` results numbered from 1, kept in `hits`
+fn search(
+    repo: &PoemRepository,
+    keyword: &str,
+    opts: &SearchOptions,
+    limit: usize,
+    hits: &mut Vec<Hit>,
+    output: &mut impl Write,
+) -> Result<()> {
+    let mut found = repo.search_snippets(keyword, opts, SNIPPET_BYTES)?;
+    let total = found.len();
+    found.truncate(limit);
+    *hits = found;
+    let style = Style::default();
+    for (i, hit) in hits.iter().enumerate() {
+        write!(
+            output,
+            "{} {}",
+            format!("{:>2}.", i + 1).dimmed(),
+            Styled(hit, &style)
+        )?;
+    }
+    if total > hits.len() {
+        writeln!(
+            output,
+            "{}",
+            format!("还有{}首，:limit 改变列出的数量", total - hits.len()).dimmed()
+        )?;
+    } else if total == 0 {
+        writeln!(output, "{}", "没有找到".dimmed())?;
+    }
+    Ok(())
+}
+
+/// the result numbered `arg` of the last search
+fn picked<'a>(hits: &'a [Hit], arg: &str) -> Result<&'a Hit> {
+    arg.parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| hits.get(i))
+        .ok_or_else(|| anyhow!("no result {}, give a number of the last search", arg))
+}
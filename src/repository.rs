@@ -0,0 +1,903 @@
+use crate::{
+    annotate::sort_key,
+    error::PoemError,
+    explain::{readable, Explanation},
+    index::{
+        fields, index_in_ram, open_or_create_index, schema, tokenizer_config, Access, Tokenizer,
+        TokenizerConfig,
+    },
+    layout::{below, header, highlighted, markdown_header, markdown_quote},
+    lines,
+    output::{Render, Style},
+    poem::is_line_break,
+    query::{build_query, poem_query, syntax_warnings},
+    rhyme::{rhyme_of, rhyme_of_poem},
+    timings, words_count, Authors, Line, LineHit, Poem, PoemFilter, PoemStore, SearchOptions, Tags,
+    UserPoems,
+};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use colored::*;
+use fnv::FnvHasher;
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tantivy::{
+    collector::{Count, FacetCollector, TopDocs},
+    query::{AllQuery, BooleanQuery, BoostQuery, Occur, Query, TermQuery},
+    schema::{Field, IndexRecordOption},
+    IndexReader, LeasedItem, Searcher, SnippetGenerator, Term,
+};
+
+/// how many of the most distinctive terms of a poem `similar` looks for in the others
+const SIMILAR_TERMS: usize = 25;
+
+/// the bundled poems, or the ones installed with `fetch` in their place
+pub fn bundled_poems() -> Result<Vec<Poem>> {
+    Ok(PoemStore::global().bundled()?.to_vec())
+}
+
+/// poems from a json file in the same format as the bundled `poems.json`, or a yaml or toml
+/// file by the extension, or the bundled poems plus the ones added by the user if no file is given,
+/// with the tags given by the user
+pub fn load_poems(path: Option<&Path>) -> Result<Vec<Poem>> {
+    let mut poems = match path {
+        Some(path) => PoemStore::global().file(path)?.to_vec(),
+        None => {
+            let mut poems = bundled_poems()?;
+            poems.extend(UserPoems::load()?.into_poems());
+            poems
+        }
+    };
+    Tags::load()?.apply(&mut poems);
+    Ok(poems)
+}
+
+/// the poem of `load_poems` with `id`, looked up by the storage in the poems of the user,
+/// none if there is no such poem
+pub fn load_poem(path: Option<&Path>, id: &str) -> Result<Option<Poem>> {
+    let poem = match path {
+        Some(path) => PoemStore::global()
+            .file(path)?
+            .iter()
+            .find(|p| p.id() == id)
+            .cloned(),
+        None => match PoemStore::global().bundled()?.iter().find(|p| p.id() == id) {
+            Some(poem) => Some(poem.clone()),
+            None => UserPoems::find(id)?,
+        },
+    };
+    let mut poems: Vec<Poem> = poem.into_iter().collect();
+    Tags::load()?.apply(&mut poems);
+    Ok(poems.pop())
+}
+
+/// the poems of `load_poems` passing `filter`, the poems of the user filtered by the storage
+pub fn load_poems_matching(path: Option<&Path>, filter: &PoemFilter) -> Result<Vec<Poem>> {
+    let mut poems = match path {
+        Some(path) => PoemStore::global().file(path)?.to_vec(),
+        None => {
+            let mut poems = bundled_poems()?;
+            poems.retain(|p| filter.matches_columns(p));
+            poems.extend(UserPoems::matching(filter)?);
+            poems
+        }
+    };
+    // 标签是另外存的 加上以后才能按标签筛
+    Tags::load()?.apply(&mut poems);
+    poems.retain(|p| filter.matches(p));
+    Ok(poems)
+}
+
+/// search every repository of `sources` by name for `keyword` and merge the hits, the scores
+/// divided by the best one of their source so they can be compared
+///
+/// a poem found in several sources is only kept with the best score
+pub fn federated_search(
+    sources: &[(String, PoemRepository)],
+    keyword: &str,
+    opts: &SearchOptions,
+) -> Result<Vec<Hit>> {
+    let mut hits = Vec::new();
+    for (name, repo) in sources {
+        let found = repo.search(keyword, opts)?;
+        let best = found.iter().map(|h| h.score).fold(0.0, f32::max);
+        hits.extend(found.into_iter().map(|h| Hit {
+            score: if best > 0.0 { h.score / best } else { 0.0 },
+            source: Some(name.clone()),
+            ..h
+        }));
+    }
+    // 稳定排序 分数一样时按来源的顺序
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut seen = HashSet::new();
+    hits.retain(|h| seen.insert(h.poem.id()));
+    Ok(hits)
+}
+
+/// a poem found by `search` with its relevance score
+#[derive(Debug, Serialize, Clone)]
+pub struct Hit {
+    pub score: f32,
+    #[serde(flatten)]
+    pub poem: Poem,
+    /// byte ranges of the matched terms in `poem.content`
+    #[serde(skip)]
+    pub highlights: Vec<Range<usize>>,
+    /// the part of the content around the matched terms, only set by `search_snippets`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<Snippet>,
+    /// how the score is made up, only set by `search_explained`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+    /// the collection or index the poem is found in, only set by `federated_search`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// the order of search results
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// the best match first
+    Relevance,
+    /// by the pinyin of the title
+    Title,
+    /// by the pinyin of the author
+    Author,
+    /// by the pinyin of the dynasty
+    Dynasty,
+    /// the shortest first
+    Length,
+}
+
+impl SortBy {
+    /// sort `hits`, which are in the order of relevance, hits equal in this order keep it
+    pub fn sort(self, hits: &mut [Hit]) {
+        match self {
+            SortBy::Relevance => {}
+            SortBy::Title => hits.sort_by_cached_key(|h| sort_key(&h.poem.title)),
+            SortBy::Author => hits.sort_by_cached_key(|h| sort_key(&h.poem.author)),
+            SortBy::Dynasty => hits.sort_by_cached_key(|h| sort_key(&h.poem.dynasty)),
+            SortBy::Length => hits.sort_by_cached_key(|h| h.poem.content.chars().count()),
+        }
+    }
+}
+
+/// the order of listed poems
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOrder {
+    /// the order of the poems file
+    Dataset,
+    /// by the pinyin of the title
+    Title,
+    Id,
+}
+
+/// an excerpt of the content of a poem
+#[derive(Debug, Serialize, Clone)]
+#[serde(transparent)]
+pub struct Snippet {
+    pub text: String,
+    /// byte ranges of the matched terms in `text`
+    #[serde(skip)]
+    pub highlights: Vec<Range<usize>>,
+    /// whether some content comes before and after `text`
+    #[serde(skip)]
+    pub truncated: (bool, bool),
+}
+
+impl Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, &Style::default())
+    }
+}
+
+impl Render for Hit {
+    fn render(&self, f: &mut fmt::Formatter<'_>, style: &Style) -> fmt::Result {
+        if let Some(source) = &self.source {
+            writeln!(f, "{}", format!("〈{}〉", source).dimmed())?;
+        }
+        // 片段只有一行 不管layout
+        let score = style.scores.then_some(self.score);
+        match &self.snippet {
+            Some(snippet) if style.markdown => {
+                markdown_header(&self.poem, score, f)?;
+                let text = format!(
+                    "{}{}{}",
+                    if snippet.truncated.0 { "…" } else { "" },
+                    snippet.text,
+                    if snippet.truncated.1 { "…" } else { "" }
+                );
+                // 前面的省略号占了3个字节
+                let shift = if snippet.truncated.0 {
+                    '…'.len_utf8()
+                } else {
+                    0
+                };
+                let highlights: Vec<_> = snippet
+                    .highlights
+                    .iter()
+                    .map(|r| r.start + shift..r.end + shift)
+                    .collect();
+                markdown_quote(&text, &highlights, f)
+            }
+            Some(snippet) => {
+                header(&self.poem, score, f)?;
+                if snippet.truncated.0 {
+                    write!(f, "…")?;
+                }
+                highlighted(&snippet.text, &snippet.highlights, f)?;
+                if snippet.truncated.1 {
+                    write!(f, "…")?;
+                }
+                writeln!(f)
+            }
+            None => style
+                .layout()
+                .render(&self.poem, &self.highlights, score, f),
+        }?;
+        below(&self.poem, style, f)?;
+        match &self.explanation {
+            Some(explanation) => write!(f, "{}", explanation),
+            None => Ok(()),
+        }
+    }
+}
+
+/// byte ranges of the matches of `regex` in `text`, none if there is no match
+fn regex_matches(regex: &Regex, text: &str) -> Option<Vec<Range<usize>>> {
+    let matches: Vec<Range<usize>> = regex
+        .find_iter(text)
+        .map(|m| m.range())
+        .filter(|r| !r.is_empty())
+        .collect();
+    (!matches.is_empty()).then_some(matches)
+}
+
+/// the poem stored in the index with the hex `id`, none if it is not there
+fn indexed_poem(searcher: &Searcher, field: Field, id: &str) -> Result<Option<Poem>> {
+    let raw_id = match u64::from_str_radix(id, 16) {
+        Ok(raw_id) => raw_id,
+        Err(_) => return Ok(None),
+    };
+    let query = TermQuery::new(
+        Term::from_field_u64(field, raw_id),
+        IndexRecordOption::Basic,
+    );
+    match searcher.search(&query, &TopDocs::with_limit(1))?.first() {
+        Some((_, doc_address)) => Ok(Some(Poem::try_from(searcher.doc(*doc_address)?)?)),
+        None => Ok(None),
+    }
+}
+
+fn snippet(generator: &SnippetGenerator, content: &str) -> Snippet {
+    let snippet = generator.snippet(content);
+    // 只有标题或作者匹配时content里没有片段 用第一句代替
+    let fragment = match snippet.fragments() {
+        "" => content.split_inclusive(is_line_break).next().unwrap_or(""),
+        fragment => fragment,
+    };
+    // 片段里没有位置信息 只能回到content里找
+    let start = content.find(fragment).unwrap_or(0);
+    Snippet {
+        text: fragment.replace('\n', " "),
+        highlights: snippet.highlighted().to_vec(),
+        truncated: (start > 0, start + fragment.len() < content.len()),
+    }
+}
+
+/// poems plus the index built over them
+///
+/// the index is optional, only `search` needs it
+pub struct PoemRepository {
+    index_path: PathBuf,
+    reader: Option<IndexReader>,
+    tokenizer: TokenizerConfig,
+    poems: Vec<Poem>,
+}
+
+impl PoemRepository {
+    /// open the repository with the index stored in `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let index_path = path.as_ref().to_path_buf();
+        let tokenizer = tokenizer_config(&index_path)?;
+        let begin = Instant::now();
+        let reader = if index_path.exists() {
+            let index = open_or_create_index(&index_path, Access::Read, &tokenizer)?;
+            if index.schema() != *schema() {
+                return Err(PoemError::IndexCorrupt {
+                    path: index_path,
+                    reason: "it was built with a different schema".to_string(),
+                }
+                .into());
+            }
+            let reader = index.reader()?;
+            log::debug!(
+                "{} documents in {} segments at {}",
+                reader.searcher().num_docs(),
+                reader.searcher().segment_readers().len(),
+                index_path.display()
+            );
+            Some(reader)
+        } else {
+            None
+        };
+        timings::record("open index", begin.elapsed());
+
+        Ok(Self {
+            index_path,
+            reader,
+            tokenizer,
+            poems: timings::time("load poems", || load_poems(None))?,
+        })
+    }
+
+    /// a repository over `poems` with an index built in memory, only for `search`, the years
+    /// of the authors are looked up in `authors`
+    pub fn in_memory(poems: Vec<Poem>, authors: &Authors) -> Result<Self> {
+        let tokenizer = TokenizerConfig::default();
+        let index = index_in_ram(&poems, authors, &tokenizer)?;
+        Ok(Self {
+            index_path: PathBuf::new(),
+            reader: Some(index.reader()?),
+            tokenizer,
+            poems,
+        })
+    }
+
+    /// a repository over `poems` without any index
+    pub fn from_poems(poems: Vec<Poem>) -> Self {
+        Self {
+            index_path: PathBuf::new(),
+            reader: None,
+            tokenizer: TokenizerConfig::default(),
+            poems,
+        }
+    }
+
+    /// replace the bundled poems with `poems`
+    pub fn with_poems(mut self, poems: Vec<Poem>) -> Self {
+        self.poems = poems;
+        self
+    }
+
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+
+    fn searcher(&self) -> Result<LeasedItem<Searcher>> {
+        let reader = self
+            .reader
+            .as_ref()
+            .ok_or_else(|| PoemError::IndexMissing(self.index_path.clone()))?;
+        Ok(reader.searcher())
+    }
+
+    /// search the index for `keyword`
+    pub fn search(&self, keyword: &str, opts: &SearchOptions) -> Result<Vec<Hit>> {
+        self.search_hits(keyword, opts, None)
+    }
+
+    /// like `search`, every hit also has a snippet of at most about `max_bytes` bytes
+    /// around the matched terms
+    pub fn search_snippets(
+        &self,
+        keyword: &str,
+        opts: &SearchOptions,
+        max_bytes: usize,
+    ) -> Result<Vec<Hit>> {
+        self.search_hits(keyword, opts, Some(max_bytes))
+    }
+
+    /// how many poems match `keyword`, counted in the index without reading the documents
+    pub fn count(&self, keyword: &str, opts: &SearchOptions) -> Result<usize> {
+        if opts.reads_documents(keyword)? {
+            let mut count = 0;
+            self.search_each(keyword, opts, |_| {
+                count += 1;
+                Ok(())
+            })?;
+            return Ok(count);
+        }
+        let searcher = self.searcher()?;
+        let query = poem_query(searcher.index(), keyword, opts)?;
+        Ok(searcher.search(query.as_ref(), &Count)?)
+    }
+
+    /// like `search`, every hit is passed to `f` as soon as it is read from the index,
+    /// most relevant first
+    pub fn search_each(
+        &self,
+        keyword: &str,
+        opts: &SearchOptions,
+        f: impl FnMut(Hit) -> Result<()>,
+    ) -> Result<()> {
+        self.for_each_hit(keyword, opts, None, false, f)
+    }
+
+    /// like `search`, every hit with the explanation of its score
+    pub fn search_explained(&self, keyword: &str, opts: &SearchOptions) -> Result<Vec<Hit>> {
+        let mut hits = Vec::new();
+        self.for_each_hit(keyword, opts, None, true, |hit| {
+            hits.push(hit);
+            Ok(())
+        })?;
+        Ok(hits)
+    }
+
+    /// the parts of the keywords the query parser takes as syntax, and the options that do
+    /// nothing with the tokenizer of the index
+    pub fn query_warnings(&self, keyword: &str, opts: &SearchOptions) -> Result<Vec<String>> {
+        let searcher = self.searcher()?;
+        let mut warnings = syntax_warnings(searcher.index(), &opts.fields()?, keyword, opts);
+        warnings.extend(self.tokenizer_warning(opts));
+        Ok(warnings)
+    }
+
+    /// like `query_warnings`, for `search_lines`
+    pub fn line_query_warnings(&self, keyword: &str, opts: &SearchOptions) -> Result<Vec<String>> {
+        let searcher = self.lines_searcher()?;
+        let field = lines::fields()["line"];
+        let mut warnings = syntax_warnings(searcher.index(), &[field], keyword, opts);
+        warnings.extend(self.tokenizer_warning(opts));
+        Ok(warnings)
+    }
+
+    fn tokenizer_warning(&self, opts: &SearchOptions) -> Option<String> {
+        // 每个字都是一个词 单字只能精确匹配
+        (opts.fuzzy && self.tokenizer.tokenizer == Tokenizer::Unicode).then(|| {
+            "--fuzzy does nothing with the unicode tokenizer, every character is a term, \
+             rebuild the index with `poem index --force --tokenizer jieba` to tolerate typos"
+                .to_string()
+        })
+    }
+
+    /// the query tree `keyword` and `opts` are parsed into, with readable terms
+    pub fn parsed_query(&self, keyword: &str, opts: &SearchOptions) -> Result<String> {
+        let searcher = self.searcher()?;
+        let query = poem_query(searcher.index(), keyword, opts)?;
+        Ok(readable(&format!("{:#?}", query)))
+    }
+
+    fn search_hits(
+        &self,
+        keyword: &str,
+        opts: &SearchOptions,
+        snippet_bytes: Option<usize>,
+    ) -> Result<Vec<Hit>> {
+        let mut hits = Vec::new();
+        self.for_each_hit(keyword, opts, snippet_bytes, false, |hit| {
+            hits.push(hit);
+            Ok(())
+        })?;
+        Ok(hits)
+    }
+
+    fn for_each_hit(
+        &self,
+        keyword: &str,
+        opts: &SearchOptions,
+        snippet_bytes: Option<usize>,
+        explain: bool,
+        mut f: impl FnMut(Hit) -> Result<()>,
+    ) -> Result<()> {
+        let searcher = self.searcher()?;
+        let fields = fields();
+
+        let regex = opts.regex(keyword)?;
+        let query = timings::time("parse query", || {
+            poem_query(searcher.index(), keyword, opts)
+        })?;
+        log::debug!("query: {:?}", query);
+        let top_docs = timings::time("search", || {
+            searcher.search(query.as_ref(), &TopDocs::with_limit(10000))
+        })?;
+        log::debug!("{} documents matched", top_docs.len());
+
+        // 片段足够长时 整个content就是一个片段 高亮的位置就是在content中的位置
+        let mut snippets = SnippetGenerator::create(&searcher, query.as_ref(), fields["content"])?;
+        snippets.set_max_num_chars(usize::MAX);
+        let mut excerpts = match snippet_bytes {
+            Some(max) => {
+                let mut excerpts =
+                    SnippetGenerator::create(&searcher, query.as_ref(), fields["content"])?;
+                excerpts.set_max_num_chars(max);
+                Some(excerpts)
+            }
+            None => None,
+        };
+        // 取文档的时间分散在每个结果里 加起来算
+        let mut fetching = Duration::ZERO;
+        for (score, doc_address) in top_docs.into_iter().filter(|(s, _)| opts.accepts(*s)) {
+            let begin = Instant::now();
+            let doc = searcher.doc(doc_address)?;
+            fetching += begin.elapsed();
+            // 用不同schema建的旧索引中的文档可能缺字段 跳过它们而不是让整个搜索失败
+            let poem = match Poem::try_from(doc) {
+                Ok(poem) => poem,
+                Err(e) => {
+                    eprintln!("skipping malformed document {:?}: {}", doc_address, e);
+                    continue;
+                }
+            };
+            if !opts.rhymes(rhyme_of_poem(&poem)) {
+                continue;
+            }
+            let highlights = match &regex {
+                Some(regex) => match regex_matches(regex, &poem.content) {
+                    Some(matches) => matches,
+                    None => continue,
+                },
+                None => snippets.snippet(&poem.content).highlighted().to_vec(),
+            };
+            let snippet = excerpts.as_mut().map(|g| snippet(g, &poem.content));
+            let explanation = if explain {
+                Some(Explanation::new(&query.explain(&searcher, doc_address)?)?)
+            } else {
+                None
+            };
+            f(Hit {
+                score,
+                poem,
+                highlights,
+                snippet,
+                explanation,
+                source: None,
+            })?;
+        }
+        timings::record("fetch documents", fetching);
+        Ok(())
+    }
+
+    /// the `limit` poems sharing the most distinctive terms with the poem of `id`, the most alike first
+    pub fn similar(&self, id: &str, limit: usize) -> Result<Vec<Hit>> {
+        let searcher = self.searcher()?;
+        let fields = fields();
+        // 索引不是用这些诗建的时候 从索引里存的文档找
+        let poem = match self.get(id) {
+            Some(poem) => poem.clone(),
+            None => indexed_poem(&searcher, fields["id"], id)?
+                .ok_or_else(|| anyhow!("no poem with id {}", id))?,
+        };
+        // 诗都很短 词出现一次也算
+        let mut frequencies: HashMap<Term, usize> = HashMap::new();
+        for (name, text) in [("title", &poem.title), ("content", &poem.content)] {
+            let field = fields[name];
+            let mut tokens = searcher
+                .index()
+                .tokenizer_for_field(field)?
+                .token_stream(text);
+            while tokens.advance() {
+                *frequencies
+                    .entry(Term::from_field_text(field, &tokens.token().text))
+                    .or_default() += 1;
+            }
+        }
+        // 词的权重按没删掉的诗算 tantivy的MoreLikeThisQuery把删掉的也算进文档频率
+        let alive = searcher.num_docs();
+        let mut weighted = Vec::with_capacity(frequencies.len());
+        for (term, frequency) in frequencies {
+            let query = TermQuery::new(term.clone(), IndexRecordOption::Basic);
+            let doc_frequency = searcher.search(&query, &Count)? as u64;
+            // 只在这首里出现的词找不到别的诗
+            if doc_frequency < 2 {
+                continue;
+            }
+            let idf = 1.0 + ((alive - doc_frequency) as f32 + 0.5) / (doc_frequency as f32 + 0.5);
+            weighted.push((frequency as f32 * idf.ln(), term));
+        }
+        weighted.sort_by(|a, b| b.0.total_cmp(&a.0));
+        weighted.truncate(SIMILAR_TERMS);
+        let query = BooleanQuery::new(
+            weighted
+                .into_iter()
+                .map(|(weight, term)| {
+                    let query = TermQuery::new(term, IndexRecordOption::WithFreqs);
+                    let query: Box<dyn Query> = Box::new(BoostQuery::new(Box::new(query), weight));
+                    (Occur::Should, query)
+                })
+                .collect(),
+        );
+        // 多取一个 去掉它自己
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit + 1))?;
+        let mut hits = Vec::with_capacity(limit);
+        for (score, doc_address) in top_docs {
+            let other = match Poem::try_from(searcher.doc(doc_address)?) {
+                Ok(other) => other,
+                Err(e) => {
+                    eprintln!("skipping malformed document {:?}: {}", doc_address, e);
+                    continue;
+                }
+            };
+            if other.id() == id {
+                continue;
+            }
+            hits.push(Hit {
+                score,
+                poem: other,
+                highlights: Vec::new(),
+                snippet: None,
+                explanation: None,
+                source: None,
+            });
+        }
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// search the line index for `keyword`, `opts.field` is ignored since lines only have one text field
+    pub fn search_lines(&self, keyword: &str, opts: &SearchOptions) -> Result<Vec<LineHit>> {
+        let searcher = self.lines_searcher()?;
+        let field = lines::fields()["line"];
+
+        let regex = opts.regex(keyword)?;
+        let query = build_query(searcher.index(), &[field], keyword, opts)?;
+        let top_docs = searcher.search(query.as_ref(), &TopDocs::with_limit(10000))?;
+
+        let mut snippets = SnippetGenerator::create(&searcher, query.as_ref(), field)?;
+        snippets.set_max_num_chars(usize::MAX);
+        let mut hits = Vec::with_capacity(top_docs.len());
+        for (score, doc_address) in top_docs.into_iter().filter(|(s, _)| opts.accepts(*s)) {
+            let line = match Line::try_from(searcher.doc(doc_address)?) {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("skipping malformed document {:?}: {}", doc_address, e);
+                    continue;
+                }
+            };
+            // 句子索引里没有长度和体裁 按所在的诗判断
+            if !opts.shape.is_empty()
+                && !self
+                    .get(&line.poem_id)
+                    .is_some_and(|p| opts.shape.matches(p))
+            {
+                continue;
+            }
+            if !opts.by(&line.author, &line.dynasty) {
+                continue;
+            }
+            if !opts.rhymes(line.text.chars().last().and_then(rhyme_of)) {
+                continue;
+            }
+            let highlights = match &regex {
+                Some(regex) => match regex_matches(regex, &line.text) {
+                    Some(matches) => matches,
+                    None => continue,
+                },
+                None => snippets.snippet(&line.text).highlighted().to_vec(),
+            };
+            hits.push(LineHit {
+                score,
+                line,
+                highlights,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// every line in the line index, in the order they were indexed
+    pub fn all_lines(&self) -> Result<Vec<Line>> {
+        let searcher = self.lines_searcher()?;
+        let limit = (searcher.num_docs() as usize).max(1);
+        let mut addresses: Vec<_> = searcher
+            .search(&AllQuery, &TopDocs::with_limit(limit))?
+            .into_iter()
+            .map(|(_, address)| address)
+            .collect();
+        addresses.sort();
+        let mut lines = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            match Line::try_from(searcher.doc(address)?) {
+                Ok(line) => lines.push(line),
+                Err(e) => eprintln!("skipping malformed document {:?}: {}", address, e),
+            }
+        }
+        Ok(lines)
+    }
+
+    fn lines_searcher(&self) -> Result<LeasedItem<Searcher>> {
+        let path = lines::index_path(&self.index_path);
+        if self.reader.is_none() || !path.exists() {
+            return Err(anyhow!(
+                "no line index found at {}, run `poem index` first",
+                path.display()
+            ));
+        }
+        Ok(
+            lines::open_or_create_index(&path, Access::Read, &self.tokenizer)?
+                .reader()?
+                .searcher(),
+        )
+    }
+
+    /// how many poems of each dynasty match `keyword`, the most first
+    pub fn dynasty_facets(
+        &self,
+        keyword: &str,
+        opts: &SearchOptions,
+    ) -> Result<Vec<(String, u64)>> {
+        // 要取出文档才能判断时 只能数搜到的结果
+        if opts.reads_documents(keyword)? {
+            let hits = self.search(keyword, opts)?;
+            let dynasties: Vec<&str> = hits.iter().map(|h| &h.poem.dynasty[..]).collect();
+            let mut facets: Vec<(String, u64)> = words_count(&dynasties, false)
+                .into_iter()
+                .map(|(d, count)| (d.to_string(), count as u64))
+                .collect();
+            facets.sort_by_key(|f| std::cmp::Reverse(f.1));
+            return Ok(facets);
+        }
+        let searcher = self.searcher()?;
+        let fields = fields();
+
+        let query = poem_query(searcher.index(), keyword, opts)?;
+        let mut collector = FacetCollector::for_field(fields["dynasty_facet"]);
+        collector.add_facet("/");
+        let counts = searcher.search(query.as_ref(), &collector)?;
+        let mut facets: Vec<(String, u64)> = counts
+            .get("/")
+            .map(|(facet, count)| (facet.to_path().concat(), count))
+            .collect();
+        facets.sort_by_key(|f| std::cmp::Reverse(f.1));
+        Ok(facets)
+    }
+
+    /// at most `count` distinct random poems matching `filter`
+    pub fn random(&self, count: usize, filter: &PoemFilter) -> Vec<&Poem> {
+        self.random_with(count, filter, &mut rand::thread_rng())
+    }
+
+    /// like `random`, the same `rng` state always picks the same poems
+    pub fn random_with(&self, count: usize, filter: &PoemFilter, rng: &mut impl Rng) -> Vec<&Poem> {
+        // 蓄水池抽样 不需要先把所有的诗收集起来再打乱
+        let mut poems = self
+            .poems
+            .iter()
+            .filter(|p| filter.matches(p))
+            .choose_multiple(rng, count);
+        // 蓄水池里的顺序不是随机的
+        poems.shuffle(rng);
+        poems
+    }
+
+    /// the poem of the day, the same date always gets the same poem
+    pub fn daily(&self, date: NaiveDate) -> Option<&Poem> {
+        if self.poems.is_empty() {
+            return None;
+        }
+        let mut hasher = FnvHasher::default();
+        date.to_string().hash(&mut hasher);
+        Some(&self.poems[(hasher.finish() % self.poems.len() as u64) as usize])
+    }
+
+    /// all poems by `author` sorted by title
+    pub fn by_author(&self, author: &str) -> Vec<&Poem> {
+        let mut poems: Vec<&Poem> = self.poems.iter().filter(|p| p.author == author).collect();
+        poems.sort_by(|a, b| a.title.cmp(&b.title));
+        poems
+    }
+
+    /// the poem with the given id
+    pub fn get(&self, id: &str) -> Option<&Poem> {
+        self.poems.iter().find(|p| p.id() == id)
+    }
+
+    /// `limit` poems matching `filter` in `order` after skipping the first `offset` ones,
+    /// or all of them after the skipped ones
+    pub fn list_by(
+        &self,
+        filter: &PoemFilter,
+        order: ListOrder,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<&Poem> {
+        let mut poems: Vec<&Poem> = self.poems.iter().filter(|p| filter.matches(p)).collect();
+        match order {
+            ListOrder::Dataset => {}
+            // 标题相同的再按id排 每次翻页的顺序才一样
+            ListOrder::Title => poems.sort_by_cached_key(|p| (sort_key(&p.title), p.id())),
+            ListOrder::Id => poems.sort_by_cached_key(|p| p.id()),
+        }
+        poems
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    /// the first `limit` poems, or all of them
+    pub fn list(&self, limit: Option<usize>) -> &[Poem] {
+        let poems = &self.poems;
+        match limit {
+            Some(l) => {
+                if l > poems.len() {
+                    &poems[..]
+                } else {
+                    &poems[..l]
+                }
+            }
+            None => &poems[..],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+    use crate::{build_index, remove_from_index};
+    use indicatif::ProgressBar;
+
+    #[test]
+    fn similar_skips_deleted_poems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        let poems = vec![
+            poem("静夜思", "床前明月光，疑是地上霜。举头望明月，低头思故乡。"),
+            poem("月下独酌", "举杯邀明月，对影成三人。"),
+            poem("关山月", "明月出天山，苍茫云海间。"),
+            poem("把酒问月", "青天有月来几时？我今停杯一问之。"),
+        ];
+        let bar = ProgressBar::hidden();
+        build_index(
+            &path,
+            poems.clone(),
+            &Authors::default(),
+            false,
+            None,
+            None,
+            &bar,
+        )
+        .unwrap();
+        remove_from_index(&path, &poems[2..3]).unwrap();
+
+        let mut kept = poems;
+        let removed = kept.remove(2);
+        let id = kept[0].id();
+        let repo = PoemRepository::open(&path).unwrap().with_poems(kept);
+        let hits = repo.similar(&id, 5).unwrap();
+        let titles: Vec<&str> = hits.iter().map(|h| h.poem.title.as_str()).collect();
+        assert!(titles.contains(&"月下独酌"), "{:?}", titles);
+        assert!(!titles.contains(&removed.title.as_str()), "{:?}", titles);
+        assert!(!titles.contains(&"静夜思"), "{:?}", titles);
+    }
+
+    #[test]
+    fn similar_finds_poems_only_in_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index");
+        let poems = vec![
+            poem("静夜思", "床前明月光，疑是地上霜。举头望明月，低头思故乡。"),
+            poem("月下独酌", "举杯邀明月，对影成三人。"),
+            poem("春晓", "春眠不觉晓，处处闻啼鸟。"),
+        ];
+        let bar = ProgressBar::hidden();
+        build_index(
+            &path,
+            poems.clone(),
+            &Authors::default(),
+            false,
+            None,
+            None,
+            &bar,
+        )
+        .unwrap();
+
+        // 不在with_poems给的诗里
+        let repo = PoemRepository::open(&path).unwrap().with_poems(Vec::new());
+        let hits = repo.similar(&poems[0].id(), 5).unwrap();
+        assert_eq!(hits[0].poem.title, "月下独酌");
+        assert!(repo.similar("0123456789abcdef", 5).is_err());
+    }
+}
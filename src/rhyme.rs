@@ -0,0 +1,158 @@
+use crate::{
+    output::{Render, Style},
+    Poem,
+};
+use colored::*;
+use pinyin::ToPinyin;
+use serde::Serialize;
+use std::{collections::HashMap, fmt};
+
+const INITIALS: &[&str] = &[
+    "zh", "ch", "sh", "b", "p", "m", "f", "d", "t", "n", "l", "g", "k", "h", "j", "q", "x", "r",
+    "z", "c", "s",
+];
+
+/// the rhyme group of `c` in the 18 groups of 中华新韵, told from the final of its
+/// most common reading, none for punctuation and non chinese characters
+pub fn rhyme_of(c: char) -> Option<&'static str> {
+    let plain = c.to_pinyin()?.plain();
+    let initial = INITIALS.iter().find(|i| plain.starts_with(**i)).copied();
+    let rest = &plain[initial.map_or(0, str::len)..];
+    let fin = match rest {
+        // y w开头的是零声母 还原成完整的韵母
+        r if r.starts_with("yu") => format!("v{}", &r[2..]),
+        "yi" | "yin" | "ying" => rest[1..].to_string(),
+        r if r.starts_with('y') => format!("i{}", &r[1..]),
+        "wu" => "u".to_string(),
+        r if r.starts_with('w') => format!("u{}", &r[1..]),
+        // j q x后面的u其实是ü
+        r if matches!(initial, Some("j" | "q" | "x")) && r.starts_with('u') => {
+            format!("v{}", &r[1..])
+        }
+        r => r.replace('ü', "v"),
+    };
+    let group = match &fin[..] {
+        "a" | "ia" | "ua" => "麻",
+        "o" | "uo" => "波",
+        "e" => "歌",
+        "ie" | "ve" => "皆",
+        "i" if matches!(initial, Some("zh" | "ch" | "sh" | "r" | "z" | "c" | "s")) => "支",
+        "er" => "儿",
+        "i" => "齐",
+        "ei" | "ui" | "uei" => "微",
+        "ai" | "uai" => "开",
+        "u" => "姑",
+        "v" => "鱼",
+        "ou" | "iu" | "iou" => "侯",
+        "ao" | "iao" => "豪",
+        "an" | "ian" | "uan" | "van" => "寒",
+        "en" | "in" | "un" | "uen" | "vn" => "痕",
+        "ang" | "iang" | "uang" => "唐",
+        "eng" | "ing" | "ueng" => "庚",
+        "ong" | "iong" => "东",
+        _ => return None,
+    };
+    Some(group)
+}
+
+/// the rhyme of the last character of every line of a poem
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    /// the group most even lines end with, where the rhymes of a poem usually are
+    pub rhyme: Option<&'static str>,
+    pub lines: Vec<LineRhyme>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineRhyme {
+    pub text: String,
+    pub last: char,
+    pub pinyin: Option<&'static str>,
+    pub rhyme: Option<&'static str>,
+}
+
+impl Report {
+    pub fn new(poem: &Poem) -> Self {
+        let lines: Vec<LineRhyme> = poem
+            .lines()
+            .into_iter()
+            .filter_map(|line| {
+                let last = line.chars().last()?;
+                Some(LineRhyme {
+                    text: line.to_string(),
+                    last,
+                    pinyin: last.to_pinyin().map(|p| p.with_tone()),
+                    rhyme: rhyme_of(last),
+                })
+            })
+            .collect();
+        Self {
+            id: poem.id(),
+            title: poem.title.clone(),
+            author: poem.author.clone(),
+            rhyme: main_rhyme(lines.iter().map(|l| l.rhyme)),
+            lines,
+        }
+    }
+}
+
+/// the rhyme of a poem from the rhymes its lines end with in order
+fn main_rhyme(ends: impl Iterator<Item = Option<&'static str>>) -> Option<&'static str> {
+    let ends: Vec<Option<&str>> = ends.collect();
+    // 一般是偶数句押韵 只有一句时就用这一句
+    let even: Vec<&str> = match ends.len() {
+        1 => ends.iter().flatten().copied().collect(),
+        _ => ends.iter().skip(1).step_by(2).flatten().copied().collect(),
+    };
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    even.iter().for_each(|r| *counts.entry(r).or_insert(0) += 1);
+    // 次数一样时取先出现的
+    even.iter().copied().max_by_key(|r| {
+        (
+            counts[r],
+            std::cmp::Reverse(even.iter().position(|e| e == r)),
+        )
+    })
+}
+
+/// the rhyme of `poem`
+pub fn rhyme_of_poem(poem: &Poem) -> Option<&'static str> {
+    main_rhyme(
+        poem.lines()
+            .iter()
+            .map(|l| l.chars().last().and_then(rhyme_of)),
+    )
+}
+
+impl Render for Report {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        writeln!(
+            f,
+            "{} {} {}",
+            self.title.bright_cyan(),
+            self.author.cyan(),
+            self.id.dimmed()
+        )?;
+        for line in &self.lines {
+            // 押韵的字标红
+            let head = &line.text[..line.text.len() - line.last.len_utf8()];
+            let last = line.last.to_string();
+            let last = match line.rhyme {
+                Some(_) if line.rhyme == self.rhyme => last.red().bold(),
+                _ => last.cyan(),
+            };
+            writeln!(
+                f,
+                "{}{}\t{}\t{}",
+                head.cyan(),
+                last,
+                line.pinyin.unwrap_or("-"),
+                line.rhyme.unwrap_or("-")
+            )?;
+        }
+        write!(f, "韵部：{}", self.rhyme.unwrap_or("无"))
+    }
+}
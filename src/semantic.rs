@@ -0,0 +1,286 @@
+use crate::{Poem, PoemError};
+use anyhow::{anyhow, Context, Result};
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+const META_FILE: &str = "vectors.json";
+const VECTORS_FILE: &str = "vectors.bin";
+
+/// the dimensions of the built in embedding
+pub const HASH_DIMS: usize = 512;
+
+/// how texts are turned into vectors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Embedder {
+    /// built in: the characters and pairs of characters hashed into `dims` dimensions,
+    /// only finds poems sharing characters with the query
+    Hash { dims: usize },
+    /// a shell command reading a json array of texts on stdin and writing a json array of
+    /// vectors, one for each text, to stdout
+    Command(String),
+}
+
+impl Embedder {
+    /// the command if given, the built in embedding otherwise
+    pub fn new(command: Option<String>) -> Self {
+        match command {
+            Some(command) => Self::Command(command),
+            None => Self::Hash { dims: HASH_DIMS },
+        }
+    }
+
+    /// the normalized vectors of `texts`
+    pub fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut vectors = match self {
+            Self::Hash { dims } => texts.iter().map(|t| hash_embed(t, *dims)).collect(),
+            Self::Command(command) => run_command(command, texts)?,
+        };
+        vectors.iter_mut().for_each(|v| normalize(v));
+        Ok(vectors)
+    }
+}
+
+fn hash_embed(text: &str, dims: usize) -> Vec<f32> {
+    let mut vector = vec![0.0; dims];
+    let mut add = |feature: &str| {
+        let mut hasher = FnvHasher::default();
+        feature.hash(&mut hasher);
+        let hash = hasher.finish();
+        // 用最高位当符号 减少冲突带来的偏差
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % dims as u64) as usize] += sign;
+    };
+    // 标点隔开的每一段里取单字和相邻的两个字
+    for part in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = part.chars().collect();
+        for c in &chars {
+            add(&c.to_string());
+        }
+        for pair in chars.windows(2) {
+            add(&pair.iter().collect::<String>());
+        }
+    }
+    vector
+}
+
+fn run_command(command: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", command))?;
+    // 另开线程写 输出多的时候不会互相等着
+    let input = serde_json::to_vec(texts)?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| anyhow!("failed to write to `{}`", command))??;
+    if !output.status.success() {
+        return Err(anyhow!("`{}` failed: {}", command, output.status));
+    }
+    let vectors: Vec<Vec<f32>> = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("`{}` did not print a json array of vectors", command))?;
+    if vectors.len() != texts.len() {
+        return Err(anyhow!(
+            "`{}` printed {} vectors for {} texts",
+            command,
+            vectors.len(),
+            texts.len()
+        ));
+    }
+    Ok(vectors)
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// the text of `poem` that is embedded
+fn text_of(poem: &Poem) -> String {
+    format!("{}\n{}", poem.title, poem.content)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    embedder: Embedder,
+    dims: usize,
+    ids: Vec<String>,
+}
+
+/// the vectors of the poems stored next to the index
+///
+/// searched exhaustively, a collection of poems is small enough that an approximate index
+/// would not be faster
+pub struct VectorIndex {
+    pub embedder: Embedder,
+    dims: usize,
+    ids: Vec<String>,
+    vectors: Vec<f32>,
+}
+
+impl VectorIndex {
+    /// whether the index at `path` has vectors
+    pub fn exists(path: impl AsRef<Path>) -> bool {
+        paths(path.as_ref()).0.exists()
+    }
+
+    /// the vectors stored in the index at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let (meta_path, vectors_path) = paths(path.as_ref());
+        if !meta_path.exists() {
+            return Err(anyhow!(
+                "no vectors found at {}, run `poem semantic index` first",
+                meta_path.display()
+            ));
+        }
+        let meta: Meta = serde_json::from_str(&fs::read_to_string(&meta_path)?)
+            .with_context(|| format!("failed to parse {}", meta_path.display()))?;
+        let bytes = fs::read(&vectors_path).map_err(PoemError::io(&vectors_path))?;
+        if bytes.len() != meta.ids.len() * meta.dims * 4 {
+            return Err(anyhow!(
+                "{} does not match {}, run `poem semantic index --force`",
+                vectors_path.display(),
+                meta_path.display()
+            ));
+        }
+        let vectors = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Ok(Self {
+            embedder: meta.embedder,
+            dims: meta.dims,
+            ids: meta.ids,
+            vectors,
+        })
+    }
+
+    /// embed `poems` with `embedder` into the index at `path`, returns how many were embedded
+    ///
+    /// the vectors already there are kept for the poems still in `poems` unless `force` is set
+    /// or the embedder changed
+    pub fn build(
+        path: impl AsRef<Path>,
+        poems: &[Poem],
+        embedder: Embedder,
+        force: bool,
+    ) -> Result<usize> {
+        let path = path.as_ref();
+        let mut known: HashMap<String, Vec<f32>> = HashMap::new();
+        if !force && Self::exists(path) {
+            let old = Self::open(path)?;
+            if old.embedder == embedder {
+                for (i, id) in old.ids.iter().enumerate() {
+                    known.insert(id.clone(), old.vector(i).to_vec());
+                }
+            }
+        }
+        let missing: Vec<&Poem> = poems
+            .iter()
+            .filter(|p| !known.contains_key(&p.id()))
+            .collect();
+        let texts: Vec<String> = missing.iter().map(|p| text_of(p)).collect();
+        let embedded = if texts.is_empty() {
+            Vec::new()
+        } else {
+            embedder.embed(&texts)?
+        };
+        for (poem, vector) in missing.iter().zip(embedded) {
+            known.insert(poem.id(), vector);
+        }
+
+        let ids: Vec<String> = poems.iter().map(Poem::id).collect();
+        let dims = ids.first().map_or(0, |id| known[id].len());
+        let mut bytes = Vec::with_capacity(ids.len() * dims * 4);
+        for id in &ids {
+            let vector = &known[id];
+            if vector.len() != dims {
+                return Err(anyhow!(
+                    "vectors of different lengths: {} and {}",
+                    dims,
+                    vector.len()
+                ));
+            }
+            vector
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+        }
+        let (meta_path, vectors_path) = paths(path);
+        fs::create_dir_all(path)?;
+        fs::write(&vectors_path, bytes)?;
+        let meta = Meta {
+            embedder,
+            dims,
+            ids,
+        };
+        fs::write(&meta_path, serde_json::to_string(&meta)?)?;
+        Ok(missing.len())
+    }
+
+    fn vector(&self, i: usize) -> &[f32] {
+        &self.vectors[i * self.dims..(i + 1) * self.dims]
+    }
+
+    /// the ids of the `limit` poems closest to `query` by cosine similarity, the closest first
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<(&str, f32)>> {
+        let query = self
+            .embedder
+            .embed(&[query.to_string()])?
+            .pop()
+            .unwrap_or_default();
+        if query.len() != self.dims {
+            return Err(anyhow!(
+                "the query has {} dimensions but the poems have {}",
+                query.len(),
+                self.dims
+            ));
+        }
+        Ok(self.nearest(&query, limit))
+    }
+
+    /// the ids of the `limit` poems closest to the poem of `id`, none if it is not in the index
+    pub fn similar(&self, id: &str, limit: usize) -> Option<Vec<(&str, f32)>> {
+        let i = self.ids.iter().position(|i| i == id)?;
+        let mut nearest = self.nearest(self.vector(i), limit + 1);
+        nearest.retain(|(other, _)| *other != id);
+        nearest.truncate(limit);
+        Some(nearest)
+    }
+
+    fn nearest(&self, query: &[f32], limit: usize) -> Vec<(&str, f32)> {
+        let mut scores: Vec<(&str, f32)> = self
+            .ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let score = self.vector(i).iter().zip(query).map(|(a, b)| a * b).sum();
+                (&id[..], score)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.truncate(limit);
+        scores
+    }
+}
+
+fn paths(path: &Path) -> (PathBuf, PathBuf) {
+    (path.join(META_FILE), path.join(VECTORS_FILE))
+}
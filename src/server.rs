@@ -0,0 +1,138 @@
+use crate::{error::PoemError, PoemFilter, PoemRepository, SearchOptions};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// what is wrong with a request, the other errors are the server's own
+#[derive(Debug, Error)]
+enum RequestError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("unknown path `{0}`")]
+    NotFound(String),
+    #[error("only GET is supported")]
+    MethodNotAllowed,
+}
+
+/// the status to answer with for `e`, 400 for a keyword that can not be parsed and 500 for
+/// an error of the server like an index that can not be read
+fn status_of(e: &anyhow::Error) -> u16 {
+    for cause in e.chain() {
+        if let Some(e) = cause.downcast_ref::<RequestError>() {
+            return match e {
+                RequestError::Invalid(_) => 400,
+                RequestError::NotFound(_) => 404,
+                RequestError::MethodNotAllowed => 405,
+            };
+        }
+        if let Some(PoemError::QuerySyntax { .. }) = cause.downcast_ref::<PoemError>() {
+            return 400;
+        }
+    }
+    500
+}
+
+/// serve the json api until the process is killed, errors are answered with a json `error`
+/// and 400 for a bad request, 404 for an unknown path or 500 for an error of the server
+///
+/// - `GET /search?q=<keyword>[&field=<field>]`
+/// - `GET /random[?count=<n>][&dynasty=<dynasty>][&author=<author>][&title_contains=<text>]`
+/// - `GET /poems`
+pub fn serve(addr: &str, repo: &PoemRepository) -> Result<()> {
+    let server = Server::http(addr).map_err(|e| anyhow!("failed to listen on {}: {}", addr, e))?;
+
+    for request in server.incoming_requests() {
+        let (status, body) = match handle(&request, repo) {
+            Ok(body) => (200, body),
+            Err(e) => (
+                status_of(&e),
+                json(&HashMap::from([("error", e.to_string())]))?,
+            ),
+        };
+        let header = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+            .expect("static header is valid");
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(header);
+        // 客户端提前断开不影响后续请求
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+fn handle(request: &Request, repo: &PoemRepository) -> Result<String> {
+    if request.method() != &Method::Get {
+        return Err(RequestError::MethodNotAllowed.into());
+    }
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (request.url(), ""),
+    };
+    let params: HashMap<String, String> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+
+    match path {
+        "/search" => {
+            let keyword = params
+                .get("q")
+                .filter(|q| !q.trim().is_empty())
+                .ok_or_else(|| RequestError::Invalid("missing query parameter `q`".to_string()))?;
+            let opts = SearchOptions {
+                field: params.get("field").cloned(),
+                ..Default::default()
+            };
+            opts.fields()
+                .map_err(|e| RequestError::Invalid(e.to_string()))?;
+            json(&repo.search(keyword, &opts)?)
+        }
+        "/random" => {
+            let count = match params.get("count") {
+                Some(c) => c.parse().map_err(|e| {
+                    RequestError::Invalid(format!("`count` is not a number: {}", e))
+                })?,
+                None => 1,
+            };
+            let filter = PoemFilter {
+                dynasty: params.get("dynasty").cloned(),
+                author: params.get("author").cloned(),
+                title_contains: params.get("title_contains").cloned(),
+                ..Default::default()
+            };
+            json(&repo.random(count, &filter))
+        }
+        "/poems" => json(repo.list(None)),
+        _ => Err(RequestError::NotFound(path.to_string()).into()),
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn only_bad_requests_are_400() {
+        let syntax = anyhow::Error::from(PoemError::QuerySyntax {
+            query: "明月(".to_string(),
+            reason: "unclosed group".to_string(),
+        });
+        assert_eq!(status_of(&syntax.context("failed to search")), 400);
+        let invalid = RequestError::Invalid("missing query parameter `q`".to_string());
+        assert_eq!(status_of(&invalid.into()), 400);
+        assert_eq!(
+            status_of(&RequestError::NotFound("/".to_string()).into()),
+            404
+        );
+        let missing = anyhow::Error::from(PoemError::IndexMissing(PathBuf::from("index")));
+        assert_eq!(status_of(&missing.context("failed to search")), 500);
+        assert_eq!(status_of(&anyhow!("disk full")), 500);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{error::PoemError, Poem, PoemFilter};
+use anyhow::{anyhow, Context, Result};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+// 标题 作者和朝代单独成列 不用解析json就能查 position是rowid 按加进来的顺序排
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS poems (
+    position INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL,
+    id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    author TEXT NOT NULL,
+    dynasty TEXT NOT NULL,
+    poem TEXT NOT NULL,
+    UNIQUE (kind, id)
+);
+CREATE INDEX IF NOT EXISTS poems_author ON poems (kind, author, dynasty);
+";
+
+/// which poems of the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// the poems added with `add` or `import`
+    User,
+    /// the poems installed with `fetch` in place of the bundled ones
+    Dataset,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::User => "user",
+            Kind::Dataset => "dataset",
+        }
+    }
+}
+
+/// a sqlite database of poems, read and written with the sqlite3 program
+#[derive(Debug, Clone)]
+pub(crate) struct Database {
+    path: PathBuf,
+}
+
+impl Database {
+    pub(crate) fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// run `sql` on the database, stopping at the first error, returns what it printed
+    fn run(&self, sql: &str) -> Result<String> {
+        let mut child = Command::new("sqlite3")
+            .arg("-bail")
+            .arg("-batch")
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to run sqlite3, is it installed?")?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(format!("{}{}", SCHEMA, sql).as_bytes())
+            .map_err(PoemError::io(&self.path))?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "sqlite3 failed on {}: {}",
+                self.path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// the poems of `kind` in the order they were added
+    pub(crate) fn load(&self, kind: Kind) -> Result<Vec<Poem>> {
+        self.select(kind, "")
+    }
+
+    /// the poem of `kind` with `id`, none if there is no such poem
+    pub(crate) fn get(&self, kind: Kind, id: &str) -> Result<Option<Poem>> {
+        let poems = self.select(kind, &format!(" AND id = {}", quote(id)))?;
+        Ok(poems.into_iter().next())
+    }
+
+    /// the poems of `kind` passing the dynasty, author and title filters of `filter`,
+    /// the others are left to the caller
+    pub(crate) fn matching(&self, kind: Kind, filter: &PoemFilter) -> Result<Vec<Poem>> {
+        let mut conditions = String::new();
+        if let Some(dynasty) = &filter.dynasty {
+            conditions.push_str(&format!(" AND dynasty = {}", quote(dynasty)));
+        }
+        if let Some(author) = &filter.author {
+            conditions.push_str(&format!(" AND author = {}", quote(author)));
+        }
+        // instr不像LIKE要转义%和_
+        if let Some(title) = &filter.title_contains {
+            conditions.push_str(&format!(" AND instr(title, {}) > 0", quote(title)));
+        }
+        self.select(kind, &conditions)
+    }
+
+    fn select(&self, kind: Kind, conditions: &str) -> Result<Vec<Poem>> {
+        let json = self.run(&format!(
+            "SELECT json_group_array(json(poem)) FROM \
+             (SELECT poem FROM poems WHERE kind = {}{} ORDER BY position);",
+            quote(kind.name()),
+            conditions
+        ))?;
+        serde_json::from_str(&json)
+            .map_err(PoemError::from)
+            .with_context(|| format!("failed to parse the poems in {}", self.path.display()))
+    }
+
+    /// add the poems of `kind` not there yet, returns them
+    pub(crate) fn insert(&self, kind: Kind, poems: Vec<Poem>) -> Result<Vec<Poem>> {
+        let mut sql = String::from("BEGIN;\n");
+        for poem in &poems {
+            // 每行之后打印插进去了几行 已经有的是0
+            sql.push_str(&format!("{}\nSELECT changes();\n", insert(kind, poem)?));
+        }
+        // -bail在出错的语句停下 没提交的事务随着连接关掉回滚
+        sql.push_str("COMMIT;\n");
+        let changes = self.run(&sql)?;
+        Ok(poems
+            .into_iter()
+            .zip(changes.lines())
+            .filter(|(_, changed)| *changed != "0")
+            .map(|(poem, _)| poem)
+            .collect())
+    }
+
+    /// put `poem` in place of the poem of `kind` with `id`, returns false if there is no such poem
+    pub(crate) fn update(&self, kind: Kind, id: &str, poem: &Poem) -> Result<bool> {
+        let changed = self.run(&format!(
+            "UPDATE poems SET id = {}, title = {}, author = {}, dynasty = {}, poem = {} \
+             WHERE kind = {} AND id = {};\nSELECT changes();",
+            quote(&poem.id()),
+            quote(&poem.title),
+            quote(&poem.author),
+            quote(&poem.dynasty),
+            quote(&serde_json::to_string(poem)?),
+            quote(kind.name()),
+            quote(id)
+        ))?;
+        Ok(changed.trim() != "0")
+    }
+
+    /// remove the poems of `kind` with `ids`, returns the ones there were
+    pub(crate) fn delete(&self, kind: Kind, ids: &[String]) -> Result<Vec<Poem>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids: Vec<String> = ids.iter().map(|id| quote(id)).collect();
+        let deleted = self.run(&format!(
+            "DELETE FROM poems WHERE kind = {} AND id IN ({}) RETURNING poem;",
+            quote(kind.name()),
+            ids.join(", ")
+        ))?;
+        // 存的json没有换行 一行一首
+        deleted
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(PoemError::from)
+                    .with_context(|| format!("failed to parse a poem in {}", self.path.display()))
+            })
+            .collect()
+    }
+
+    /// put `poems` in place of all the poems of `kind`, all of them or none if it fails midway
+    pub(crate) fn replace(&self, kind: Kind, poems: &[Poem]) -> Result<()> {
+        let mut sql = format!(
+            "BEGIN;\nDELETE FROM poems WHERE kind = {};\n",
+            quote(kind.name())
+        );
+        for poem in poems {
+            sql.push_str(&insert(kind, poem)?);
+            sql.push('\n');
+        }
+        sql.push_str("COMMIT;\n");
+        self.run(&sql)?;
+        Ok(())
+    }
+
+    /// remove the poems of `kind`, returns false if there were none
+    pub(crate) fn clear(&self, kind: Kind) -> Result<bool> {
+        let changed = self.run(&format!(
+            "DELETE FROM poems WHERE kind = {};\nSELECT changes();",
+            quote(kind.name())
+        ))?;
+        Ok(changed.trim() != "0")
+    }
+}
+
+/// the statement adding `poem` to the poems of `kind`, doing nothing if it is already there
+fn insert(kind: Kind, poem: &Poem) -> Result<String> {
+    Ok(format!(
+        "INSERT OR IGNORE INTO poems (kind, id, title, author, dynasty, poem) \
+         VALUES ({}, {}, {}, {}, {}, {});",
+        quote(kind.name()),
+        quote(&poem.id()),
+        quote(&poem.title),
+        quote(&poem.author),
+        quote(&poem.dynasty),
+        quote(&serde_json::to_string(poem)?)
+    ))
+}
+
+/// `s` as a sql string literal
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn poem(title: &str) -> Poem {
+        let mut poem = testing::poem(title, "床前明月光，疑是地上霜。");
+        poem.tags = vec!["思乡".to_string()];
+        poem
+    }
+
+    #[test]
+    fn poems_are_loaded_as_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Database::new(dir.path().join("poems.db"));
+        assert!(database.load(Kind::User).unwrap().is_empty());
+        let poems = vec![poem("静夜思"), poem("it's"), poem("月下独酌")];
+        database.replace(Kind::User, &poems).unwrap();
+        assert_eq!(database.load(Kind::User).unwrap(), poems);
+        assert!(database.load(Kind::Dataset).unwrap().is_empty());
+    }
+
+    #[test]
+    fn replacing_keeps_the_poems_of_the_other_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Database::new(dir.path().join("poems.db"));
+        database.replace(Kind::User, &[poem("静夜思")]).unwrap();
+        database.replace(Kind::Dataset, &[poem("关山月")]).unwrap();
+        database.replace(Kind::User, &[poem("月下独酌")]).unwrap();
+        assert_eq!(database.load(Kind::User).unwrap(), [poem("月下独酌")]);
+        assert!(database.clear(Kind::Dataset).unwrap());
+        assert!(!database.clear(Kind::Dataset).unwrap());
+        assert_eq!(database.load(Kind::User).unwrap(), [poem("月下独酌")]);
+    }
+
+    #[test]
+    fn rows_are_changed_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Database::new(dir.path().join("poems.db"));
+        let added = database
+            .insert(
+                Kind::User,
+                vec![poem("静夜思"), poem("关山月"), poem("静夜思")],
+            )
+            .unwrap();
+        assert_eq!(added, [poem("静夜思"), poem("关山月")]);
+        assert!(database
+            .insert(Kind::User, vec![poem("关山月")])
+            .unwrap()
+            .is_empty());
+
+        let id = poem("静夜思").id();
+        assert_eq!(database.get(Kind::User, &id).unwrap(), Some(poem("静夜思")));
+        assert_eq!(database.get(Kind::Dataset, &id).unwrap(), None);
+        // 改过的还在原来的位置
+        assert!(database.update(Kind::User, &id, &poem("夜思")).unwrap());
+        assert!(!database.update(Kind::User, &id, &poem("夜思")).unwrap());
+        assert_eq!(
+            database.load(Kind::User).unwrap(),
+            [poem("夜思"), poem("关山月")]
+        );
+
+        let ids = [poem("关山月").id(), id];
+        assert_eq!(database.delete(Kind::User, &ids).unwrap(), [poem("关山月")]);
+        assert_eq!(database.load(Kind::User).unwrap(), [poem("夜思")]);
+    }
+
+    #[test]
+    fn filters_are_run_in_sql() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Database::new(dir.path().join("poems.db"));
+        let mut other = poem("春晓");
+        other.author = "孟浩然".to_string();
+        database
+            .insert(
+                Kind::User,
+                vec![poem("静夜思"), poem("50%_it's"), other.clone()],
+            )
+            .unwrap();
+        let filter = |author: Option<&str>, title: Option<&str>| PoemFilter {
+            author: author.map(str::to_string),
+            title_contains: title.map(str::to_string),
+            ..PoemFilter::default()
+        };
+        assert_eq!(
+            database
+                .matching(Kind::User, &filter(Some("孟浩然"), None))
+                .unwrap(),
+            [other]
+        );
+        assert_eq!(
+            database
+                .matching(Kind::User, &filter(None, Some("%_it'")))
+                .unwrap(),
+            [poem("50%_it's")]
+        );
+        assert!(database
+            .matching(Kind::User, &filter(Some("李白"), Some("春")))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{
+    output::{Render, Style},
+    poem::is_line_break,
+    Poem,
+};
+use jieba_rs::Jieba;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+#[derive(Debug)]
+pub struct Stat<'a> {
+    total: i32,
+    author: Vec<(&'a str, i32)>,
+    dynasty: Vec<(&'a str, i32)>,
+    lengths: Option<Lengths>,
+}
+
+/// how long the contents are, punctuation not counted
+#[derive(Debug)]
+struct Lengths {
+    average: f64,
+    median: usize,
+    distinct_chars: usize,
+}
+
+impl<'a> Stat<'a> {
+    pub fn new(total: i32, author: Vec<(&'a str, i32)>, dynasty: Vec<(&'a str, i32)>) -> Self {
+        Self {
+            total,
+            author,
+            dynasty,
+            lengths: None,
+        }
+    }
+
+    /// the stat of `poems` with the content lengths, only the `top` authors with most poems
+    /// are kept if it is given
+    pub fn from_poems(poems: &'a [Poem], sort: bool, top: Option<usize>) -> Self {
+        let dynasty: Vec<&str> = poems.iter().map(|p| &p.dynasty[..]).collect();
+        let author: Vec<&str> = poems.iter().map(|p| &p.author[..]).collect();
+        let mut author = words_count(&author, sort || top.is_some());
+        if let Some(top) = top {
+            author.truncate(top);
+        }
+        let mut stat = Self::new(poems.len() as _, author, words_count(&dynasty, sort));
+
+        let chars = |p: &'a Poem| {
+            p.content
+                .chars()
+                .filter(|&c| !is_line_break(c) && !c.is_whitespace())
+        };
+        let mut lengths: Vec<usize> = poems.iter().map(|p| chars(p).count()).collect();
+        lengths.sort_unstable();
+        if !lengths.is_empty() {
+            stat.lengths = Some(Lengths {
+                average: lengths.iter().sum::<usize>() as f64 / lengths.len() as f64,
+                median: lengths[lengths.len() / 2],
+                distinct_chars: poems.iter().flat_map(chars).collect::<HashSet<_>>().len(),
+            });
+        }
+        stat
+    }
+}
+
+impl<'a> Display for Stat<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "总数：{}", self.total).unwrap();
+        if let Some(l) = &self.lengths {
+            writeln!(f, "平均字数：{:.1}", l.average).unwrap();
+            writeln!(f, "字数中位数：{}", l.median).unwrap();
+            writeln!(f, "不同的字：{}", l.distinct_chars).unwrap();
+        }
+        writeln!(f, "朝代：").unwrap();
+        self.dynasty
+            .iter()
+            .for_each(|d| writeln!(f, "{:>7}：{}", d.0, d.1).unwrap());
+        writeln!(f, "作者：").unwrap();
+        self.author
+            .iter()
+            .for_each(|d| writeln!(f, "{:>7}： {}", d.0, d.1).unwrap());
+        Ok(())
+    }
+}
+
+/// how many times `name` appears
+#[derive(Debug, Serialize)]
+pub struct Count<'a> {
+    pub name: &'a str,
+    pub count: i32,
+}
+
+impl<'a> Render for Count<'a> {
+    fn render(&self, f: &mut std::fmt::Formatter<'_>, _: &Style) -> std::fmt::Result {
+        write!(f, "{:>7}：{}", self.name, self.count)
+    }
+}
+
+/// what `frequencies` counts
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreqUnit {
+    /// single characters
+    Char,
+    /// words of the jieba dictionary
+    Word,
+}
+
+/// the `top` most frequent characters or words in the content of `poems`, punctuation not counted
+pub fn frequencies<'a>(
+    poems: impl Iterator<Item = &'a Poem>,
+    by: FreqUnit,
+    top: usize,
+) -> Vec<Count<'a>> {
+    let jieba = match by {
+        FreqUnit::Char => None,
+        FreqUnit::Word => Some(Jieba::new()),
+    };
+    let mut units = Vec::new();
+    for poem in poems {
+        let content = &poem.content[..];
+        match &jieba {
+            Some(jieba) => units.extend(jieba.cut(content, false)),
+            None => units.extend(
+                content
+                    .char_indices()
+                    .map(|(i, c)| &content[i..i + c.len_utf8()]),
+            ),
+        }
+    }
+    // 标点和空白不算 汉字都是alphanumeric
+    units.retain(|u| u.chars().any(char::is_alphanumeric));
+
+    // 次数相同的按字排 每次的结果才一样
+    let mut counts = words_count(&units, false);
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts.truncate(top);
+    counts
+        .into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect()
+}
+
+pub fn words_count<'a>(words: &[&'a str], sort: bool) -> Vec<(&'a str, i32)> {
+    let mut map = HashMap::new();
+    for w in words {
+        let entry = map.entry(*w).or_insert(0);
+        *entry += 1;
+    }
+    let mut pairs = map.into_iter().collect::<Vec<_>>();
+    if sort {
+        pairs.sort_by_key(|p| -p.1);
+    }
+    pairs
+}
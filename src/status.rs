@@ -0,0 +1,149 @@
+use crate::{
+    index::{
+        indexed_fingerprints, open_or_create_index, schema, tokenizer_config, Access, Tokenizer,
+    },
+    lines,
+    output::{Render, Style},
+    Authors, Poem, PoemError,
+};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{collections::HashSet, fmt, fs, path::Path};
+
+/// what the index in a directory looks like
+#[derive(Debug, Serialize)]
+pub struct IndexStatus {
+    pub path: String,
+    /// how many poems are indexed
+    pub poems: u64,
+    /// how many lines are indexed
+    pub lines: u64,
+    /// bytes on disk, the line index included
+    pub size: u64,
+    pub segments: usize,
+    /// false if the index was built by an older version and has to be rebuilt
+    pub schema_current: bool,
+    pub tokenizer: Tokenizer,
+    /// how many words the user dictionary has
+    pub user_dict_words: usize,
+    /// when the index was last committed to, in rfc 3339
+    pub last_commit: Option<String>,
+    /// poems of the dataset not in the index yet
+    pub unindexed: usize,
+    /// poems in the index changed since they were indexed
+    pub changed: usize,
+    /// poems in the index no longer in the dataset
+    pub stale: usize,
+}
+
+/// the status of the index in `path` compared with `poems` and the years of their authors
+/// in `authors`
+pub fn index_status(
+    path: impl AsRef<Path>,
+    poems: &[Poem],
+    authors: &Authors,
+) -> Result<IndexStatus> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(PoemError::IndexMissing(path.to_path_buf()).into());
+    }
+    let tokenizer = tokenizer_config(path)?;
+    let index = open_or_create_index(path, Access::Read, &tokenizer)?;
+    let schema_current = index.schema() == *schema();
+    let searcher = index.reader()?.searcher();
+
+    let lines_path = lines::index_path(path);
+    let lines = if lines_path.exists() {
+        let lines_index = lines::open_or_create_index(&lines_path, Access::Read, &tokenizer)?;
+        lines_index.reader()?.searcher().num_docs()
+    } else {
+        0
+    };
+
+    // 旧的索引没有id字段 没法比较
+    let (unindexed, changed, stale) = if schema_current {
+        let indexed = indexed_fingerprints(&index)?;
+        let mut ids = HashSet::new();
+        let (mut unindexed, mut changed) = (0, 0);
+        for poem in poems.iter().filter(|p| ids.insert(p.raw_id())) {
+            match indexed.get(&poem.raw_id()) {
+                None => unindexed += 1,
+                Some(&fingerprint) if fingerprint != poem.fingerprint(authors) => changed += 1,
+                Some(_) => {}
+            }
+        }
+        let stale = indexed.keys().filter(|id| !ids.contains(id)).count();
+        (unindexed, changed, stale)
+    } else {
+        (poems.len(), 0, 0)
+    };
+
+    let last_commit = fs::metadata(path.join("meta.json"))
+        .and_then(|m| m.modified())
+        .ok()
+        .map(|t| DateTime::<Local>::from(t).to_rfc3339());
+
+    Ok(IndexStatus {
+        path: path.display().to_string(),
+        poems: searcher.num_docs(),
+        lines,
+        size: dir_size(path)?,
+        segments: searcher.segment_readers().len(),
+        schema_current,
+        tokenizer: tokenizer.tokenizer,
+        user_dict_words: tokenizer
+            .user_dict
+            .iter()
+            .flat_map(|d| d.lines())
+            .filter(|l| !l.trim().is_empty())
+            .count(),
+        last_commit,
+        unindexed,
+        changed,
+        stale,
+    })
+}
+
+/// bytes of the files in `path` and the directories in it
+pub(crate) fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += if meta.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(size)
+}
+
+impl Render for IndexStatus {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        writeln!(f, "路径：{}", self.path)?;
+        writeln!(f, "诗数：{}", self.poems)?;
+        writeln!(f, "句数：{}", self.lines)?;
+        writeln!(f, "大小：{:.1} KiB", self.size as f64 / 1024.0)?;
+        writeln!(f, "段数：{}", self.segments)?;
+        if self.schema_current {
+            writeln!(f, "结构：最新")?;
+        } else {
+            writeln!(f, "结构：过期，需要 `poem index --force` 重建")?;
+        }
+        write!(f, "分词：{}", self.tokenizer)?;
+        if self.user_dict_words > 0 {
+            write!(f, "，用户词典{}个词", self.user_dict_words)?;
+        }
+        writeln!(f)?;
+        if let Some(t) = &self.last_commit {
+            writeln!(f, "提交：{}", t)?;
+        }
+        write!(
+            f,
+            "待添加：{}，待更新：{}，待删除：{}",
+            self.unindexed, self.changed, self.stale
+        )
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Error, Result};
+use once_cell::sync::OnceCell;
+use std::{path::PathBuf, str::FromStr};
+
+static STORAGE: OnceCell<Storage> = OnceCell::new();
+
+/// where the poems of your own and the ones installed with `fetch` are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// json files in the data dir
+    Json,
+    /// a sqlite database, only with the `sqlite` feature
+    Sqlite(PathBuf),
+}
+
+impl FromStr for Storage {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_prefix("sqlite:") {
+            None if s == "json" => Ok(Storage::Json),
+            None => Err(anyhow!("no storage {}, it is json or sqlite:<path>", s)),
+            Some("") => Err(anyhow!("sqlite: needs the path of the database after it")),
+            Some(_) if cfg!(not(feature = "sqlite")) => Err(anyhow!(
+                "poem was built without the sqlite feature, build it with `--features sqlite`"
+            )),
+            Some(path) => Ok(Storage::Sqlite(PathBuf::from(path))),
+        }
+    }
+}
+
+/// keep the poems in `storage` from now on, json files if it is never set,
+/// only the first storage set is used
+pub fn set_storage(storage: Storage) {
+    let _ = STORAGE.set(storage);
+}
+
+/// the database the poems are kept in, none if they are kept in json files
+#[cfg(feature = "sqlite")]
+pub(crate) fn database() -> Option<crate::sqlite::Database> {
+    match STORAGE.get() {
+        Some(Storage::Sqlite(path)) => Some(crate::sqlite::Database::new(path)),
+        Some(Storage::Json) | None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_is_parsed() {
+        assert_eq!("json".parse::<Storage>().unwrap(), Storage::Json);
+        assert!("sqlite:".parse::<Storage>().is_err());
+        assert!("yaml".parse::<Storage>().is_err());
+        let sqlite = "sqlite:poems.db".parse::<Storage>();
+        match cfg!(feature = "sqlite") {
+            true => assert_eq!(sqlite.unwrap(), Storage::Sqlite("poems.db".into())),
+            false => assert!(sqlite.is_err()),
+        }
+    }
+}
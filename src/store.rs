@@ -0,0 +1,75 @@
+use crate::{error::PoemError, fetch, format::Format, Poem};
+use anyhow::{Context, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+const POEMS_STR: &str = include_str!("../poems.json");
+
+/// json files bigger than this are parsed as they are read instead of read into a string first
+const STREAM_BYTES: u64 = 8 * 1024 * 1024;
+
+static STORE: Lazy<PoemStore> = Lazy::new(PoemStore::default);
+
+/// the poems of every source, each parsed at most once and shared by everything in the process
+#[derive(Debug, Default)]
+pub struct PoemStore {
+    bundled: OnceCell<Arc<Vec<Poem>>>,
+    files: Mutex<HashMap<PathBuf, Arc<Vec<Poem>>>>,
+}
+
+impl PoemStore {
+    /// the store of the process
+    pub fn global() -> &'static Self {
+        &STORE
+    }
+
+    /// the bundled poems, or the ones installed with `fetch` in their place
+    pub fn bundled(&self) -> Result<Arc<Vec<Poem>>> {
+        self.bundled
+            .get_or_try_init(|| {
+                let poems = match fetch::installed()? {
+                    Some(poems) => poems,
+                    None => serde_json::from_str(POEMS_STR)?,
+                };
+                Ok(Arc::new(poems))
+            })
+            .cloned()
+    }
+
+    /// the poems of the json, yaml or toml file at `path`, by the extension
+    pub fn file(&self, path: &Path) -> Result<Arc<Vec<Poem>>> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(poems) = self.files.lock().unwrap().get(&key) {
+            return Ok(poems.clone());
+        }
+        let poems = Arc::new(read(path)?);
+        self.files.lock().unwrap().insert(key, poems.clone());
+        Ok(poems)
+    }
+
+    /// like `file` but read again, for a file changed since it was read
+    pub fn reload(&self, path: &Path) -> Result<Arc<Vec<Poem>>> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.files.lock().unwrap().remove(&key);
+        self.file(path)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Poem>> {
+    let file = File::open(path).map_err(PoemError::io(path))?;
+    let format = Format::of(path);
+    // 大文件边读边解析 不用整个读成字符串
+    let poems = if format == Format::Json && file.metadata()?.len() > STREAM_BYTES {
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| PoemError::from(e).into())
+    } else {
+        let s = io::read_to_string(file).map_err(PoemError::io(path))?;
+        format.read(&s)
+    };
+    poems.with_context(|| format!("failed to parse {}", path.display()))
+}
@@ -0,0 +1,113 @@
+use crate::{
+    output::{Render, Style},
+    profile, Poem,
+};
+use anyhow::Result;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+pub(crate) const TAGS_FILE: &str = "tags.json";
+
+/// the tags the user gave poems by id, added to the ones of the dataset when loading
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tags {
+    tags: BTreeMap<String, Vec<String>>,
+}
+
+impl Tags {
+    pub fn load() -> Result<Self> {
+        profile::load(TAGS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        profile::save(TAGS_FILE, self)
+    }
+
+    /// returns false if the poem already has the tag
+    pub fn add(&mut self, id: &str, tag: &str) -> bool {
+        let tags = self.tags.entry(id.to_string()).or_default();
+        if tags.iter().any(|t| t == tag) {
+            return false;
+        }
+        tags.push(tag.to_string());
+        true
+    }
+
+    /// returns false if the poem does not have the tag
+    pub fn remove(&mut self, id: &str, tag: &str) -> bool {
+        let tags = match self.tags.get_mut(id) {
+            Some(tags) => tags,
+            None => return false,
+        };
+        let len = tags.len();
+        tags.retain(|t| t != tag);
+        let removed = tags.len() != len;
+        if tags.is_empty() {
+            self.tags.remove(id);
+        }
+        removed
+    }
+
+    /// give the tags of poem `from` to poem `to`, returns false if `from` has none
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let tags = match self.tags.remove(from) {
+            Some(tags) => tags,
+            None => return false,
+        };
+        for tag in &tags {
+            self.add(to, tag);
+        }
+        true
+    }
+
+    /// add the tags of the user to `poems`
+    pub fn apply(&self, poems: &mut [Poem]) {
+        if self.tags.is_empty() {
+            return;
+        }
+        for poem in poems {
+            if let Some(tags) = self.tags.get(&poem.id()) {
+                for tag in tags {
+                    if !poem.tags.contains(tag) {
+                        poem.tags.push(tag.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// a tag and how many poems have it
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub poems: usize,
+}
+
+/// every tag of `poems`, the most used first
+pub fn tag_counts(poems: &[Poem]) -> Vec<TagCount> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for tag in poems.iter().flat_map(|p| &p.tags) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    let mut counts: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(tag, poems)| TagCount {
+            tag: tag.to_string(),
+            poems,
+        })
+        .collect();
+    counts.sort_by(|a, b| b.poems.cmp(&a.poems).then_with(|| a.tag.cmp(&b.tag)));
+    counts
+}
+
+impl Render for TagCount {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(f, "{}\t{}", self.tag.cyan(), self.poems)
+    }
+}
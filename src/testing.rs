@@ -0,0 +1,15 @@
+use crate::Poem;
+
+/// a poem by 李白 of 唐, the other fields empty
+pub(crate) fn poem(title: &str, content: &str) -> Poem {
+    Poem {
+        title: title.to_string(),
+        author: "李白".to_string(),
+        dynasty: "唐".to_string(),
+        content: content.to_string(),
+        source: None,
+        tags: Vec::new(),
+        translation: None,
+        appreciation: None,
+    }
+}
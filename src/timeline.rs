@@ -0,0 +1,188 @@
+use crate::{
+    output::{Render, Style},
+    Authors, Poem,
+};
+use colored::*;
+use serde::Serialize;
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+/// the dynasties in the order they came, the others come after them by the years of their authors
+const ORDER: &[&str] = &[
+    "先秦",
+    "秦",
+    "两汉",
+    "汉",
+    "三国",
+    "魏晋",
+    "晋",
+    "南北朝",
+    "隋",
+    "唐",
+    "五代",
+    "宋",
+    "辽",
+    "金",
+    "元",
+    "明",
+    "清",
+    "近现代",
+    "现代",
+    "当代",
+];
+
+/// how many columns the bars of the lives take
+const BAR_WIDTH: usize = 30;
+
+/// the authors of a dynasty by when they lived
+#[derive(Debug, Serialize)]
+pub struct Era {
+    pub dynasty: String,
+    /// the first birth and the last death of the authors, none if none of them is known
+    pub start: Option<i32>,
+    pub end: Option<i32>,
+    /// the ones born first first, the ones without years last
+    pub authors: Vec<Life>,
+}
+
+/// an author and the poems by them
+#[derive(Debug, Serialize)]
+pub struct Life {
+    pub name: String,
+    pub birth: Option<i32>,
+    pub death: Option<i32>,
+    pub poems: Vec<Work>,
+}
+
+/// the title of a poem and the id to show it with
+#[derive(Debug, Serialize)]
+pub struct Work {
+    pub id: String,
+    pub title: String,
+}
+
+/// the authors of `poems` laid out by dynasty and by the years in `authors`
+pub fn timeline(poems: &[Poem], authors: &Authors) -> Vec<Era> {
+    let mut lives: HashMap<(&str, &str), Life> = HashMap::new();
+    for poem in poems {
+        let life = lives
+            .entry((&poem.dynasty, &poem.author))
+            .or_insert_with(|| {
+                let author = authors.get(&poem.author, &poem.dynasty);
+                Life {
+                    name: poem.author.clone(),
+                    birth: author.and_then(|a| a.birth),
+                    death: author.and_then(|a| a.death),
+                    poems: Vec::new(),
+                }
+            });
+        life.poems.push(Work {
+            id: poem.id(),
+            title: poem.title.clone(),
+        });
+    }
+
+    let mut eras: HashMap<&str, Vec<Life>> = HashMap::new();
+    for ((dynasty, _), life) in lives {
+        eras.entry(dynasty).or_default().push(life);
+    }
+    let mut eras: Vec<Era> = eras
+        .into_iter()
+        .map(|(dynasty, mut authors)| {
+            authors.sort_by(|a, b| {
+                by_year(a.birth.or(a.death), b.birth.or(b.death)).then_with(|| a.name.cmp(&b.name))
+            });
+            for life in &mut authors {
+                life.poems.sort_by(|a, b| a.title.cmp(&b.title));
+            }
+            Era {
+                dynasty: dynasty.to_string(),
+                start: authors.iter().filter_map(|a| a.birth.or(a.death)).min(),
+                end: authors.iter().filter_map(|a| a.death.or(a.birth)).max(),
+                authors,
+            }
+        })
+        .collect();
+    eras.sort_by(|a, b| {
+        let position = |era: &Era| ORDER.iter().position(|d| *d == era.dynasty);
+        match (position(a), position(b)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => by_year(a.start, b.start).then_with(|| a.dynasty.cmp(&b.dynasty)),
+        }
+    });
+    eras
+}
+
+// 年份不详的排在后面
+fn by_year(a: Option<i32>, b: Option<i32>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// how many columns `s` takes in the terminal, chinese characters take two
+fn width(s: &str) -> usize {
+    s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+fn year(y: Option<i32>) -> String {
+    y.map_or("?".to_string(), |y| y.to_string())
+}
+
+impl Render for Era {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(f, "{}", self.dynasty.bright_cyan().bold())?;
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            write!(f, " {}", format!("{}—{}", start, end).dimmed())?;
+        }
+        let names = self
+            .authors
+            .iter()
+            .map(|a| width(&a.name))
+            .max()
+            .unwrap_or(0);
+        for life in &self.authors {
+            let years = match (life.birth, life.death) {
+                (None, None) => String::new(),
+                (birth, death) => format!("{}—{}", year(birth), year(death)),
+            };
+            write!(
+                f,
+                "\n  {:>9}  {}{:pad$}  ",
+                years,
+                life.name.cyan(),
+                "",
+                pad = names - width(&life.name)
+            )?;
+            write!(f, "{}", self.bar(life).yellow())?;
+            let titles: Vec<&str> = life.poems.iter().map(|w| &w.title[..]).collect();
+            write!(f, "  {}", titles.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Era {
+    /// where the life of `life` is in the years of the era, blank if it is not known
+    fn bar(&self, life: &Life) -> String {
+        let (start, end) = match (self.start, self.end, life.birth, life.death) {
+            (Some(start), Some(end), Some(_), _) | (Some(start), Some(end), _, Some(_)) => {
+                (start, end)
+            }
+            _ => return " ".repeat(BAR_WIDTH),
+        };
+        let span = (end - start).max(1) as f64;
+        let column =
+            |y: i32| (((y - start) as f64 / span) * (BAR_WIDTH - 1) as f64).round() as usize;
+        let from = column(life.birth.or(life.death).unwrap());
+        let to = column(life.death.or(life.birth).unwrap()).max(from);
+        let mut bar = " ".repeat(from);
+        bar.push_str(&"━".repeat(to - from + 1));
+        bar.push_str(&" ".repeat(BAR_WIDTH - to - 1));
+        bar
+    }
+}
@@ -0,0 +1,46 @@
+use once_cell::sync::Lazy;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// how long each stage took, in the order they first ran
+static STAGES: Lazy<Mutex<Vec<(&'static str, Duration)>>> = Lazy::new(Default::default);
+
+/// start the clock of the total in `summary`, the first time a stage is kept otherwise
+pub fn start() {
+    Lazy::force(&START);
+}
+
+/// keep that `stage` took `elapsed`, added to the time it took before if it ran already
+pub fn record(stage: &'static str, elapsed: Duration) {
+    start();
+    log::info!("{} took {:.2?}", stage, elapsed);
+    let mut stages = STAGES.lock().unwrap();
+    match stages.iter_mut().find(|(s, _)| *s == stage) {
+        Some((_, total)) => *total += elapsed,
+        None => stages.push((stage, elapsed)),
+    }
+}
+
+/// run `f` as the stage `stage`
+pub fn time<T>(stage: &'static str, f: impl FnOnce() -> T) -> T {
+    let begin = Instant::now();
+    let value = f();
+    record(stage, begin.elapsed());
+    value
+}
+
+/// one line with the time of each stage and the total since `start`
+pub fn summary() -> String {
+    let mut parts: Vec<String> = STAGES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(stage, elapsed)| format!("{} {:.2?}", stage, elapsed))
+        .collect();
+    parts.push(format!("total {:.2?}", START.elapsed()));
+    format!("timings: {}", parts.join(", "))
+}
@@ -0,0 +1,286 @@
+use crate::{
+    classify::{classify, Form},
+    output::{Render, Style},
+    Poem,
+};
+use anyhow::{anyhow, Result};
+use colored::*;
+use pinyin::ToPinyin;
+use serde::Serialize;
+use std::fmt;
+
+/// the tone class of a character in classical prosody
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Tone {
+    /// 平: the first and second tones
+    Ping,
+    /// 仄: the third and fourth tones
+    Ze,
+}
+
+impl Tone {
+    pub(crate) fn symbol(tone: Option<Tone>) -> char {
+        match tone {
+            Some(Tone::Ping) => '平',
+            Some(Tone::Ze) => '仄',
+            None => '·',
+        }
+    }
+}
+
+/// the tone class of `c` from the tone of its most common reading in modern mandarin,
+/// none for punctuation, non chinese characters and the neutral tone
+///
+/// the 入声 of middle chinese is gone in mandarin, so some 仄 characters come out as 平
+pub fn tone_of(c: char) -> Option<Tone> {
+    match c.to_pinyin()?.with_tone_num_end().chars().last()? {
+        '1' | '2' => Some(Tone::Ping),
+        '3' | '4' => Some(Tone::Ze),
+        _ => None,
+    }
+}
+
+// 五言的四种基本句式 七言在前面加两个和第二字相反的字
+const A: &str = "仄仄平平仄";
+const B: &str = "平平仄仄平";
+const C: &str = "平平平仄仄";
+const D: &str = "仄仄仄平平";
+
+/// the four standard patterns of a 绝句, a 律诗 repeats them twice
+const TEMPLATES: &[(&str, [&str; 4])] = &[
+    ("仄起首句不入韵", [A, B, C, D]),
+    ("仄起首句入韵", [D, B, C, D]),
+    ("平起首句不入韵", [C, D, A, B]),
+    ("平起首句入韵", [B, D, A, B]),
+];
+
+/// the tones of every line of a poem, checked against the closest template if asked to
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub title: String,
+    /// the name of the template checked against
+    pub template: Option<String>,
+    pub lines: Vec<ToneLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToneLine {
+    pub text: String,
+    pub tones: Vec<Option<Tone>>,
+    /// the tones the template asks for
+    pub expected: Option<String>,
+    /// the indexes of the characters breaking the template
+    pub violations: Vec<usize>,
+}
+
+impl Report {
+    /// the tones of `poem`, with `check` the poem must be a 绝句 or 律诗 and every line
+    /// is compared with the template it breaks least
+    pub fn new(poem: &Poem, check: bool) -> Result<Self> {
+        let mut lines: Vec<ToneLine> = poem
+            .lines()
+            .into_iter()
+            .map(|l| ToneLine {
+                text: l.to_string(),
+                tones: l.chars().map(tone_of).collect(),
+                expected: None,
+                violations: Vec::new(),
+            })
+            .collect();
+        let mut template = None;
+        if check {
+            let form = classify(poem);
+            let seven = match form {
+                Form::Wujue | Form::Wulv => false,
+                Form::Qijue | Form::Qilv => true,
+                _ => {
+                    return Err(anyhow!(
+                        "only 绝句 and 律诗 can be checked, this is {}",
+                        form
+                    ))
+                }
+            };
+            let (name, patterns) = TEMPLATES
+                .iter()
+                .map(|(name, patterns)| {
+                    let patterns: Vec<String> = patterns
+                        .iter()
+                        .cycle()
+                        .take(lines.len())
+                        .map(|p| if seven { extend(p) } else { p.to_string() })
+                        .collect();
+                    let count: usize = lines
+                        .iter()
+                        .zip(&patterns)
+                        .map(|(l, p)| violations(&l.tones, p).len())
+                        .sum();
+                    (count, name, patterns)
+                })
+                .min_by_key(|(count, _, _)| *count)
+                .map(|(_, name, patterns)| (name, patterns))
+                .unwrap();
+            for (line, pattern) in lines.iter_mut().zip(patterns) {
+                line.violations = violations(&line.tones, &pattern);
+                line.expected = Some(pattern);
+            }
+            template = Some(format!("{} {}", form, name));
+        }
+        Ok(Self {
+            title: poem.title.clone(),
+            template,
+            lines,
+        })
+    }
+}
+
+/// the seven character pattern of a five character one
+fn extend(pattern: &str) -> String {
+    let prefix = match pattern.chars().nth(1) {
+        Some('平') => "仄仄",
+        _ => "平平",
+    };
+    format!("{}{}", prefix, pattern)
+}
+
+/// the characters of `tones` not following `pattern`, the odd ones but the last may be either
+/// as the saying 一三五不论 goes
+fn violations(tones: &[Option<Tone>], pattern: &str) -> Vec<usize> {
+    let len = tones.len();
+    tones
+        .iter()
+        .zip(pattern.chars())
+        .enumerate()
+        .filter(|(i, _)| is_fixed(*i, len))
+        .filter(|(_, (tone, expected))| tone.is_some_and(|t| Tone::symbol(Some(t)) != *expected))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// whether the tone of the character at `i` of a line of `len` characters matters,
+/// the even ones and the last
+pub(crate) fn is_fixed(i: usize, len: usize) -> bool {
+    i % 2 == 1 || i + 1 == len
+}
+
+impl Render for Report {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(f, "{}", self.title.bright_cyan())?;
+        if let Some(template) = &self.template {
+            write!(f, " {}", template.dimmed())?;
+        }
+        for line in &self.lines {
+            writeln!(f)?;
+            writeln!(f, "{}", line.text.cyan())?;
+            for (i, tone) in line.tones.iter().enumerate() {
+                let symbol = Tone::symbol(*tone).to_string();
+                if line.violations.contains(&i) {
+                    write!(f, "{}", symbol.red().bold())?;
+                } else {
+                    write!(f, "{}", symbol)?;
+                }
+            }
+            if let Some(expected) = &line.expected {
+                write!(f, "  {}", expected.dimmed())?;
+            }
+        }
+        if self.template.is_some() {
+            let count: usize = self.lines.iter().map(|l| l.violations.len()).sum();
+            write!(f, "\n不合律：{}处", count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::poem;
+
+    const DENG_GUAN_QUE_LOU: &str = "白日依山尽，黄河入海流。欲穷千里目，更上一层楼。";
+    const DENG_GAO: &str = "风急天高猿啸哀，渚清沙白鸟飞回。无边落木萧萧下，不尽长江滚滚来。\
+                            万里悲秋常作客，百年多病独登台。艰难苦恨繁霜鬓，潦倒新停浊酒杯。";
+
+    fn symbols(line: &ToneLine) -> String {
+        line.tones.iter().map(|t| Tone::symbol(*t)).collect()
+    }
+
+    #[test]
+    fn tones_of_characters() {
+        for (c, tone) in [
+            ('山', Some(Tone::Ping)),
+            ('河', Some(Tone::Ping)),
+            ('海', Some(Tone::Ze)),
+            ('月', Some(Tone::Ze)),
+            // 入声字在普通话里读成了平声
+            ('白', Some(Tone::Ping)),
+            ('，', None),
+            ('a', None),
+        ] {
+            assert_eq!(tone_of(c), tone, "{}", c);
+        }
+    }
+
+    #[test]
+    fn a_five_character_quatrain_follows_its_template() {
+        let report = Report::new(&poem("登鹳雀楼", DENG_GUAN_QUE_LOU), true).unwrap();
+        assert_eq!(report.template.as_deref(), Some("五言绝句 仄起首句不入韵"));
+        let tones: Vec<String> = report.lines.iter().map(symbols).collect();
+        assert_eq!(
+            tones,
+            ["平仄平平仄", "平平仄仄平", "仄平平仄仄", "仄仄平平平"]
+        );
+        let expected: Vec<&str> = report
+            .lines
+            .iter()
+            .map(|l| l.expected.as_deref().unwrap())
+            .collect();
+        assert_eq!(expected, [A, B, C, D]);
+        // 一三五不论
+        assert!(report.lines.iter().all(|l| l.violations.is_empty()));
+    }
+
+    #[test]
+    fn a_seven_character_regulated_verse_is_checked() {
+        let report = Report::new(&poem("登高", DENG_GAO), true).unwrap();
+        let template = report.template.unwrap();
+        assert!(template.starts_with("七言律诗 "), "{}", template);
+        assert_eq!(report.lines.len(), 8);
+        assert!(report
+            .lines
+            .iter()
+            .all(|l| l.expected.as_ref().unwrap().chars().count() == 7));
+        // 急是入声 普通话读平声 算作不合
+        assert_eq!(symbols(&report.lines[0]), "平平平平平仄平");
+        assert!(report.lines[0].violations.contains(&1));
+    }
+
+    #[test]
+    fn only_quatrains_and_regulated_verses_are_checked() {
+        let poem = poem("三句", "床前明月光，疑是地上霜。举头望明月。");
+        assert!(Report::new(&poem, true).is_err());
+        let report = Report::new(&poem, false).unwrap();
+        assert_eq!(report.template, None);
+        assert_eq!(report.lines.len(), 3);
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(extend(A), "平平仄仄平平仄");
+        assert_eq!(extend(B), "仄仄平平仄仄平");
+        let tones = |symbols: &str| -> Vec<Option<Tone>> {
+            symbols
+                .chars()
+                .map(|c| match c {
+                    '平' => Some(Tone::Ping),
+                    '仄' => Some(Tone::Ze),
+                    _ => None,
+                })
+                .collect()
+        };
+        // 第一三字不论 第二四字和韵脚要合
+        assert_eq!(violations(&tones("平平平仄平"), A), [1, 3, 4]);
+        assert!(violations(&tones("仄平仄仄平"), B).is_empty());
+        // 读不出声调的字不算错
+        assert_eq!(violations(&tones("平·平仄平"), A), [3, 4]);
+    }
+}
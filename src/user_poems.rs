@@ -0,0 +1,152 @@
+use crate::{profile, Poem, PoemFilter};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub(crate) const USER_POEMS_FILE: &str = "poems.json";
+
+/// poems added by the user, merged with the bundled ones when loading
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserPoems {
+    poems: Vec<Poem>,
+}
+
+impl UserPoems {
+    pub fn load() -> Result<Self> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            let poems = database.load(crate::sqlite::Kind::User)?;
+            return Ok(Self { poems });
+        }
+        profile::load(USER_POEMS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            return database.replace(crate::sqlite::Kind::User, &self.poems);
+        }
+        profile::save(USER_POEMS_FILE, self)
+    }
+
+    /// add the poems not saved yet to the saved ones, returns them
+    pub fn insert(poems: Vec<Poem>) -> Result<Vec<Poem>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            return database.insert(crate::sqlite::Kind::User, poems);
+        }
+        let mut user_poems = Self::load()?;
+        let added = user_poems.extend(poems);
+        user_poems.save()?;
+        Ok(added)
+    }
+
+    /// remove the saved poems with `ids`, returns the ones there were
+    pub fn delete(ids: &[String]) -> Result<Vec<Poem>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            return database.delete(crate::sqlite::Kind::User, ids);
+        }
+        let mut user_poems = Self::load()?;
+        let removed: Vec<Poem> = ids.iter().filter_map(|id| user_poems.remove(id)).collect();
+        user_poems.save()?;
+        Ok(removed)
+    }
+
+    /// put `poem` in place of the saved poem with `id`, returns false if there is no such poem
+    pub fn update(id: &str, poem: &Poem) -> Result<bool> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            return database.update(crate::sqlite::Kind::User, id, poem);
+        }
+        let mut user_poems = Self::load()?;
+        match user_poems.poems.iter_mut().find(|p| p.id() == id) {
+            Some(old) => *old = poem.clone(),
+            None => return Ok(false),
+        }
+        user_poems.save()?;
+        Ok(true)
+    }
+
+    /// the saved poem with `id`, none if there is no such poem
+    pub fn find(id: &str) -> Result<Option<Poem>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            return database.get(crate::sqlite::Kind::User, id);
+        }
+        Ok(Self::load()?.get(id).cloned())
+    }
+
+    /// the saved poems of the dynasty, by the author and with the title of `filter`,
+    /// the tags and the shape are left to the caller
+    pub fn matching(filter: &PoemFilter) -> Result<Vec<Poem>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = crate::storage::database() {
+            return database.matching(crate::sqlite::Kind::User, filter);
+        }
+        let mut poems = Self::load()?.into_poems();
+        poems.retain(|p| filter.matches_columns(p));
+        Ok(poems)
+    }
+
+    /// returns false if a poem with the same id is already there
+    pub fn add(&mut self, poem: Poem) -> bool {
+        // 和extend一样按id去重 标签或者译文不同也算同一首
+        if self.poems.iter().any(|p| p.raw_id() == poem.raw_id()) {
+            return false;
+        }
+        self.poems.push(poem);
+        true
+    }
+
+    /// add the poems not there yet, returns them
+    pub fn extend(&mut self, poems: Vec<Poem>) -> Vec<Poem> {
+        let mut ids: HashSet<u64> = self.poems.iter().map(Poem::raw_id).collect();
+        let added: Vec<Poem> = poems
+            .into_iter()
+            .filter(|p| ids.insert(p.raw_id()))
+            .collect();
+        self.poems.extend(added.iter().cloned());
+        added
+    }
+
+    /// the removed poem, none if there is no such poem
+    pub fn remove(&mut self, id: &str) -> Option<Poem> {
+        let i = self.poems.iter().position(|p| p.id() == id)?;
+        Some(self.poems.remove(i))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Poem> {
+        self.poems.iter().find(|p| p.id() == id)
+    }
+
+    pub fn poems(&self) -> &[Poem] {
+        &self.poems
+    }
+
+    pub fn into_poems(self) -> Vec<Poem> {
+        self.poems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn poem(translation: Option<&str>) -> Poem {
+        let mut poem = testing::poem("静夜思", "床前明月光，疑是地上霜。");
+        poem.translation = translation.map(str::to_string);
+        poem
+    }
+
+    #[test]
+    fn poems_are_added_once_by_id() {
+        let mut user_poems = UserPoems::default();
+        assert!(user_poems.add(poem(None)));
+        assert!(!user_poems.add(poem(Some("明亮的月光"))));
+        assert!(user_poems.extend(vec![poem(Some("明亮的月光"))]).is_empty());
+        assert_eq!(user_poems.poems(), [poem(None)]);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    output::{Render, Style},
+    Poem,
+};
+use colored::*;
+use serde::Serialize;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+};
+
+/// what is wrong with a poem or the file of the poems
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Problem {
+    /// bytes that are not utf-8
+    Encoding,
+    Empty,
+    /// the same title and author as a poem before
+    Duplicate,
+    Control,
+    /// spaces around a field or a line, or inside a line
+    Whitespace,
+    /// characters that are neither chinese nor punctuation
+    Garbage,
+}
+
+impl Problem {
+    fn name(self) -> &'static str {
+        match self {
+            Problem::Encoding => "encoding",
+            Problem::Empty => "empty",
+            Problem::Duplicate => "duplicate",
+            Problem::Control => "control",
+            Problem::Whitespace => "whitespace",
+            Problem::Garbage => "garbage",
+        }
+    }
+}
+
+/// a problem found by `validate` and where it is
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub problem: Problem,
+    /// the number of the poem from 1, none for problems of the file
+    pub poem: Option<usize>,
+    pub title: Option<String>,
+    pub field: Option<&'static str>,
+    /// the line from 1, of the file for encoding problems and of the field otherwise
+    pub line: Option<usize>,
+    /// the character in the line from 1, the byte for encoding problems
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// the text of `bytes` with what is not utf-8 replaced by `�`, and where it was
+pub fn check_encoding(bytes: &[u8]) -> (String, Vec<Issue>) {
+    let mut issues = Vec::new();
+    let mut offset = 0;
+    while let Err(e) = std::str::from_utf8(&bytes[offset..]) {
+        let position = offset + e.valid_up_to();
+        let before = &bytes[..position];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let len = e.error_len().unwrap_or(bytes.len() - position);
+        issues.push(Issue {
+            problem: Problem::Encoding,
+            poem: None,
+            title: None,
+            field: None,
+            line: Some(before.iter().filter(|&&b| b == b'\n').count() + 1),
+            column: Some(position - line_start + 1),
+            message: format!("invalid utf-8 {:02x?}", &bytes[position..position + len]),
+        });
+        offset = position + len;
+    }
+    (String::from_utf8_lossy(bytes).into_owned(), issues)
+}
+
+/// the problems of `poems`, numbered from 1 in order
+pub fn validate(poems: &[Poem]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut seen: HashMap<(&str, &str), usize> = HashMap::new();
+    for (i, poem) in poems.iter().enumerate() {
+        let number = i + 1;
+        let mut report = |problem, field, line: Option<usize>, column, message: String| {
+            issues.push(Issue {
+                problem,
+                poem: Some(number),
+                title: Some(poem.title.clone()),
+                field: Some(field),
+                line,
+                column,
+                message,
+            })
+        };
+        let fields = [
+            ("title", &poem.title),
+            ("author", &poem.author),
+            ("dynasty", &poem.dynasty),
+            ("content", &poem.content),
+        ];
+        for (field, value) in fields {
+            if value.trim().is_empty() {
+                report(Problem::Empty, field, None, None, format!("no {}", field));
+                continue;
+            }
+            // 只有正文分行
+            let lines: Vec<&str> = if field == "content" {
+                value.split('\n').collect()
+            } else {
+                vec![value]
+            };
+            let line_of = |n: usize| (field == "content").then_some(n + 1);
+            for (n, line) in lines.iter().enumerate() {
+                for (column, c) in line.chars().enumerate() {
+                    if c.is_control() {
+                        report(
+                            Problem::Control,
+                            field,
+                            line_of(n),
+                            Some(column + 1),
+                            format!("control character {:?}", c),
+                        );
+                    }
+                }
+                check_whitespace(line, |column, message| {
+                    report(Problem::Whitespace, field, line_of(n), column, message)
+                });
+                if field == "content" {
+                    for (column, run) in garbage(line) {
+                        report(
+                            Problem::Garbage,
+                            field,
+                            line_of(n),
+                            Some(column),
+                            format!("`{}` is not chinese", run),
+                        );
+                    }
+                }
+            }
+        }
+        match seen.entry((poem.title.trim(), poem.author.trim())) {
+            Entry::Occupied(first) => report(
+                Problem::Duplicate,
+                "title",
+                None,
+                None,
+                format!("the same title and author as poem {}", first.get()),
+            ),
+            Entry::Vacant(entry) => {
+                entry.insert(number);
+            }
+        }
+    }
+    issues
+}
+
+fn check_whitespace(line: &str, mut report: impl FnMut(Option<usize>, String)) {
+    let is_space = |c: char| c.is_whitespace() && !c.is_control();
+    if line.starts_with(is_space) {
+        report(Some(1), "leading whitespace".to_string());
+    }
+    if line.ends_with(is_space) {
+        report(
+            Some(line.trim_end_matches(is_space).chars().count() + 1),
+            "trailing whitespace".to_string(),
+        );
+    }
+    let inner = line.trim_matches(is_space);
+    let start = line.chars().take_while(|&c| is_space(c)).count();
+    if let Some(i) = inner.chars().position(is_space) {
+        let c = inner.chars().nth(i).unwrap();
+        report(Some(start + i + 1), format!("whitespace {:?} inside", c));
+    }
+}
+
+// 空白和控制字符另外检查
+fn is_expected(c: char) -> bool {
+    matches!(c,
+        '\u{4e00}'..='\u{9fff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{20000}'..='\u{2fa1f}'
+        | '\u{f900}'..='\u{faff}'
+        // 〇和中文标点
+        | '\u{3000}'..='\u{303f}'
+        | '\u{2000}'..='\u{206f}'
+        | '\u{ff01}'..='\u{ff0f}'
+        | '\u{ff1a}'..='\u{ff20}'
+        | '\u{ff3b}'..='\u{ff40}'
+        | '\u{ff5b}'..='\u{ff65}'
+        // 缺字用□标出
+        | '·' | '□'
+    ) || c.is_ascii_punctuation()
+        || c.is_whitespace()
+        || c.is_control()
+}
+
+/// the runs of characters of `line` that are not chinese, with the column they start at
+fn garbage(line: &str) -> Vec<(usize, String)> {
+    let mut runs: Vec<(usize, String)> = Vec::new();
+    let mut last = None;
+    for (i, c) in line.chars().enumerate() {
+        if is_expected(c) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((_, run)) if last.map(|l| l + 1) == Some(i) => run.push(c),
+            _ => runs.push((i + 1, c.to_string())),
+        }
+        last = Some(i);
+    }
+    runs
+}
+
+impl Render for Issue {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        match (self.poem, &self.title) {
+            (Some(poem), Some(title)) => write!(f, "poem {} 《{}》", poem, title)?,
+            _ => write!(f, "file")?,
+        }
+        if let Some(field) = self.field {
+            write!(f, " {}", field)?;
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, " {}:{}", line, column)?,
+            (Some(line), None) => write!(f, " {}", line)?,
+            (None, Some(column)) => write!(f, " :{}", column)?,
+            (None, None) => {}
+        }
+        let problem = match self.problem {
+            Problem::Encoding | Problem::Empty | Problem::Control => self.problem.name().red(),
+            _ => self.problem.name().yellow(),
+        };
+        write!(f, " {}: {}", problem, self.message)
+    }
+}
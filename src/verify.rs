@@ -0,0 +1,214 @@
+use crate::{
+    index::{fields, indexed_ids, schema, TOKENIZER_FILE},
+    lines,
+    output::{Render, Style},
+    read_only::ReadOnlyDirectory,
+    Poem, PoemError, TokenizerConfig,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::{collections::HashSet, fmt, fs, path::Path};
+use tantivy::{schema::Schema, Index, LeasedItem, Searcher, TantivyError};
+
+/// what is wrong with an index
+#[derive(Debug, Serialize)]
+pub struct Verification {
+    pub path: String,
+    /// what is wrong with the files of the index, it has to be rebuilt if there is anything
+    pub damage: Vec<String>,
+    /// how many poems are indexed, none if the index can not be read
+    pub poems: Option<u64>,
+    /// poems of the dataset not in the index
+    pub unindexed: usize,
+    /// documents of poems no longer in the dataset
+    pub stale: usize,
+    /// documents of poems indexed more than once
+    pub duplicates: usize,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.damage.is_empty() && self.unindexed == 0 && self.stale == 0 && self.duplicates == 0
+    }
+}
+
+/// check that every file of the index in `path` and its line index can be read, that they have
+/// the current schema, and that the poems in the index are `poems`
+pub fn verify_index(path: impl AsRef<Path>, poems: &[Poem]) -> Result<Verification> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(PoemError::IndexMissing(path.to_path_buf()).into());
+    }
+    let mut damage = Vec::new();
+    let tokenizer_path = path.join(TOKENIZER_FILE);
+    if tokenizer_path.exists() {
+        let parsed = fs::read_to_string(&tokenizer_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| Ok(serde_json::from_str::<TokenizerConfig>(&s)?));
+        if let Err(e) = parsed {
+            damage.push(format!("{} can not be read: {}", TOKENIZER_FILE, e));
+        }
+    }
+
+    let searcher = check(path, schema(), "poem index", &mut damage);
+    check(
+        &lines::index_path(path),
+        lines::schema(),
+        "line index",
+        &mut damage,
+    );
+
+    let (mut unindexed, mut stale, mut duplicates) = (0, 0, 0);
+    if let Some(searcher) = &searcher {
+        match indexed_ids(searcher.index(), fields()["id"]) {
+            Ok(indexed) => {
+                let ids: HashSet<u64> = poems.iter().map(Poem::raw_id).collect();
+                unindexed = ids.difference(&indexed).count();
+                stale = indexed.difference(&ids).count();
+                duplicates = searcher.num_docs() as usize - indexed.len();
+            }
+            Err(e) => damage.push(format!("the ids of the poem index can not be read: {}", e)),
+        }
+    }
+
+    Ok(Verification {
+        path: path.display().to_string(),
+        damage,
+        poems: searcher.map(|s| s.num_docs()),
+        unindexed,
+        stale,
+        duplicates,
+    })
+}
+
+/// the searcher of the index in `path` if every document in it can be read and it has `schema`,
+/// what is wrong with it goes to `damage`
+fn check(
+    path: &Path,
+    schema: &Schema,
+    name: &str,
+    damage: &mut Vec<String>,
+) -> Option<LeasedItem<Searcher>> {
+    let problems = damage.len();
+    if !path.exists() {
+        damage.push(format!("the {} is missing", name));
+        return None;
+    }
+    // 只读存储的字段 不用注册分词器
+    let opened = ReadOnlyDirectory::open(path)
+        .map_err(TantivyError::from)
+        .and_then(Index::open);
+    let index = match opened {
+        Ok(index) => index,
+        Err(e) => {
+            damage.push(format!("the {} can not be opened: {}", name, e));
+            return None;
+        }
+    };
+    if index.schema() != *schema {
+        damage.push(format!("the {} was built with a different schema", name));
+    }
+    check_files(&index, path, name, damage);
+    if damage.len() > problems {
+        return None;
+    }
+    let searcher = match index.reader() {
+        Ok(reader) => reader.searcher(),
+        Err(e) => {
+            damage.push(format!("the {} can not be read: {}", name, e));
+            return None;
+        }
+    };
+    // 每个文档都读一遍 读不出来的段整个算坏的
+    for segment in searcher.segment_readers() {
+        let readable = segment
+            .get_store_reader()
+            .map_err(tantivy::TantivyError::from)
+            .and_then(|store| {
+                segment
+                    .doc_ids_alive()
+                    .try_for_each(|doc| store.get(doc).map(drop))
+            });
+        if let Err(e) = readable {
+            damage.push(format!(
+                "segment {} of the {} can not be read: {}",
+                segment.segment_id().short_uuid_string(),
+                name,
+                e
+            ));
+        }
+    }
+    (damage.len() == problems).then_some(searcher)
+}
+
+// 断电时文件可能没写完或者根本没写
+fn check_files(index: &Index, path: &Path, name: &str, damage: &mut Vec<String>) {
+    let metas = match index.searchable_segment_metas() {
+        Ok(metas) => metas,
+        Err(e) => {
+            damage.push(format!(
+                "the segments of the {} can not be listed: {}",
+                name, e
+            ));
+            return;
+        }
+    };
+    // 没有删除过文档的段也会列出.del文件
+    let mut missing: Vec<String> = metas
+        .iter()
+        .flat_map(|m| {
+            m.list_files()
+                .into_iter()
+                .filter(move |f| m.has_deletes() || f.extension().is_none_or(|e| e != "del"))
+        })
+        .filter(|f| !path.join(f).exists())
+        .map(|f| f.display().to_string())
+        .collect();
+    missing.sort();
+    if !missing.is_empty() {
+        damage.extend(
+            missing
+                .into_iter()
+                .map(|f| format!("{} of the {} is missing", f, name)),
+        );
+        return;
+    }
+    match index.validate_checksum() {
+        Ok(damaged) => {
+            let mut damaged: Vec<String> =
+                damaged.iter().map(|f| f.display().to_string()).collect();
+            damaged.sort();
+            damage.extend(
+                damaged
+                    .into_iter()
+                    .map(|f| format!("{} of the {} is damaged", f, name)),
+            );
+        }
+        Err(e) => damage.push(format!(
+            "the files of the {} can not be checked: {}",
+            name, e
+        )),
+    }
+}
+
+impl Render for Verification {
+    fn render(&self, f: &mut fmt::Formatter<'_>, _: &Style) -> fmt::Result {
+        write!(f, "路径：{}", self.path)?;
+        if let Some(poems) = self.poems {
+            write!(f, "\n诗数：{}", poems)?;
+        }
+        for damage in &self.damage {
+            write!(f, "\n损坏：{}", damage)?;
+        }
+        if self.unindexed > 0 || self.stale > 0 {
+            write!(f, "\n待添加：{}，待删除：{}", self.unindexed, self.stale)?;
+        }
+        if self.duplicates > 0 {
+            write!(f, "\n重复：{}", self.duplicates)?;
+        }
+        if self.is_ok() {
+            write!(f, "\n索引完好")?;
+        }
+        Ok(())
+    }
+}
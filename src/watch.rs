@@ -0,0 +1,102 @@
+use crate::PoemError;
+use anyhow::Result;
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// how often the file is looked at
+const POLL: Duration = Duration::from_millis(500);
+
+/// when the file was last changed and how big it is, none while it is not there
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// call `on_change` every time the file at `path` is changed, until the process is stopped
+///
+/// the file is looked at twice a second, a change is only taken once the file stays the same
+/// for a look so an editor is done writing it, and a file that is gone for a while, like when an
+/// editor saves by moving a new file in its place, is waited for
+pub fn watch(path: &Path, mut on_change: impl FnMut()) -> Result<()> {
+    let mut last = stamp(path);
+    if last.is_none() {
+        fs::metadata(path).map_err(PoemError::io(path))?;
+    }
+    loop {
+        thread::sleep(POLL);
+        let mut now = stamp(path);
+        if now == last {
+            continue;
+        }
+        // 编辑器可能分几次写完 等到不再变了再索引
+        loop {
+            thread::sleep(POLL);
+            let again = stamp(path);
+            if again == now {
+                break;
+            }
+            now = again;
+        }
+        last = now;
+        if last.is_some() {
+            log::debug!("{} changed", path.display());
+            on_change();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_index, testing::poem, Authors, Indexed, PoemStore};
+    use indicatif::ProgressBar;
+    use std::sync::mpsc;
+
+    #[test]
+    fn edits_are_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("poems.json");
+        let index = dir.path().join("index");
+        let mut poem = poem("静夜思", "床前明月光，疑是地上霜。");
+        fs::write(&file, serde_json::to_string(&[&poem]).unwrap()).unwrap();
+        let poems = PoemStore::global().file(&file).unwrap().to_vec();
+        let bar = ProgressBar::hidden();
+        build_index(&index, poems, &Authors::default(), false, None, None, &bar).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let (watched, indexed) = (file.clone(), index.clone());
+        thread::spawn(move || {
+            watch(&watched, || {
+                let poems = PoemStore::global().reload(&watched).unwrap().to_vec();
+                let counts = build_index(
+                    &indexed,
+                    poems,
+                    &Authors::default(),
+                    false,
+                    None,
+                    None,
+                    &bar,
+                );
+                sender.send(counts.unwrap()).unwrap();
+            })
+        });
+        // 等监视开始 不然改动之前的状态就已经是改过的了
+        thread::sleep(POLL * 2);
+        poem.translation = Some("床前洒满了明亮的月光".to_string());
+        fs::write(&file, serde_json::to_string(&[&poem]).unwrap()).unwrap();
+
+        let counts = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(
+            counts,
+            Indexed {
+                added: 0,
+                updated: 1,
+                removed: 0
+            }
+        );
+    }
+}